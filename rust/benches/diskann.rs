@@ -0,0 +1,166 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Searches over an in-memory DiskANN graph, one query at a time, one query at a
+//! time reusing the search buffers, and in a batch.
+//!
+//! The number of allocations of each mode is printed before the benchmarks.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use arrow_array::{FixedSizeListArray, RecordBatch, RecordBatchReader};
+use arrow_schema::{DataType, Field, Schema as ArrowSchema};
+use criterion::{criterion_group, criterion_main, Criterion};
+#[cfg(target_os = "linux")]
+use pprof::criterion::{Output, PProfProfiler};
+
+use lance::arrow::{linalg::MatrixView, *};
+use lance::dataset::Dataset;
+use lance::index::vector::diskann::{DiskANNGraph, DiskANNParams, SearchParams, SearchScratch};
+use lance::index::vector::SearchIndex;
+use lance::utils::testing::generate_random_array;
+
+/// Counts the allocations of the benchmark process.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const DIMENSION: usize = 64;
+const NUM_QUERIES: usize = 100;
+
+fn bench_search(c: &mut Criterion) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let uri = tmp_dir.path().to_str().unwrap();
+    let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+        "vector",
+        DataType::FixedSizeList(
+            Arc::new(Field::new("item", DataType::Float32, true)),
+            DIMENSION as i32,
+        ),
+        true,
+    )]));
+    let values = generate_random_array(10_000 * DIMENSION);
+    let batches = RecordBatchBuffer::new(vec![RecordBatch::try_new(
+        schema,
+        vec![Arc::new(
+            FixedSizeListArray::try_new(&values, DIMENSION as i32).unwrap(),
+        )],
+    )
+    .unwrap()]);
+    let mut reader: Box<dyn RecordBatchReader> = Box::new(batches);
+    let index = rt.block_on(async {
+        Dataset::write(&mut reader, uri, None).await.unwrap();
+        let dataset = Dataset::open(uri).await.unwrap();
+        let mut params = DiskANNParams::default();
+        params.r(32).l(64);
+        DiskANNGraph::build(&dataset, "vector", &params)
+            .await
+            .unwrap()
+    });
+
+    let queries = MatrixView::random(NUM_QUERIES, DIMENSION);
+    let params = SearchParams::new(64);
+    let search_one_by_one = || {
+        rt.block_on(async {
+            for i in 0..NUM_QUERIES {
+                index
+                    .search(queries.row(i).unwrap(), 10, &params)
+                    .await
+                    .unwrap();
+            }
+        })
+    };
+    let search_with_scratch = || {
+        let mut scratch = SearchScratch::new();
+        for i in 0..NUM_QUERIES {
+            index
+                .search_with_scratch(queries.row(i).unwrap(), 10, &params, &mut scratch)
+                .unwrap();
+        }
+    };
+    let search_batch = || index.search_batch(&queries, 10, &params).unwrap();
+
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    search_one_by_one();
+    let one_by_one = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    search_with_scratch();
+    let with_scratch = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    search_batch();
+    let batch = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    println!(
+        "Allocations per query: one by one {}, with scratch {}, batch {}",
+        one_by_one / NUM_QUERIES,
+        with_scratch / NUM_QUERIES,
+        batch / NUM_QUERIES
+    );
+
+    c.bench_function(
+        format!("DiskANN_search(d={},queries={})", DIMENSION, NUM_QUERIES).as_str(),
+        |b| b.iter(search_one_by_one),
+    );
+    c.bench_function(
+        format!(
+            "DiskANN_search_with_scratch(d={},queries={})",
+            DIMENSION, NUM_QUERIES
+        )
+        .as_str(),
+        |b| b.iter(search_with_scratch),
+    );
+    c.bench_function(
+        format!(
+            "DiskANN_search_batch(d={},queries={})",
+            DIMENSION, NUM_QUERIES
+        )
+        .as_str(),
+        |b| b.iter(search_batch),
+    );
+}
+
+#[cfg(target_os = "linux")]
+criterion_group!(
+    name=benches;
+    config = Criterion::default().significance_level(0.1).sample_size(10)
+        .with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
+    targets = bench_search);
+
+#[cfg(not(target_os = "linux"))]
+criterion_group!(
+    name=benches;
+    config = Criterion::default().significance_level(0.1).sample_size(10);
+    targets = bench_search);
+
+criterion_main!(benches);
@@ -47,7 +47,9 @@ where
 
 /// Argmin on a [PrimitiveArray].
 ///
-/// Returns the index of the min value in the array.
+/// Returns the index of the min value in the array. Nulls are greater than any
+/// value. Among equal min values, the lowest index is returned, so the result
+/// does not depend on the order of the comparisons.
 pub fn argmin<T: ArrowNumericType>(array: &PrimitiveArray<T>) -> Option<u32>
 where
     T::Native: PartialOrd,
@@ -55,10 +57,12 @@ where
     array
         .iter()
         .enumerate()
-        .max_by(|(_, x), (_, y)| match (x, y) {
+        // `min_by` returns the first of the equal minimums.
+        .min_by(|(_, x), (_, y)| match (x, y) {
+            (None, None) => Ordering::Equal,
             (None, _) => Ordering::Greater,
             (Some(_), None) => Ordering::Less,
-            (Some(vx), Some(vy)) => vy.partial_cmp(vx).unwrap(),
+            (Some(vx), Some(vy)) => vx.partial_cmp(vy).unwrap(),
         })
         .map(|(idx, _)| idx as u32)
 }
@@ -153,7 +157,13 @@ mod tests {
 
         let empty_vec: Vec<i16> = vec![];
         let emtpy = Int16Array::from(empty_vec);
-        assert_eq!(argmin(&emtpy), None)
+        assert_eq!(argmin(&emtpy), None);
+
+        // Ties go to the lowest index.
+        let tied = Float32Array::from_iter(vec![5.0, 1.0, 3.0, 1.0, 1.0]);
+        assert_eq!(argmin(&tied), Some(1));
+        let nulls = Float32Array::from(vec![None, Some(2.0), None, Some(2.0)]);
+        assert_eq!(argmin(&nulls), Some(1));
     }
 
     #[test]
@@ -45,6 +45,14 @@ fn transpose(input: &[f32], dimension: usize) -> Vec<f32> {
     mat
 }
 
+/// A builder holding a copy of `values`, with room for `additional` values and as
+/// many more, to append in place after.
+fn builder_with_values(values: &[f32], additional: usize) -> Float32Builder {
+    let mut builder = Float32Builder::with_capacity((values.len() + additional) * 2);
+    builder.append_slice(values);
+    builder
+}
+
 /// A 2-D matrix view on top of Arrow Arrays.
 ///
 #[derive(Debug, Clone)]
@@ -141,6 +149,41 @@ impl MatrixView {
         }
     }
 
+    /// Append the rows of `other` to this matrix.
+    ///
+    /// The values are appended in place if this matrix holds the only reference to
+    /// them, with a capacity growing geometrically: appending `n` rows batch by batch
+    /// copies `O(n)` values overall. Otherwise, e.g., after a clone, they are copied.
+    ///
+    /// # Panics if either matrix is transposed.
+    pub fn append(&mut self, other: &Self) -> Result<()> {
+        assert!(
+            !self.transpose && !other.transpose,
+            "Does not support appending transposed matrices"
+        );
+        if other.num_columns != self.num_columns {
+            return Err(Error::Arrow(format!(
+                "Append dimension mismatch: {} columns to a matrix of {} columns",
+                other.num_columns, self.num_columns
+            )));
+        }
+
+        let data = std::mem::replace(
+            &mut self.data,
+            Arc::new(Float32Array::from(Vec::<f32>::new())),
+        );
+        let additional = other.data.len();
+        let mut builder = match Arc::try_unwrap(data) {
+            Ok(array) => array
+                .into_builder()
+                .unwrap_or_else(|array| builder_with_values(array.values(), additional)),
+            Err(data) => builder_with_values(data.values(), additional),
+        };
+        builder.append_slice(other.data.values());
+        self.data = Arc::new(builder.finish());
+        Ok(())
+    }
+
     /// Compute the centroid from all the rows. Returns `None` if this matrix is empty.
     ///
     /// # Panics if the matrix is transposed.
@@ -392,6 +435,36 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_append() {
+        let row =
+            |values: [f32; 2]| MatrixView::new(Arc::new(Float32Array::from(values.to_vec())), 2);
+        let mut matrix = MatrixView::new(
+            Arc::new(Float32Array::from_iter_values((0..8).map(|v| v as f32))),
+            2,
+        );
+        matrix.append(&row([8.0, 9.0])).unwrap();
+        assert_eq!(matrix.num_rows(), 5);
+        assert_eq!(matrix.row(4).unwrap(), &[8.0, 9.0]);
+
+        // The capacity has grown, so the next row is appended in place.
+        let values = matrix.data().values().as_ptr();
+        matrix.append(&row([10.0, 11.0])).unwrap();
+        assert_eq!(matrix.data().values().as_ptr(), values);
+        assert_eq!(matrix.row(5).unwrap(), &[10.0, 11.0]);
+
+        // A shared matrix is copied, leaving the clone untouched.
+        let shared = matrix.clone();
+        matrix.append(&row([12.0, 13.0])).unwrap();
+        assert_eq!(shared.num_rows(), 6);
+        assert_eq!(matrix.num_rows(), 7);
+        assert_eq!(matrix.row(3).unwrap(), &[6.0, 7.0]);
+
+        assert!(matrix
+            .append(&MatrixView::new(Arc::new(Float32Array::from(vec![1.0])), 1))
+            .is_err());
+    }
+
     #[test]
     #[cfg(unix)]
     fn test_svd() {
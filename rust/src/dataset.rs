@@ -39,7 +39,7 @@ use crate::arrow::*;
 use crate::datatypes::Schema;
 use crate::format::{pb, Fragment, Index, Manifest};
 use crate::io::{
-    object_reader::{read_message, read_struct},
+    object_reader::{read_message, read_struct, ObjectReader},
     read_manifest, read_metadata_offset, write_manifest, FileWriter, ObjectStore,
 };
 use crate::{Error, Result};
@@ -87,12 +87,15 @@ async fn new_file_writer<'a>(
     object_store: &'a ObjectStore,
     data_file_path: &str,
     schema: &'a Schema,
+    params: &WriteParams,
 ) -> Result<FileWriter<'a>> {
     let full_path = object_store
         .base_path()
         .child(DATA_DIR)
         .child(data_file_path);
-    FileWriter::try_new(object_store, &full_path, schema).await
+    let mut writer = FileWriter::try_new(object_store, &full_path, schema).await?;
+    writer.set_collect_statistics(params.collect_statistics);
+    Ok(writer)
 }
 
 /// Get the manifest file path for a version.
@@ -137,14 +140,11 @@ impl Dataset {
         base_path: Path,
         manifest_path: &Path,
     ) -> Result<Self> {
-        let object_reader = object_store.open(manifest_path).await?;
+        let object_reader: Arc<dyn ObjectReader> = object_store.open(manifest_path).await?.into();
         let bytes = object_store.inner.get(manifest_path).await?.bytes().await?;
         let offset = read_metadata_offset(&bytes)?;
         let mut manifest: Manifest = read_struct(object_reader.as_ref(), offset).await?;
-        manifest
-            .schema
-            .load_dictionary(object_reader.as_ref())
-            .await?;
+        manifest.schema.set_dictionary_reader(&object_reader);
         Ok(Self {
             object_store,
             base: base_path,
@@ -213,11 +213,25 @@ impl Dataset {
         if matches!(params.mode, WriteMode::Append) {
             if let Some(d) = dataset.as_ref() {
                 let m = d.manifest.as_ref();
+                // The indices are kept by an append.
+                for field in m.schema.indexed_vector_fields() {
+                    if let Some(f) = schema.mut_field_by_id(field.id) {
+                        f.indexed = true;
+                    }
+                }
                 if schema != m.schema {
-                    return Err(Error::IO(format!(
-                        "Append with different schema: original={} new={}",
-                        m.schema, schema
-                    )));
+                    let diff = schema
+                        .fields
+                        .iter()
+                        .zip(m.schema.fields.iter())
+                        .find_map(|(field, expected)| field.diff(expected));
+                    return Err(Error::IO(match diff {
+                        Some(diff) => format!("Append with different schema: {}", diff),
+                        None => format!(
+                            "Append with different schema: original={} new={}",
+                            m.schema, schema
+                        ),
+                    }));
                 }
             }
         }
@@ -251,6 +265,9 @@ impl Dataset {
         let mut buffer = RecordBatchBuffer::empty();
         for batch_result in peekable {
             let batch = batch_result?;
+            if let Some(max_ratio) = params.max_dictionary_ratio {
+                schema.check_dictionary_cardinality(&batch, max_ratio)?;
+            }
             buffer.batches.push(batch);
             if buffer.num_rows() >= params.max_rows_per_group {
                 // TODO: the max rows per group boundary is not accurately calculated yet.
@@ -260,7 +277,7 @@ impl Dataset {
                         let fragment = Fragment::with_file(fragment_id, &file_path, &schema);
                         fragments.push(fragment);
                         fragment_id += 1;
-                        Some(new_file_writer(&object_store, &file_path, &schema).await?)
+                        Some(new_file_writer(&object_store, &file_path, &schema, &params).await?)
                     }
                 };
 
@@ -286,7 +303,7 @@ impl Dataset {
                     let file_path = format!("{}.lance", Uuid::new_v4());
                     let fragment = Fragment::with_file(fragment_id, &file_path, &schema);
                     fragments.push(fragment);
-                    Some(new_file_writer(&object_store, &file_path, &schema).await?)
+                    Some(new_file_writer(&object_store, &file_path, &schema, &params).await?)
                 }
             };
             let batches = buffer.finish()?;
@@ -1049,6 +1066,11 @@ mod tests {
         let actual = indices.first().unwrap().dataset_version;
         let expected = dataset.manifest.version;
         assert_eq!(actual, expected);
+        let indexed = dataset.schema().indexed_vector_fields();
+        assert_eq!(indexed.len(), 1);
+        assert_eq!(indexed[0].name, "embeddings");
+        let reopened = Dataset::open(test_uri).await.unwrap();
+        assert_eq!(reopened.schema().indexed_vector_fields().len(), 1);
 
         // Append should inherit index
         let mut write_params = WriteParams::default();
@@ -1066,6 +1088,7 @@ mod tests {
         let actual = indices.first().unwrap().dataset_version;
         let expected = dataset.manifest.version - 1;
         assert_eq!(actual, expected);
+        assert_eq!(dataset.schema().indexed_vector_fields().len(), 1);
 
         // Overwrite should invalidate index
         let mut write_params = WriteParams::default();
@@ -1080,6 +1103,7 @@ mod tests {
             .unwrap();
         assert!(dataset.manifest.index_section.is_none());
         assert!(dataset.load_indices().await.unwrap().is_empty());
+        assert!(dataset.schema().indexed_vector_fields().is_empty());
     }
 
     async fn create_bad_file() -> Result<Dataset> {
@@ -37,6 +37,15 @@ pub struct WriteParams {
 
     /// Write mode
     pub mode: WriteMode,
+
+    /// Reject dictionary columns with more than this many dictionary values per
+    /// row, which are better stored without a dictionary. Not checked if `None`.
+    pub max_dictionary_ratio: Option<f64>,
+
+    /// Collect the statistics of the fields of each data file, see
+    /// [`Field::statistics`](crate::datatypes::Field::statistics). It takes one pass
+    /// over the written values.
+    pub collect_statistics: bool,
 }
 
 impl Default for WriteParams {
@@ -45,6 +54,8 @@ impl Default for WriteParams {
             max_rows_per_file: 1024 * 1024, // 1 million
             max_rows_per_group: 1024,
             mode: WriteMode::Create,
+            max_dictionary_ratio: None,
+            collect_statistics: true,
         }
     }
 }
@@ -20,18 +20,23 @@ use std::sync::Arc;
 
 use arrow_array::ArrayRef;
 use arrow_schema::{DataType, Field as ArrowField, TimeUnit};
+use tokio::sync::OnceCell;
 
 mod field;
 mod schema;
+mod statistics;
 
 use crate::format::pb;
+use crate::io::object_reader::ObjectReader;
 use crate::{Error, Result};
-pub use field::Field;
+pub use field::{BufferDescriptor, BufferKind, DiffReason, Field, LayoutDescriptor, SchemaDiff};
 pub use schema::Schema;
+pub use statistics::FieldStatistics;
+pub(crate) use statistics::StatisticsCollector;
 
 /// LogicalType is a string presentation of arrow type.
 /// to be serialized into protobuf.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Hash)]
 pub struct LogicalType(String);
 
 impl fmt::Display for LogicalType {
@@ -52,6 +57,10 @@ impl LogicalType {
     fn is_struct(&self) -> bool {
         self.0 == "struct"
     }
+
+    fn is_dictionary(&self) -> bool {
+        self.0.starts_with("dict:")
+    }
 }
 
 impl From<&str> for LogicalType {
@@ -139,6 +148,8 @@ impl TryFrom<&DataType> for LogicalType {
                 *len
             ),
             DataType::FixedSizeBinary(len) => format!("fixed_size_binary:{}", *len),
+            // TODO: map ListView and LargeListView to "list" and "large_list" once
+            // arrow-rs has them. They are not in the arrow version we depend on.
             _ => return Err(Error::Schema(format!("Unsupported data type: {:?}", dt))),
         };
 
@@ -210,11 +221,16 @@ impl TryFrom<&LogicalType> for DataType {
                     }
                 }
                 "dict" => {
-                    if splits.len() != 4 {
+                    if splits.len() < 4 {
                         Err(Error::Schema(format!("Unsupport dictionary type: {}", lt)))
                     } else {
-                        let value_type: Self = (&LogicalType::from(splits[1])).try_into()?;
-                        let index_type: Self = (&LogicalType::from(splits[2])).try_into()?;
+                        // The value type, i.e., decimal, might contain `:` as well.
+                        let num_splits = splits.len();
+                        let value_type: Self =
+                            (&LogicalType::from(splits[1..num_splits - 2].join(":").as_str()))
+                                .try_into()?;
+                        let index_type: Self =
+                            (&LogicalType::from(splits[num_splits - 2])).try_into()?;
                         Ok(Dictionary(Box::new(index_type), Box::new(value_type)))
                     }
                 }
@@ -262,13 +278,53 @@ impl TryFrom<&LogicalType> for DataType {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Default)]
+/// Dictionary value array of a dictionary field.
+///
+/// When a schema is read from a file, the value array is not loaded eagerly.
+/// It is read from the file on its first access, via [`Field::dictionary_values()`].
+#[derive(Clone, Default)]
 pub struct Dictionary {
     pub(crate) offset: usize,
 
     pub(crate) length: usize,
 
+    /// The values are ordered, as declared by the Arrow field or dictionary array.
+    pub ordered: bool,
+
     pub(crate) values: Option<ArrayRef>,
+
+    /// Reader of the file where the value array is stored.
+    pub(crate) reader: Option<Arc<dyn ObjectReader>>,
+
+    /// Value array loaded lazily from `reader`, shared by the clones of this dictionary.
+    pub(crate) lazy_values: Arc<OnceCell<ArrayRef>>,
+}
+
+impl Dictionary {
+    /// Returns the value array if it has already been set or loaded.
+    pub(crate) fn loaded_values(&self) -> Option<&ArrayRef> {
+        self.values.as_ref().or_else(|| self.lazy_values.get())
+    }
+}
+
+impl Debug for Dictionary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Dictionary")
+            .field("offset", &self.offset)
+            .field("length", &self.length)
+            .field("ordered", &self.ordered)
+            .field("values", &self.loaded_values())
+            .finish()
+    }
+}
+
+impl PartialEq for Dictionary {
+    fn eq(&self, other: &Self) -> bool {
+        self.offset == other.offset
+            && self.length == other.length
+            && self.ordered == other.ordered
+            && self.loaded_values() == other.loaded_values()
+    }
 }
 
 impl From<&pb::Dictionary> for Dictionary {
@@ -276,7 +332,8 @@ impl From<&pb::Dictionary> for Dictionary {
         Self {
             offset: proto.offset as usize,
             length: proto.length as usize,
-            values: None,
+            ordered: proto.ordered,
+            ..Default::default()
         }
     }
 }
@@ -286,6 +343,7 @@ impl From<&Dictionary> for pb::Dictionary {
         Self {
             offset: d.offset as i64,
             length: d.length as i64,
+            ordered: d.ordered,
         }
     }
 }
@@ -14,27 +14,44 @@
 
 //! Lance Schema Field
 
-use std::{cmp::max, fmt, sync::Arc};
+use std::{
+    cmp::max,
+    collections::{HashMap, HashSet},
+    fmt,
+    hash::Hasher,
+    sync::Arc,
+};
 
 use arrow_array::{
     cast::AsArray,
+    downcast_dictionary_array,
     types::{
-        Int16Type, Int32Type, Int64Type, Int8Type, UInt16Type, UInt32Type, UInt64Type, UInt8Type,
+        ArrowDictionaryKeyType, Int16Type, Int32Type, Int64Type, Int8Type, UInt16Type, UInt32Type,
+        UInt64Type, UInt8Type,
     },
-    ArrayRef,
+    Array, ArrayRef, DictionaryArray,
 };
 use arrow_schema::{DataType, Field as ArrowField};
-use async_recursion::async_recursion;
+use siphasher::sip::SipHasher13;
 
-use super::{Dictionary, LogicalType};
+use super::{Dictionary, FieldStatistics, LogicalType};
 use crate::{
     arrow::*,
-    encodings::Encoding,
+    encodings::{compression::Compression, Encoding},
     format::pb,
-    io::object_reader::{read_binary_array, read_fixed_stride_array, ObjectReader},
+    io::{
+        object_reader::{read_binary_array, read_fixed_stride_array, ObjectReader},
+        ReadBatchParams,
+    },
     Error, Result,
 };
 
+/// Arrow metadata key of the extension type name.
+const EXTENSION_NAME_KEY: &str = "ARROW:extension:name";
+
+/// Arrow metadata key of the extension type parameters.
+const EXTENSION_METADATA_KEY: &str = "ARROW:extension:metadata";
+
 /// Lance Schema Field
 ///
 #[derive(Debug, Clone, PartialEq)]
@@ -44,9 +61,22 @@ pub struct Field {
     parent_id: i32,
     logical_type: LogicalType,
     extension_name: String,
+
+    /// Serialized parameters of the extension type.
+    extension_metadata: Option<String>,
+
     pub(crate) encoding: Option<Encoding>,
     pub nullable: bool,
 
+    /// Block compression of the pages, see [`Field::set_compression`].
+    compression: Option<Compression>,
+
+    /// Statistics of the values, set when the field is read from a file.
+    statistics: Option<FieldStatistics>,
+
+    /// A vector index is built on this field.
+    pub indexed: bool,
+
     pub children: Vec<Field>,
 
     /// Dictionary value array if this field is dictionary.
@@ -55,6 +85,9 @@ pub struct Field {
 
 impl Field {
     /// Returns arrow data type.
+    ///
+    /// A dictionary field keeps its [`DataType::Dictionary`] type, with the key type
+    /// of its logical type. See [`Self::decode_dictionary`] for the value type.
     pub fn data_type(&self) -> DataType {
         match &self.logical_type {
             lt if lt.is_list() => DataType::List(Arc::new(ArrowField::from(&self.children[0]))),
@@ -68,6 +101,28 @@ impl Field {
         }
     }
 
+    /// Returns true if this field is a vector column, i.e., a FixedSizeList of
+    /// numeric values with a positive dimension.
+    pub fn is_vector(&self) -> bool {
+        self.vector_dimension().is_some()
+    }
+
+    /// Returns the dimension of the vectors, if this field is a vector column.
+    pub fn vector_dimension(&self) -> Option<usize> {
+        // Not using `data_type()`, that panics on FixedSizeList of nested types.
+        match DataType::try_from(&self.logical_type) {
+            Ok(DataType::FixedSizeList(elem, dim)) if elem.data_type().is_numeric() && dim > 0 => {
+                Some(dim as usize)
+            }
+            _ => None,
+        }
+    }
+
+    /// Id of the parent field, or `-1` for a top-level field.
+    pub fn parent_id(&self) -> i32 {
+        self.parent_id
+    }
+
     pub fn child(&self, name: &str) -> Option<&Self> {
         self.children.iter().find(|f| f.name == name)
     }
@@ -84,42 +139,139 @@ impl Field {
         self.dictionary = Some(Dictionary {
             offset: 0,
             length: 0,
+            ordered: self.dictionary.as_ref().map_or(false, |d| d.ordered),
             values: Some(arr.clone()),
+            ..Default::default()
         });
     }
 
-    pub(super) fn set_dictionary(&mut self, arr: &ArrayRef) {
+    /// Attach the value array of a dictionary array, keeping whether it is ordered.
+    fn set_dictionary_array<K: ArrowDictionaryKeyType>(&mut self, arr: &DictionaryArray<K>) {
+        self.set_dictionary_values(arr.values());
+        if arr.is_ordered() {
+            if let Some(dictionary) = self.dictionary.as_mut() {
+                dictionary.ordered = true;
+            }
+        }
+    }
+
+    /// Returns the dictionary value array of this dictionary field.
+    ///
+    /// If the values were not set from a [`RecordBatch`](arrow_array::RecordBatch),
+    /// they are read from storage on the first access, and cached afterwards.
+    pub async fn dictionary_values(&self) -> Result<&ArrayRef> {
+        let DataType::Dictionary(_, value_type) = self.data_type() else {
+            return Err(Error::Schema(format!(
+                "Field {} is not a dictionary field",
+                self.name
+            )));
+        };
+        let dict_info = self
+            .dictionary
+            .as_ref()
+            .ok_or_else(|| Error::Schema(format!("Field {} misses dictionary info", self.name)))?;
+        if let Some(values) = dict_info.values.as_ref() {
+            return Ok(values);
+        }
+        let reader = dict_info.reader.as_ref().ok_or_else(|| {
+            Error::Schema(format!(
+                "Field {} is dictionary type, but misses the dictionary value array",
+                self.name
+            ))
+        })?;
+        dict_info
+            .lazy_values
+            .get_or_try_init(|| {
+                read_dictionary_values(
+                    reader.as_ref(),
+                    value_type.as_ref(),
+                    dict_info.offset,
+                    dict_info.length,
+                    ..,
+                )
+            })
+            .await
+    }
+
+    /// Read the dictionary values of the keys `[start, start + len)` from `reader`,
+    /// without reading the whole value array.
+    pub async fn read_dictionary_slice(
+        &self,
+        reader: &dyn ObjectReader,
+        start: usize,
+        len: usize,
+    ) -> Result<ArrayRef> {
+        let DataType::Dictionary(_, value_type) = self.data_type() else {
+            return Err(Error::Schema(format!(
+                "Field {} is not a dictionary field",
+                self.name
+            )));
+        };
+        let dict_info = self
+            .dictionary
+            .as_ref()
+            .ok_or_else(|| Error::Schema(format!("Field {} misses dictionary info", self.name)))?;
+        let loaded = dict_info.loaded_values();
+        let num_values = loaded.map_or(dict_info.length, |values| values.len());
+        if start + len > num_values {
+            return Err(Error::Schema(format!(
+                "Dictionary slice {}..{} is out of range of field {} with {} values",
+                start,
+                start + len,
+                self.name,
+                num_values
+            )));
+        }
+        if let Some(values) = loaded {
+            return Ok(values.slice(start, len));
+        }
+        read_dictionary_values(
+            reader,
+            value_type.as_ref(),
+            dict_info.offset,
+            dict_info.length,
+            start..start + len,
+        )
+        .await
+    }
+
+    /// Recursively attach the dictionary value arrays of `arr` to the dictionary fields.
+    ///
+    /// The key type of a dictionary array must be the one declared by the field, for
+    /// the keys are persisted with the declared width.
+    pub(super) fn set_dictionary(&mut self, arr: &ArrayRef) -> Result<()> {
         let data_type = self.data_type();
         match data_type {
-            DataType::Dictionary(key_type, _) => match key_type.as_ref() {
-                DataType::Int8 => {
-                    self.set_dictionary_values(arr.as_dictionary::<Int8Type>().values())
-                }
-                DataType::Int16 => {
-                    self.set_dictionary_values(arr.as_dictionary::<Int16Type>().values())
-                }
-                DataType::Int32 => {
-                    self.set_dictionary_values(arr.as_dictionary::<Int32Type>().values())
-                }
-                DataType::Int64 => {
-                    self.set_dictionary_values(arr.as_dictionary::<Int64Type>().values())
-                }
-                DataType::UInt8 => {
-                    self.set_dictionary_values(arr.as_dictionary::<UInt8Type>().values())
-                }
-                DataType::UInt16 => {
-                    self.set_dictionary_values(arr.as_dictionary::<UInt16Type>().values())
-                }
-                DataType::UInt32 => {
-                    self.set_dictionary_values(arr.as_dictionary::<UInt32Type>().values())
-                }
-                DataType::UInt64 => {
-                    self.set_dictionary_values(arr.as_dictionary::<UInt64Type>().values())
+            DataType::Dictionary(key_type, _) => {
+                match arr.data_type() {
+                    DataType::Dictionary(arr_key_type, _) if arr_key_type == &key_type => {}
+                    dt => {
+                        return Err(Error::Schema(format!(
+                            "Field {} expects a dictionary array with {} keys, got: {}",
+                            self.name, key_type, dt
+                        )))
+                    }
                 }
-                _ => {
-                    panic!("Unsupported dictionary key type: {}", key_type);
+                match key_type.as_ref() {
+                    DataType::Int8 => self.set_dictionary_array(arr.as_dictionary::<Int8Type>()),
+                    DataType::Int16 => self.set_dictionary_array(arr.as_dictionary::<Int16Type>()),
+                    DataType::Int32 => self.set_dictionary_array(arr.as_dictionary::<Int32Type>()),
+                    DataType::Int64 => self.set_dictionary_array(arr.as_dictionary::<Int64Type>()),
+                    DataType::UInt8 => self.set_dictionary_array(arr.as_dictionary::<UInt8Type>()),
+                    DataType::UInt16 => {
+                        self.set_dictionary_array(arr.as_dictionary::<UInt16Type>())
+                    }
+                    DataType::UInt32 => {
+                        self.set_dictionary_array(arr.as_dictionary::<UInt32Type>())
+                    }
+                    DataType::UInt64 => {
+                        self.set_dictionary_array(arr.as_dictionary::<UInt64Type>())
+                    }
+                    _ => {
+                        panic!("Unsupported dictionary key type: {}", key_type);
+                    }
                 }
-            },
+            }
             DataType::Struct(subfields) => {
                 for (i, f) in subfields.iter().enumerate() {
                     let lance_field = self
@@ -128,21 +280,190 @@ impl Field {
                         .find(|c| c.name == *f.name())
                         .unwrap();
                     let struct_arr = arr.as_struct();
-                    lance_field.set_dictionary(struct_arr.column(i));
+                    lance_field.set_dictionary(struct_arr.column(i))?;
                 }
             }
             DataType::List(_) => {
                 let list_arr = arr.as_list::<i32>();
-                self.children[0].set_dictionary(list_arr.values());
+                self.children[0].set_dictionary(list_arr.values())?;
             }
             DataType::LargeList(_) => {
                 let list_arr = arr.as_list::<i64>();
-                self.children[0].set_dictionary(list_arr.values());
+                self.children[0].set_dictionary(list_arr.values())?;
             }
             _ => {
                 // Field types that don't support dictionaries
             }
         }
+        Ok(())
+    }
+
+    /// The storage encoding of this field. Structs have none.
+    pub fn encoding(&self) -> Option<Encoding> {
+        self.encoding.clone()
+    }
+
+    /// Override the storage encoding, if it can store the values of this field.
+    pub fn set_encoding(&mut self, encoding: Encoding) -> Result<()> {
+        let data_type = self.data_type();
+        if !is_legal_encoding(&data_type, &encoding) {
+            return Err(Error::Schema(format!(
+                "Field {}: encoding {:?} can not store {}",
+                self.name, encoding, data_type
+            )));
+        }
+        self.encoding = Some(encoding);
+        Ok(())
+    }
+
+    /// The block compression of the pages of this field.
+    pub fn compression(&self) -> Option<Compression> {
+        self.compression
+    }
+
+    /// Compress the pages of this field, or stop compressing them with `None`.
+    ///
+    /// Only the pages written by the plain and var-binary encoders can be compressed,
+    /// which include the offsets of lists. Compressed pages are read whole.
+    pub fn set_compression(&mut self, compression: Option<Compression>) -> Result<()> {
+        if compression.is_some()
+            && !matches!(self.encoding, Some(Encoding::Plain | Encoding::VarBinary))
+        {
+            return Err(Error::Schema(format!(
+                "Field {}: encoding {:?} does not support compression",
+                self.name, self.encoding
+            )));
+        }
+        self.compression = compression;
+        Ok(())
+    }
+
+    /// Statistics of the values of this field, collected when the file it is read
+    /// from was written.
+    pub fn statistics(&self) -> Option<&FieldStatistics> {
+        self.statistics.as_ref()
+    }
+
+    pub(crate) fn set_statistics(&mut self, statistics: Option<FieldStatistics>) {
+        self.statistics = statistics;
+    }
+
+    /// Check that the dictionaries of this field are worth it for `arr`.
+    ///
+    /// A dictionary with more than `max_ratio` values per row is about as large
+    /// as the data itself. Returns [`Error::Schema`] in that case, so the
+    /// column can be written plainly instead.
+    pub fn check_dictionary_cardinality(&self, arr: &ArrayRef, max_ratio: f64) -> Result<()> {
+        match arr.data_type() {
+            DataType::Dictionary(_, value_type) => {
+                let cardinality = downcast_dictionary_array!(
+                    arr => arr.values().len(),
+                    _ => unreachable!()
+                );
+                if !arr.is_empty() && cardinality as f64 > max_ratio * arr.len() as f64 {
+                    let suggestion = if value_type.is_binary_like() {
+                        "VarBinary"
+                    } else {
+                        "Plain"
+                    };
+                    return Err(Error::Schema(format!(
+                        "Field {}: dictionary has {} values for {} rows, more than the ratio {}; \
+                         consider {} encoding instead",
+                        self.name,
+                        cardinality,
+                        arr.len(),
+                        max_ratio,
+                        suggestion
+                    )));
+                }
+            }
+            DataType::Struct(_) => {
+                let struct_arr = arr.as_struct();
+                for child in self.children.iter() {
+                    if let Some(column) = struct_arr.column_by_name(&child.name) {
+                        child.check_dictionary_cardinality(column, max_ratio)?;
+                    }
+                }
+            }
+            DataType::List(_) => {
+                self.children[0]
+                    .check_dictionary_cardinality(arr.as_list::<i32>().values(), max_ratio)?;
+            }
+            DataType::LargeList(_) => {
+                self.children[0]
+                    .check_dictionary_cardinality(arr.as_list::<i64>().values(), max_ratio)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Returns the field representing the same data as this dictionary field,
+    /// but decoded to its value type.
+    ///
+    /// Non-dictionary fields are returned as is.
+    pub fn decode_dictionary(&self) -> Result<Self> {
+        let DataType::Dictionary(_, value_type) = self.data_type() else {
+            return Ok(self.clone());
+        };
+        let encoding = match value_type.as_ref() {
+            dt if dt.is_binary_like() => Encoding::VarBinary,
+            dt if dt.is_fixed_stride() => Encoding::Plain,
+            dt => {
+                return Err(Error::Schema(format!(
+                    "Does not support {} as dictionary value type",
+                    dt
+                )))
+            }
+        };
+        Ok(Self {
+            name: self.name.clone(),
+            id: self.id,
+            parent_id: self.parent_id,
+            logical_type: LogicalType::try_from(value_type.as_ref())?,
+            extension_name: self.extension_name.clone(),
+            extension_metadata: self.extension_metadata.clone(),
+            encoding: Some(encoding),
+            nullable: self.nullable,
+            compression: self.compression,
+            statistics: self.statistics.clone(),
+            indexed: self.indexed,
+            children: vec![],
+            dictionary: None,
+        })
+    }
+
+    /// Clone this field and its children without the dictionary value arrays.
+    ///
+    /// The offset and length of each dictionary are kept, as well as the reader
+    /// of the file they are stored in, so the values can be loaded again via
+    /// [`Field::dictionary_values()`].
+    pub fn without_dictionary_values(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            id: self.id,
+            parent_id: self.parent_id,
+            logical_type: self.logical_type.clone(),
+            extension_name: self.extension_name.clone(),
+            extension_metadata: self.extension_metadata.clone(),
+            encoding: self.encoding.clone(),
+            nullable: self.nullable,
+            compression: self.compression,
+            statistics: self.statistics.clone(),
+            indexed: self.indexed,
+            children: self
+                .children
+                .iter()
+                .map(|c| c.without_dictionary_values())
+                .collect(),
+            dictionary: self.dictionary.as_ref().map(|d| Dictionary {
+                offset: d.offset,
+                length: d.length,
+                ordered: d.ordered,
+                reader: d.reader.clone(),
+                ..Default::default()
+            }),
+        }
     }
 
     pub(super) fn sub_field(&self, path_components: &[&str]) -> Option<&Self> {
@@ -164,8 +485,12 @@ impl Field {
             parent_id: self.parent_id,
             logical_type: self.logical_type.clone(),
             extension_name: self.extension_name.clone(),
+            extension_metadata: self.extension_metadata.clone(),
             encoding: self.encoding.clone(),
             nullable: self.nullable,
+            compression: self.compression,
+            statistics: self.statistics.clone(),
+            indexed: self.indexed,
             children: vec![],
             dictionary: self.dictionary.clone(),
         };
@@ -185,6 +510,72 @@ impl Field {
         Ok(f)
     }
 
+    /// Extract the descendant field at `path_components`, relative to this field,
+    /// without its ancestors, so it can be added as a top-level field.
+    pub fn extract(&self, path_components: &[&str]) -> Result<Self> {
+        let mut field = self
+            .sub_field(path_components)
+            .ok_or_else(|| {
+                Error::Schema(format!(
+                    "Field {} has no sub-field {}",
+                    self.name,
+                    path_components.join(".")
+                ))
+            })?
+            .clone();
+        field.parent_id = -1;
+        Ok(field)
+    }
+
+    /// Project the descendants matching a glob `pattern`, relative to this field.
+    ///
+    /// The pattern is a dot-separated path with one segment per nesting level,
+    /// each matched against the child names of that level: `*` matches any run of
+    /// characters and `?` any single character. Neither crosses a `.`, there is no
+    /// `**`. The whole subtree of a child matching the last segment is kept.
+    ///
+    /// Returns `None` if nothing matches.
+    pub fn project_glob(&self, pattern: &str) -> Result<Option<Self>> {
+        let segments = pattern.split('.').collect::<Vec<_>>();
+        if segments.iter().any(|s| s.is_empty()) {
+            return Err(Error::Schema(format!(
+                "Invalid glob pattern for field {}: '{}'",
+                self.name, pattern
+            )));
+        }
+        Ok(self.project_glob_segments(&segments))
+    }
+
+    fn project_glob_segments(&self, segments: &[&str]) -> Option<Self> {
+        let Some((first, rest)) = segments.split_first() else {
+            return Some(self.clone());
+        };
+        let children = self
+            .children
+            .iter()
+            .filter(|c| glob_match(first, &c.name))
+            .filter_map(|c| c.project_glob_segments(rest))
+            .collect::<Vec<_>>();
+        if children.is_empty() {
+            return None;
+        }
+        Some(Self {
+            name: self.name.clone(),
+            id: self.id,
+            parent_id: self.parent_id,
+            logical_type: self.logical_type.clone(),
+            extension_name: self.extension_name.clone(),
+            extension_metadata: self.extension_metadata.clone(),
+            encoding: self.encoding.clone(),
+            nullable: self.nullable,
+            compression: self.compression,
+            statistics: self.statistics.clone(),
+            indexed: self.indexed,
+            children,
+            dictionary: self.dictionary.clone(),
+        })
+    }
+
     /// Intersection of two [`Field`]s.
     ///
     pub(super) fn intersection(&self, other: &Self) -> Result<Self> {
@@ -215,8 +606,12 @@ impl Field {
                 parent_id: self.parent_id,
                 logical_type: self.logical_type.clone(),
                 extension_name: self.extension_name.clone(),
+                extension_metadata: self.extension_metadata.clone(),
                 encoding: self.encoding.clone(),
                 nullable: self.nullable,
+                compression: self.compression,
+                statistics: self.statistics.clone(),
+                indexed: self.indexed,
                 children,
                 dictionary: self.dictionary.clone(),
             };
@@ -259,14 +654,128 @@ impl Field {
                 parent_id: self.parent_id,
                 logical_type: self.logical_type.clone(),
                 extension_name: self.extension_name.clone(),
+                extension_metadata: self.extension_metadata.clone(),
                 encoding: self.encoding.clone(),
                 nullable: self.nullable,
+                compression: self.compression,
+                statistics: self.statistics.clone(),
+                indexed: self.indexed,
                 children,
                 dictionary: self.dictionary.clone(),
             })
         }
     }
 
+    /// Exclude the fields whose id is in `ids`, with their subtrees. Unlike
+    /// [`Self::exclude`], this does not depend on the names, so it still holds
+    /// after the fields are renamed.
+    ///
+    /// Returns `None` if this field is excluded, or if all its children are.
+    pub fn exclude_by_id(&self, ids: &HashSet<i32>) -> Option<Self> {
+        if ids.contains(&self.id) {
+            return None;
+        }
+        if self.children.is_empty() {
+            return Some(self.clone());
+        }
+        let children = self
+            .children
+            .iter()
+            .filter_map(|c| c.exclude_by_id(ids))
+            .collect::<Vec<_>>();
+        if children.is_empty() {
+            return None;
+        }
+        Some(Self {
+            name: self.name.clone(),
+            id: self.id,
+            parent_id: self.parent_id,
+            logical_type: self.logical_type.clone(),
+            extension_name: self.extension_name.clone(),
+            extension_metadata: self.extension_metadata.clone(),
+            encoding: self.encoding.clone(),
+            nullable: self.nullable,
+            compression: self.compression,
+            statistics: self.statistics.clone(),
+            indexed: self.indexed,
+            children,
+            dictionary: self.dictionary.clone(),
+        })
+    }
+
+    /// The first structural difference of this field from the `expected` one, or
+    /// `None` if they have the same structure.
+    ///
+    /// The names, types, nullability and encodings are compared, then the children,
+    /// in order. Ids, dictionaries and index flags are ignored.
+    pub fn diff(&self, expected: &Self) -> Option<SchemaDiff> {
+        self.diff_at(expected, &self.name)
+    }
+
+    fn diff_at(&self, expected: &Self, path: &str) -> Option<SchemaDiff> {
+        let reason = if self.name != expected.name {
+            Some(DiffReason::Name(self.name.clone(), expected.name.clone()))
+        } else if self.logical_type != expected.logical_type {
+            Some(DiffReason::Type(self.data_type(), expected.data_type()))
+        } else if self.nullable != expected.nullable {
+            Some(DiffReason::Nullability(self.nullable, expected.nullable))
+        } else if self.encoding != expected.encoding {
+            Some(DiffReason::Encoding(
+                self.encoding.clone(),
+                expected.encoding.clone(),
+            ))
+        } else if self.children.len() != expected.children.len() {
+            Some(DiffReason::ChildCount(
+                self.children.len(),
+                expected.children.len(),
+            ))
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            return Some(SchemaDiff {
+                path: path.to_string(),
+                reason,
+            });
+        }
+        self.children
+            .iter()
+            .zip(expected.children.iter())
+            .find_map(|(child, expected_child)| {
+                child.diff_at(expected_child, &format!("{}.{}", path, child.name))
+            })
+    }
+
+    /// The physical buffers the file writer stores for this field, in each page.
+    ///
+    /// Validity bitmaps are not stored. The children of structs and lists have
+    /// pages of their own.
+    pub fn physical_layout(&self) -> LayoutDescriptor {
+        let buffer = |kind, data_type| BufferDescriptor { kind, data_type };
+        let buffers = match self.data_type() {
+            DataType::Null | DataType::Struct(_) => vec![],
+            DataType::Dictionary(key_type, _) => {
+                vec![buffer(BufferKind::Keys, key_type.as_ref().clone())]
+            }
+            DataType::List(_) => vec![buffer(BufferKind::Offsets, DataType::Int32)],
+            DataType::LargeList(_) => vec![buffer(BufferKind::Offsets, DataType::Int64)],
+            dt if dt.is_binary_like() => vec![
+                buffer(BufferKind::Values, DataType::UInt8),
+                buffer(BufferKind::Positions, DataType::Int64),
+            ],
+            // The values of the lists, contiguous.
+            DataType::FixedSizeList(item, _) => {
+                vec![buffer(BufferKind::Values, item.data_type().clone())]
+            }
+            DataType::FixedSizeBinary(_) => vec![buffer(BufferKind::Values, DataType::UInt8)],
+            dt => vec![buffer(BufferKind::Values, dt)],
+        };
+        LayoutDescriptor {
+            encoding: self.encoding.clone(),
+            buffers,
+        }
+    }
+
     /// Merge the children of other field into this one.
     pub(super) fn merge(&mut self, other: &Self) -> Result<()> {
         for other_child in other.children.as_slice() {
@@ -287,6 +796,60 @@ impl Field {
         )
     }
 
+    /// The ids of this field and all its descendants, in pre-order.
+    pub fn all_ids(&self) -> Vec<i32> {
+        fn visit(field: &Field, ids: &mut Vec<i32>) {
+            ids.push(field.id);
+            field.children.iter().for_each(|c| visit(c, ids));
+        }
+        let mut ids = vec![];
+        visit(self, &mut ids);
+        ids
+    }
+
+    /// Hash of the structure of this subtree: the names, types, nullability, encodings
+    /// and children, in order. The ids and the dictionary values are ignored.
+    ///
+    /// The fields are serialized explicitly, in little endian, and hashed with
+    /// SipHash-1-3 under fixed keys, so the fingerprint is the same across processes,
+    /// platforms and Rust releases, e.g., to key a persistent cache.
+    pub fn fingerprint(&self) -> u64 {
+        fn write_str(state: &mut SipHasher13, s: &str) {
+            state.write(&(s.len() as u64).to_le_bytes());
+            state.write(s.as_bytes());
+        }
+        fn visit(field: &Field, state: &mut SipHasher13) {
+            write_str(state, &field.name);
+            write_str(state, &field.logical_type.0);
+            write_str(state, &field.extension_name);
+            match &field.extension_metadata {
+                Some(metadata) => {
+                    state.write(&[1]);
+                    write_str(state, metadata);
+                }
+                None => state.write(&[0]),
+            }
+            let encoding = field
+                .encoding
+                .clone()
+                .map_or(pb::Encoding::None, pb::Encoding::from);
+            state.write(&(encoding as i32).to_le_bytes());
+            state.write(&[field.nullable as u8]);
+            let compression = field
+                .compression
+                .as_ref()
+                .map(pb::Compression::from)
+                .unwrap_or_default();
+            state.write(&compression.codec.to_le_bytes());
+            state.write(&compression.level.to_le_bytes());
+            state.write(&(field.children.len() as u64).to_le_bytes());
+            field.children.iter().for_each(|c| visit(c, state));
+        }
+        let mut state = SipHasher13::new();
+        visit(self, &mut state);
+        state.finish()
+    }
+
     /// Recursively set field ID and parent ID for this field and all its children.
     pub(super) fn set_id(&mut self, parent_id: i32, id_seed: &mut i32) {
         self.parent_id = parent_id;
@@ -299,7 +862,171 @@ impl Field {
             .for_each(|f| f.set_id(self.id, id_seed));
     }
 
-    // Find any nested child with a specific field id
+    /// Apply `f` to every leaf field of this subtree, i.e., the fields without children.
+    pub fn map_leaves_mut(&mut self, mut f: impl FnMut(&mut Self)) {
+        fn visit(field: &mut Field, f: &mut dyn FnMut(&mut Field)) {
+            if field.children.is_empty() {
+                f(field);
+            } else {
+                field.children.iter_mut().for_each(|c| visit(c, f));
+            }
+        }
+        visit(self, &mut f);
+    }
+
+    /// The children of this field, sorted by name.
+    pub fn sorted_children(&self) -> Vec<&Self> {
+        let mut children = self.children.iter().collect::<Vec<_>>();
+        children.sort_by(|a, b| a.name.cmp(&b.name));
+        children
+    }
+
+    /// Recursively sort the children by name, keeping their ids.
+    ///
+    /// This is a canonical form to compare schemas regardless of the order of the
+    /// struct fields. It changes the order of the struct fields of [`Self::data_type()`],
+    /// so it is not meant to read data with.
+    pub fn canonicalize(&mut self) {
+        self.children.sort_by(|a, b| a.name.cmp(&b.name));
+        self.children.iter_mut().for_each(|c| c.canonicalize());
+    }
+
+    /// Deep clone of this field, with all the ids of the subtree reassigned from `start_id`.
+    ///
+    /// Returns the clone and the next free id. The clone is a top-level field, to graft
+    /// it into another schema without id collisions.
+    pub fn with_fresh_ids(&self, start_id: i32) -> (Self, i32) {
+        fn reassign(field: &mut Field, parent_id: i32, next_id: &mut i32) {
+            field.parent_id = parent_id;
+            field.id = *next_id;
+            *next_id += 1;
+            let id = field.id;
+            field
+                .children
+                .iter_mut()
+                .for_each(|f| reassign(f, id, next_id));
+        }
+
+        let mut field = self.clone();
+        let mut next_id = start_id;
+        reassign(&mut field, -1, &mut next_id);
+        (field, next_id)
+    }
+
+    /// Replace the child `name` of this struct field by `new_child`, in place.
+    ///
+    /// A `new_child` without id (negative id) must keep the same name: it takes the
+    /// id of the replaced child, so that the existing data stays addressable.
+    /// Otherwise, the id of `new_child` is kept. The parent ids of the new subtree
+    /// are updated to fit in this field.
+    pub fn replace_child(&mut self, name: &str, mut new_child: Self) -> Result<()> {
+        if !self.logical_type.is_struct() {
+            return Err(Error::Schema(format!(
+                "Field {}: cannot replace child {} of a non-struct field",
+                self.name, name
+            )));
+        }
+        let Some(idx) = self.children.iter().position(|c| c.name == name) else {
+            return Err(Error::Schema(format!(
+                "Field {}: child {} does not exist",
+                self.name, name
+            )));
+        };
+        if new_child.name != name {
+            if new_child.id < 0 {
+                return Err(Error::Schema(format!(
+                    "Field {}: child {} is renamed to {} without an id",
+                    self.name, name, new_child.name
+                )));
+            }
+            if self.child(&new_child.name).is_some() {
+                return Err(Error::Schema(format!(
+                    "Field {}: child {} already exists",
+                    self.name, new_child.name
+                )));
+            }
+        }
+        if new_child.id < 0 {
+            new_child.id = self.children[idx].id;
+        }
+
+        fn set_parent_ids(field: &mut Field, parent_id: i32) {
+            field.parent_id = parent_id;
+            let id = field.id;
+            field
+                .children
+                .iter_mut()
+                .for_each(|c| set_parent_ids(c, id));
+        }
+        set_parent_ids(&mut new_child, self.id);
+        self.children[idx] = new_child;
+        Ok(())
+    }
+
+    /// Rebuild the field trees from their flattened protobuf form, linking each
+    /// field to its parent by `parent_id`, see `From<&Field> for Vec<pb::Field>`.
+    ///
+    /// The top-level fields have a `parent_id` of -1. The order of the fields is
+    /// kept, and a child may come before its parent.
+    pub fn from_proto_list(fields: &[pb::Field]) -> Result<Vec<Self>> {
+        let mut index_by_id = HashMap::with_capacity(fields.len());
+        for (i, f) in fields.iter().enumerate() {
+            if index_by_id.insert(f.id, i).is_some() {
+                return Err(Error::Schema(format!(
+                    "Duplicate field id {} in field {}",
+                    f.id, f.name
+                )));
+            }
+        }
+
+        let mut roots = vec![];
+        let mut children = vec![vec![]; fields.len()];
+        for (i, f) in fields.iter().enumerate() {
+            if f.parent_id == -1 {
+                roots.push(i);
+                continue;
+            }
+            let Some(&parent) = index_by_id.get(&f.parent_id) else {
+                return Err(Error::Schema(format!(
+                    "Field {} (id={}) has a missing parent: {}",
+                    f.name, f.id, f.parent_id
+                )));
+            };
+            children[parent].push(i);
+        }
+
+        fn build(i: usize, fields: &[pb::Field], children: &[Vec<usize>]) -> Field {
+            let mut field = Field::from(&fields[i]);
+            field.children = children[i]
+                .iter()
+                .map(|&c| build(c, fields, children))
+                .collect();
+            field
+        }
+        let trees = roots
+            .into_iter()
+            .map(|i| build(i, fields, &children))
+            .collect::<Vec<_>>();
+
+        // The fields in a cycle of parents are not reachable from any top-level field.
+        let num_reached = trees.iter().map(|f| f.all_ids().len()).sum::<usize>();
+        if num_reached != fields.len() {
+            return Err(Error::Schema(format!(
+                "{} fields are not attached to any top-level field, the parents form a cycle",
+                fields.len() - num_reached
+            )));
+        }
+        Ok(trees)
+    }
+
+    /// This field or any of its descendants with the field id `id`.
+    pub fn field_by_id(&self, id: i32) -> Option<&Self> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.children.iter().find_map(|c| c.field_by_id(id))
+    }
+
     pub(super) fn mut_field_by_id(&mut self, id: i32) -> Option<&mut Self> {
         for child in self.children.as_mut_slice() {
             if child.id == id {
@@ -312,58 +1039,259 @@ impl Field {
         None
     }
 
-    #[async_recursion]
-    pub(super) async fn load_dictionary<'a>(&mut self, reader: &dyn ObjectReader) -> Result<()> {
-        if let DataType::Dictionary(_, value_type) = self.data_type() {
-            assert!(self.dictionary.is_some());
-            if let Some(dict_info) = self.dictionary.as_mut() {
-                use DataType::*;
-                match value_type.as_ref() {
-                    Utf8 | Binary => {
-                        dict_info.values = Some(
-                            read_binary_array(
-                                reader,
-                                value_type.as_ref(),
-                                false,
-                                dict_info.offset,
-                                dict_info.length,
-                                ..,
-                            )
-                            .await?,
-                        );
-                    }
-                    Int8 | Int16 | Int32 | Int64 | UInt8 | UInt16 | UInt32 | UInt64 => {
-                        dict_info.values = Some(
-                            read_fixed_stride_array(
-                                reader,
-                                value_type.as_ref(),
-                                dict_info.offset,
-                                dict_info.length,
-                                ..,
-                            )
-                            .await?,
-                        );
-                    }
-                    _ => {
-                        return Err(Error::Schema(format!(
-                            "Does not support {} as dictionary value type",
-                            value_type
-                        )));
-                    }
+    /// Check the invariants of this field and all its children.
+    ///
+    /// - field ids are non-negative and unique,
+    /// - the parent id of each child is the id of its parent,
+    /// - dictionary fields have their dictionary set,
+    /// - list fields have exactly one child,
+    /// - the children of a struct have unique names.
+    ///
+    /// Returns the first violation found, with the path of the offending field.
+    pub fn validate(&self) -> Result<()> {
+        self.validate_recursive(&self.name, &mut HashSet::new())
+    }
+
+    fn validate_recursive(&self, path: &str, ids: &mut HashSet<i32>) -> Result<()> {
+        if self.id < 0 {
+            return Err(Error::Schema(format!(
+                "Field {}: negative field id {}",
+                path, self.id
+            )));
+        }
+        if !ids.insert(self.id) {
+            return Err(Error::Schema(format!(
+                "Field {}: duplicated field id {}",
+                path, self.id
+            )));
+        }
+        if self.logical_type.is_dictionary() && self.dictionary.is_none() {
+            return Err(Error::Schema(format!(
+                "Field {}: dictionary field has no dictionary",
+                path
+            )));
+        }
+        if (self.logical_type.is_list() || self.logical_type.is_large_list())
+            && self.children.len() != 1
+        {
+            return Err(Error::Schema(format!(
+                "Field {}: list field must have exactly one child, got {}",
+                path,
+                self.children.len()
+            )));
+        }
+        if self.logical_type.is_struct() {
+            let mut names = HashSet::new();
+            for child in self.children.iter() {
+                if !names.insert(child.name.as_str()) {
+                    return Err(Error::Schema(format!(
+                        "Field {}: duplicated child name {}",
+                        path, child.name
+                    )));
                 }
-            } else {
-                panic!("Should not reach here: dictionary field does not load dictionary info")
             }
-            Ok(())
+        }
+        for child in self.children.iter() {
+            let child_path = format!("{}.{}", path, child.name);
+            if child.parent_id != self.id {
+                return Err(Error::Schema(format!(
+                    "Field {}: parent id {} does not match the id of its parent {}",
+                    child_path, child.parent_id, self.id
+                )));
+            }
+            child.validate_recursive(&child_path, ids)?;
+        }
+        Ok(())
+    }
+
+    /// Attach the reader of the file where the dictionary values are stored,
+    /// so they can be loaded lazily by [`Field::dictionary_values()`].
+    pub(super) fn set_dictionary_reader(&mut self, reader: &Arc<dyn ObjectReader>) {
+        if self.data_type().is_dictionary() {
+            if let Some(dict_info) = self.dictionary.as_mut() {
+                dict_info.reader = Some(reader.clone());
+            }
         } else {
             for child in self.children.as_mut_slice() {
-                child.load_dictionary(reader).await?;
+                child.set_dictionary_reader(reader);
             }
-            Ok(())
         }
     }
 }
 
+/// Whether `encoding` can store the values of `data_type`.
+///
+/// Only the encodings [`FileWriter`](crate::io::FileWriter) can produce are legal:
+/// plain for fixed-width values, var-binary for strings and binaries, and dictionary
+/// for dictionary arrays of either. Run-length pages can not be written yet.
+fn is_legal_encoding(data_type: &DataType, encoding: &Encoding) -> bool {
+    match encoding {
+        // Lists store their offsets plainly.
+        Encoding::Plain => {
+            data_type.is_fixed_stride()
+                || matches!(data_type, DataType::List(_) | DataType::LargeList(_))
+        }
+        Encoding::VarBinary => data_type.is_binary_like(),
+        Encoding::Dictionary => match data_type {
+            DataType::Dictionary(_, value_type) => {
+                value_type.is_fixed_stride() || value_type.is_binary_like()
+            }
+            _ => false,
+        },
+        Encoding::RLE => false,
+    }
+}
+
+/// Read the value array of a dictionary, or the `params` part of it.
+async fn read_dictionary_values(
+    reader: &dyn ObjectReader,
+    value_type: &DataType,
+    offset: usize,
+    length: usize,
+    params: impl Into<ReadBatchParams>,
+) -> Result<ArrayRef> {
+    use DataType::*;
+    match value_type {
+        Utf8 | Binary => read_binary_array(reader, value_type, false, offset, length, params).await,
+        Int8
+        | Int16
+        | Int32
+        | Int64
+        | UInt8
+        | UInt16
+        | UInt32
+        | UInt64
+        | Decimal128(_, _)
+        | Decimal256(_, _) => {
+            read_fixed_stride_array(reader, value_type, offset, length, params).await
+        }
+        _ => Err(Error::Schema(format!(
+            "Does not support {} as dictionary value type",
+            value_type
+        ))),
+    }
+}
+
+/// The physical buffers of a field, see [`Field::physical_layout`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutDescriptor {
+    pub encoding: Option<Encoding>,
+
+    /// The buffers of a page, in the order they are written.
+    pub buffers: Vec<BufferDescriptor>,
+}
+
+/// One physical buffer of a page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BufferDescriptor {
+    pub kind: BufferKind,
+
+    /// Type of the elements of the buffer.
+    pub data_type: DataType,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferKind {
+    /// The values, bit-packed for booleans.
+    Values,
+
+    /// `len + 1` offsets of the lists into the values of the child field.
+    Offsets,
+
+    /// `len + 1` absolute positions in the file of the binary values, which are
+    /// written just before. The page points to the positions.
+    Positions,
+
+    /// Dictionary keys. The dictionary values are stored in the manifest.
+    Keys,
+}
+
+/// The first structural difference between two fields, see [`Field::diff`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaDiff {
+    /// Dotted path of the differing field.
+    pub path: String,
+
+    pub reason: DiffReason,
+}
+
+/// How two fields differ, as `(actual, expected)`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffReason {
+    Name(String, String),
+    Type(DataType, DataType),
+    Nullability(bool, bool),
+    Encoding(Option<Encoding>, Option<Encoding>),
+    ChildCount(usize, usize),
+}
+
+impl fmt::Display for SchemaDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let nullability = |nullable: &bool| {
+            if *nullable {
+                "nullable"
+            } else {
+                "not nullable"
+            }
+        };
+        match &self.reason {
+            DiffReason::Name(actual, expected) => write!(
+                f,
+                "column `{}` is named {}, expected: {}",
+                self.path, actual, expected
+            ),
+            DiffReason::Type(actual, expected) => write!(
+                f,
+                "column `{}` is {}, expected: {}",
+                self.path, actual, expected
+            ),
+            DiffReason::Nullability(actual, expected) => write!(
+                f,
+                "column `{}` is {}, expected: {}",
+                self.path,
+                nullability(actual),
+                nullability(expected)
+            ),
+            DiffReason::Encoding(actual, expected) => write!(
+                f,
+                "column `{}` has encoding {:?}, expected: {:?}",
+                self.path, actual, expected
+            ),
+            DiffReason::ChildCount(actual, expected) => write!(
+                f,
+                "column `{}` has {} children, expected: {}",
+                self.path, actual, expected
+            ),
+        }
+    }
+}
+
+/// Match `name` against a glob `pattern` supporting `*` and `?`.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` in the pattern, and of the name when it was seen.
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            // Let the last `*` consume one more character.
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
 impl fmt::Display for Field {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -398,12 +1326,35 @@ impl TryFrom<&ArrowField> for Field {
                 DataType::Dictionary(_, _) => Some(Encoding::Dictionary),
                 // Use plain encoder to store the offsets of list.
                 DataType::List(_) | DataType::LargeList(_) => Some(Encoding::Plain),
+                // Null arrays have no values to encode, only their length in the page table.
+                DataType::Null => None,
                 _ => None,
             },
-            extension_name: "".to_string(),
+            extension_name: field
+                .metadata()
+                .get(EXTENSION_NAME_KEY)
+                .cloned()
+                .unwrap_or_default(),
+            extension_metadata: field
+                .metadata()
+                .get(EXTENSION_METADATA_KEY)
+                .filter(|metadata| !metadata.is_empty())
+                .cloned(),
             nullable: field.is_nullable(),
+            compression: None,
+            statistics: None,
+            indexed: false,
             children,
-            dictionary: None,
+            dictionary: match field.data_type() {
+                // The values are set when the arrays are written.
+                DataType::Dictionary(_, _) if field.dict_is_ordered() == Some(true) => {
+                    Some(Dictionary {
+                        ordered: true,
+                        ..Default::default()
+                    })
+                }
+                _ => None,
+            },
         })
     }
 }
@@ -418,18 +1369,49 @@ impl TryFrom<ArrowField> for Field {
 
 impl From<&Field> for ArrowField {
     fn from(field: &Field) -> Self {
-        Self::new(&field.name, field.data_type(), field.nullable)
+        let arrow_field = match field.dictionary.as_ref() {
+            Some(dictionary) if dictionary.ordered => {
+                Self::new_dict(&field.name, field.data_type(), field.nullable, 0, true)
+            }
+            _ => Self::new(&field.name, field.data_type(), field.nullable),
+        };
+        let mut metadata = HashMap::new();
+        if !field.extension_name.is_empty() {
+            metadata.insert(EXTENSION_NAME_KEY.to_string(), field.extension_name.clone());
+            // Arrow C++ writes the metadata of every extension type, empty if it has none.
+            metadata.insert(
+                EXTENSION_METADATA_KEY.to_string(),
+                field.extension_metadata.clone().unwrap_or_default(),
+            );
+        } else if let Some(extension_metadata) = field.extension_metadata.as_ref() {
+            metadata.insert(
+                EXTENSION_METADATA_KEY.to_string(),
+                extension_metadata.clone(),
+            );
+        }
+        if metadata.is_empty() {
+            arrow_field
+        } else {
+            arrow_field.with_metadata(metadata)
+        }
     }
 }
 
 impl From<&pb::Field> for Field {
     fn from(field: &pb::Field) -> Self {
+        let logical_type = LogicalType(field.logical_type.clone());
+        // The statistics are only hints, so they are dropped if they can not be decoded.
+        let statistics = field
+            .statistics
+            .as_ref()
+            .and_then(|stats| FieldStatistics::from_pb(stats, &logical_type).ok());
         Self {
             name: field.name.clone(),
             id: field.id,
             parent_id: field.parent_id,
-            logical_type: LogicalType(field.logical_type.clone()),
+            logical_type,
             extension_name: field.extension_name.clone(),
+            extension_metadata: field.extension_metadata.clone(),
             encoding: match field.encoding {
                 1 => Some(Encoding::Plain),
                 2 => Some(Encoding::VarBinary),
@@ -438,6 +1420,9 @@ impl From<&pb::Field> for Field {
                 _ => None,
             },
             nullable: field.nullable,
+            compression: field.compression.as_ref().and_then(Option::from),
+            statistics,
+            indexed: field.indexed,
             children: vec![],
             dictionary: field.dictionary.as_ref().map(Dictionary::from),
         }
@@ -459,8 +1444,12 @@ impl From<&Field> for pb::Field {
                 _ => 0,
             },
             nullable: field.nullable,
+            compression: field.compression.as_ref().map(pb::Compression::from),
+            statistics: field.statistics.as_ref().map(pb::FieldStatistics::from),
+            indexed: field.indexed,
             dictionary: field.dictionary.as_ref().map(pb::Dictionary::from),
             extension_name: field.extension_name.clone(),
+            extension_metadata: field.extension_metadata.clone(),
             r#type: 0,
         }
     }
@@ -478,7 +1467,22 @@ impl From<&Field> for Vec<pb::Field> {
 mod tests {
     use super::*;
 
-    use arrow_schema::{DataType, Fields, TimeUnit};
+    use std::collections::HashMap;
+    use std::ops::Range;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use arrow_array::{
+        builder::{ListBuilder, StringDictionaryBuilder},
+        DictionaryArray, Int32Array, StringArray, StructArray,
+    };
+    use arrow_schema::{DataType, Fields, Schema as ArrowSchema, TimeUnit};
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use object_store::path::Path;
+
+    use crate::datatypes::Schema;
+    use crate::encodings::{binary::BinaryEncoder, plain::PlainEncoder, Encoder};
+    use crate::io::ObjectStore;
 
     #[test]
     fn arrow_field_to_field() {
@@ -590,6 +1594,1142 @@ mod tests {
         assert_eq!(ArrowField::try_from(&field).unwrap(), arrow_field);
     }
 
+    #[test]
+    fn test_list_nullability() {
+        for (list_nullable, item_nullable) in
+            [(true, true), (true, false), (false, true), (false, false)]
+        {
+            let item = Arc::new(ArrowField::new("item", DataType::Int32, item_nullable));
+            for data_type in [DataType::List(item.clone()), DataType::LargeList(item)] {
+                let arrow_field = ArrowField::new("list", data_type, list_nullable);
+                let field = Field::try_from(&arrow_field).unwrap();
+                assert_eq!(field.nullable, list_nullable);
+                assert_eq!(field.children[0].nullable, item_nullable);
+                assert_eq!(ArrowField::from(&field), arrow_field);
+
+                // Round trip through the protobuf fields of the manifest.
+                let schema =
+                    Schema::try_from(&ArrowSchema::new(vec![arrow_field.clone()])).unwrap();
+                let protos: Vec<pb::Field> = (&schema).into();
+                assert_eq!(
+                    ArrowSchema::from(&Schema::from(&protos)).field(0),
+                    &arrow_field
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_set_encoding() {
+        let mut field = Field::try_from(&ArrowField::new("a", DataType::Int32, true)).unwrap();
+        assert_eq!(field.encoding(), Some(Encoding::Plain));
+        assert!(field.set_encoding(Encoding::RLE).is_err());
+        assert!(field.set_encoding(Encoding::Dictionary).is_err());
+        field.set_encoding(Encoding::Plain).unwrap();
+        assert_eq!(field.encoding(), Some(Encoding::Plain));
+
+        let mut field = Field::try_from(&ArrowField::new("s", DataType::Utf8, true)).unwrap();
+        assert!(field.set_encoding(Encoding::RLE).is_err());
+        assert!(field.set_encoding(Encoding::Plain).is_err());
+        assert_eq!(field.encoding(), Some(Encoding::VarBinary));
+
+        let mut field = Field::try_from(&ArrowField::new(
+            "d",
+            DataType::Dictionary(Box::new(DataType::UInt32), Box::new(DataType::Utf8)),
+            true,
+        ))
+        .unwrap();
+        assert!(field.set_encoding(Encoding::RLE).is_err());
+        assert!(field.set_encoding(Encoding::VarBinary).is_err());
+        field.set_encoding(Encoding::Dictionary).unwrap();
+
+        let mut field = Field::try_from(&ArrowField::new(
+            "st",
+            DataType::Struct(Fields::from(vec![ArrowField::new(
+                "a",
+                DataType::Int32,
+                true,
+            )])),
+            true,
+        ))
+        .unwrap();
+        assert_eq!(field.encoding(), None);
+        assert!(field.set_encoding(Encoding::Plain).is_err());
+    }
+
+    #[test]
+    fn test_check_dictionary_cardinality() {
+        let field: Field = ArrowField::new(
+            "d",
+            DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8)),
+            true,
+        )
+        .try_into()
+        .unwrap();
+        let dict_of = |num_values: usize| -> ArrayRef {
+            Arc::new(
+                (0..100)
+                    .map(|i| (i % num_values).to_string())
+                    .collect::<DictionaryArray<Int32Type>>(),
+            )
+        };
+
+        // 50 values for 100 rows is right at the threshold.
+        assert!(field
+            .check_dictionary_cardinality(&dict_of(50), 0.5)
+            .is_ok());
+        let err = field
+            .check_dictionary_cardinality(&dict_of(51), 0.5)
+            .unwrap_err();
+        assert!(err.to_string().contains("VarBinary"));
+
+        let list_field: Field = ArrowField::new(
+            "l",
+            DataType::List(Arc::new(ArrowField::new("item", field.data_type(), true))),
+            true,
+        )
+        .try_into()
+        .unwrap();
+        let mut builder = ListBuilder::new(StringDictionaryBuilder::<Int32Type>::new());
+        for i in 0..100 {
+            builder.values().append_value(i.to_string());
+            if i % 10 == 9 {
+                builder.append(true);
+            }
+        }
+        let list: ArrayRef = Arc::new(builder.finish());
+        assert!(list_field.check_dictionary_cardinality(&list, 0.5).is_err());
+
+        // Non-dictionary columns are not checked.
+        let field: Field = ArrowField::new("s", DataType::Utf8, true)
+            .try_into()
+            .unwrap();
+        let strings: ArrayRef = Arc::new(StringArray::from(vec!["a", "b", "c"]));
+        assert!(field.check_dictionary_cardinality(&strings, 0.1).is_ok());
+    }
+
+    #[test]
+    fn test_replace_child() {
+        let arrow_field = ArrowField::new(
+            "s",
+            DataType::Struct(Fields::from(vec![
+                ArrowField::new("a", DataType::Int32, true),
+                ArrowField::new("b", DataType::Utf8, true),
+            ])),
+            true,
+        );
+        let (mut field, _) = Field::try_from(&arrow_field).unwrap().with_fresh_ids(0);
+        assert_eq!(field.child("a").unwrap().id, 1);
+
+        // Same name and no id: the id of the replaced child is kept.
+        let new_a = Field::try_from(&ArrowField::new("a", DataType::Int64, true)).unwrap();
+        field.replace_child("a", new_a).unwrap();
+        let a = field.child("a").unwrap();
+        assert_eq!(a.id, 1);
+        assert_eq!(a.parent_id, 0);
+        assert_eq!(a.data_type(), DataType::Int64);
+
+        // A new id is kept.
+        let mut new_b = Field::try_from(&ArrowField::new("c", DataType::Utf8, true)).unwrap();
+        new_b.id = 10;
+        field.replace_child("b", new_b).unwrap();
+        assert!(field.child("b").is_none());
+        assert_eq!(field.child("c").unwrap().id, 10);
+        assert_eq!(field.children.len(), 2);
+        field.validate().unwrap();
+
+        let err = field
+            .replace_child(
+                "z",
+                Field::try_from(&ArrowField::new("z", DataType::Utf8, true)).unwrap(),
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("does not exist"), "{}", err);
+        let renamed = Field::try_from(&ArrowField::new("d", DataType::Utf8, true)).unwrap();
+        assert!(field.replace_child("a", renamed).is_err());
+        let mut a = field.child("a").unwrap().clone();
+        assert!(a.replace_child("a", field.clone()).is_err());
+    }
+
+    #[test]
+    fn test_indexed_round_trip() {
+        let mut field: Field = ArrowField::new(
+            "vec",
+            DataType::FixedSizeList(
+                Arc::new(ArrowField::new("item", DataType::Float32, true)),
+                8,
+            ),
+            true,
+        )
+        .try_into()
+        .unwrap();
+        assert!(!field.indexed);
+        assert!(!Field::from(&pb::Field::from(&field)).indexed);
+        field.indexed = true;
+        assert!(Field::from(&pb::Field::from(&field)).indexed);
+        assert!(field.without_dictionary_values().indexed);
+    }
+
+    #[test]
+    fn test_from_proto_list() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, true),
+            ArrowField::new(
+                "s",
+                DataType::Struct(Fields::from(vec![
+                    ArrowField::new("x", DataType::Utf8, true),
+                    ArrowField::new(
+                        "l",
+                        DataType::List(Arc::new(ArrowField::new("item", DataType::Float32, true))),
+                        true,
+                    ),
+                ])),
+                true,
+            ),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+        let protos = schema
+            .fields
+            .iter()
+            .flat_map(Vec::<pb::Field>::from)
+            .collect::<Vec<_>>();
+        assert_eq!(protos.len(), 5);
+
+        assert_eq!(Field::from_proto_list(&protos).unwrap(), schema.fields);
+
+        // Children may come before their parents.
+        let mut reversed = protos.clone();
+        reversed.reverse();
+        let mut fields = Field::from_proto_list(&reversed).unwrap();
+        fields.reverse();
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[1].name, "s");
+        let mut children = fields[1].children.clone();
+        children.reverse();
+        assert_eq!(children, schema.fields[1].children);
+
+        // Dangling parent.
+        let mut dangling = protos.clone();
+        dangling[2].parent_id = 42;
+        assert!(Field::from_proto_list(&dangling).is_err());
+
+        // Duplicate id.
+        let mut duplicate = protos.clone();
+        duplicate[4].id = duplicate[3].id;
+        assert!(Field::from_proto_list(&duplicate).is_err());
+
+        // "x" and "l" are the parents of each other.
+        let mut cycle = protos;
+        cycle[2].parent_id = cycle[3].id;
+        cycle[3].parent_id = cycle[2].id;
+        assert!(Field::from_proto_list(&cycle).is_err());
+    }
+
+    #[test]
+    fn test_null_type_round_trip() {
+        let arrow_field = ArrowField::new("n", DataType::Null, true);
+        let field = Field::try_from(&arrow_field).unwrap();
+        assert_eq!(field.encoding(), None);
+
+        let proto = pb::Field::from(&field);
+        assert_eq!(proto.encoding, 0);
+        let field = Field::from(&proto);
+        assert_eq!(field.encoding(), None);
+        assert_eq!(field.data_type(), DataType::Null);
+        assert_eq!(ArrowField::from(&field), arrow_field);
+
+        // As the child of a struct.
+        let arrow_schema = ArrowSchema::new(vec![ArrowField::new(
+            "s",
+            DataType::Struct(Fields::from(vec![
+                ArrowField::new("n", DataType::Null, true),
+                ArrowField::new("i", DataType::Int32, true),
+            ])),
+            true,
+        )]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+        let protos = Vec::<pb::Field>::from(&schema.fields[0]);
+        let fields = Field::from_proto_list(&protos).unwrap();
+        assert_eq!(fields, schema.fields);
+        assert_eq!(fields[0].children[0].data_type(), DataType::Null);
+        assert_eq!(fields[0].children[0].encoding(), None);
+        assert_eq!(
+            ArrowField::from(&fields[0]),
+            arrow_schema.fields()[0].as_ref().clone()
+        );
+    }
+
+    /// An [ObjectReader] counting the number of reads.
+    struct CountingReader {
+        inner: Box<dyn ObjectReader>,
+        reads: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl ObjectReader for CountingReader {
+        fn prefetch_size(&self) -> usize {
+            self.inner.prefetch_size()
+        }
+
+        async fn size(&self) -> Result<usize> {
+            self.inner.size().await
+        }
+
+        async fn get_range(&self, range: Range<usize>) -> Result<Bytes> {
+            self.reads.fetch_add(1, Ordering::SeqCst);
+            self.inner.get_range(range).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lazy_dictionary_values() {
+        let store = ObjectStore::memory();
+        let path = Path::from("/dict");
+        let values: ArrayRef = Arc::new(Int32Array::from_iter_values(0..10));
+        let mut writer = store.create(&path).await.unwrap();
+        let pos = PlainEncoder::new(&mut writer, &DataType::Int32)
+            .encode(&[values.as_ref()])
+            .await
+            .unwrap();
+        writer.shutdown().await.unwrap();
+
+        let reads = Arc::new(AtomicUsize::new(0));
+        let reader: Arc<dyn ObjectReader> = Arc::new(CountingReader {
+            inner: store.open(&path).await.unwrap(),
+            reads: reads.clone(),
+        });
+
+        let mut field: Field = ArrowField::new(
+            "d",
+            DataType::Dictionary(Box::new(DataType::UInt8), Box::new(DataType::Int32)),
+            true,
+        )
+        .try_into()
+        .unwrap();
+        field.dictionary = Some(Dictionary {
+            offset: pos,
+            length: values.len(),
+            ..Default::default()
+        });
+        field.set_dictionary_reader(&reader);
+        let cloned = field.clone();
+        // Dictionary values are not fetched until they are accessed.
+        assert_eq!(reads.load(Ordering::SeqCst), 0);
+
+        assert_eq!(field.dictionary_values().await.unwrap(), &values);
+        let num_reads = reads.load(Ordering::SeqCst);
+        assert!(num_reads > 0);
+
+        // Loaded values are cached, and shared by the clones.
+        assert_eq!(cloned.dictionary_values().await.unwrap(), &values);
+        assert_eq!(reads.load(Ordering::SeqCst), num_reads);
+    }
+
+    #[tokio::test]
+    async fn test_read_dictionary_slice() {
+        let store = ObjectStore::memory();
+        let path = Path::from("/dict");
+        let values: ArrayRef = Arc::new(StringArray::from_iter_values(
+            (0..100).map(|i| format!("value-{}", i)),
+        ));
+        let mut writer = store.create(&path).await.unwrap();
+        let pos = BinaryEncoder::new(&mut writer)
+            .encode(&[values.as_ref()])
+            .await
+            .unwrap();
+        writer.shutdown().await.unwrap();
+        let reader = store.open(&path).await.unwrap();
+
+        let mut field: Field = ArrowField::new(
+            "d",
+            DataType::Dictionary(Box::new(DataType::UInt8), Box::new(DataType::Utf8)),
+            true,
+        )
+        .try_into()
+        .unwrap();
+        field.dictionary = Some(Dictionary {
+            offset: pos,
+            length: values.len(),
+            ..Default::default()
+        });
+
+        let slice = field
+            .read_dictionary_slice(reader.as_ref(), 40, 10)
+            .await
+            .unwrap();
+        assert_eq!(slice.as_ref(), values.slice(40, 10).as_ref());
+        assert!(field
+            .read_dictionary_slice(reader.as_ref(), 95, 10)
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_ordered_dictionary_round_trip() {
+        let data_type = DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8));
+        let arrow_field = ArrowField::new_dict("d", data_type.clone(), true, 0, true);
+        let mut field = Field::try_from(&arrow_field).unwrap();
+        assert!(field.dictionary.as_ref().unwrap().ordered);
+
+        // Setting the values from an array keeps the order.
+        let arr: ArrayRef = Arc::new(
+            vec!["a", "b", "a"]
+                .into_iter()
+                .collect::<DictionaryArray<Int32Type>>(),
+        );
+        field.set_dictionary(&arr).unwrap();
+        let dictionary = field.dictionary.as_ref().unwrap();
+        assert!(dictionary.ordered);
+        assert!(dictionary.values.is_some());
+
+        let proto = pb::Field::from(&field);
+        assert!(proto.dictionary.as_ref().unwrap().ordered);
+        let decoded = Field::from(&proto);
+        assert!(decoded.dictionary.as_ref().unwrap().ordered);
+        assert_eq!(ArrowField::from(&decoded).dict_is_ordered(), Some(true));
+
+        let unordered = Field::try_from(&ArrowField::new("d", data_type, true)).unwrap();
+        assert!(unordered.dictionary.is_none());
+        assert_eq!(ArrowField::from(&unordered).dict_is_ordered(), Some(false));
+    }
+
+    #[test]
+    fn test_dictionary_data_type() {
+        for key_type in [
+            DataType::Int8,
+            DataType::Int16,
+            DataType::UInt32,
+            DataType::Int64,
+        ] {
+            let data_type =
+                DataType::Dictionary(Box::new(key_type.clone()), Box::new(DataType::Utf8));
+            let arrow_field = ArrowField::new(
+                "s",
+                DataType::Struct(Fields::from(vec![ArrowField::new(
+                    "d",
+                    data_type.clone(),
+                    true,
+                )])),
+                true,
+            );
+            let field = Field::try_from(&arrow_field).unwrap();
+            let d = &field.children[0];
+            assert_eq!(d.data_type(), data_type);
+            assert_eq!(ArrowField::from(d).data_type(), &data_type);
+            assert_eq!(ArrowField::from(&field), arrow_field);
+
+            let protos = Vec::<pb::Field>::from(&field);
+            let decoded = Field::from_proto_list(&protos).unwrap();
+            assert_eq!(decoded[0].children[0].data_type(), data_type);
+            assert_eq!(ArrowField::from(&decoded[0]), arrow_field);
+        }
+
+        let data_type = DataType::Dictionary(Box::new(DataType::Int16), Box::new(DataType::Utf8));
+        let mut field = Field::try_from(&ArrowField::new("d", data_type.clone(), true)).unwrap();
+        let arr: ArrayRef = Arc::new(DictionaryArray::<Int16Type>::from_iter(["a", "b", "a"]));
+        field.set_dictionary(&arr).unwrap();
+        assert_eq!(field.data_type(), data_type);
+        assert_eq!(
+            field.decode_dictionary().unwrap().data_type(),
+            DataType::Utf8
+        );
+    }
+
+    #[test]
+    fn test_set_dictionary_key_type_mismatch() {
+        let data_type = DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8));
+        let arrow_field = ArrowField::new(
+            "s",
+            DataType::Struct(Fields::from(vec![ArrowField::new("d", data_type, true)])),
+            true,
+        );
+        let mut field = Field::try_from(&arrow_field).unwrap();
+
+        let keys: ArrayRef = Arc::new(DictionaryArray::<Int8Type>::from_iter(["a", "b"]));
+        let arr: ArrayRef = Arc::new(StructArray::from(vec![(
+            ArrowField::new("d", keys.data_type().clone(), true),
+            keys,
+        )]));
+        let err = field.set_dictionary(&arr).unwrap_err();
+        assert!(matches!(err, Error::Schema(_)));
+        assert!(field.children[0].dictionary.is_none());
+
+        let keys: ArrayRef = Arc::new(DictionaryArray::<Int32Type>::from_iter(["a", "b"]));
+        let arr: ArrayRef = Arc::new(StructArray::from(vec![(
+            ArrowField::new("d", keys.data_type().clone(), true),
+            keys,
+        )]));
+        field.set_dictionary(&arr).unwrap();
+        assert!(field.children[0].dictionary.is_some());
+    }
+
+    #[test]
+    fn test_extension_type_round_trip() {
+        let arrow_field = ArrowField::new(
+            "tensor",
+            DataType::FixedSizeList(
+                Arc::new(ArrowField::new("item", DataType::Float32, true)),
+                6,
+            ),
+            true,
+        )
+        .with_metadata(HashMap::from([
+            (
+                "ARROW:extension:name".to_string(),
+                "arrow.fixed_shape_tensor".to_string(),
+            ),
+            (
+                "ARROW:extension:metadata".to_string(),
+                r#"{"shape":[2,3]}"#.to_string(),
+            ),
+        ]));
+        let field = Field::try_from(&arrow_field).unwrap();
+        assert_eq!(field.extension_name, "arrow.fixed_shape_tensor");
+        assert_eq!(
+            field.extension_metadata.as_deref(),
+            Some(r#"{"shape":[2,3]}"#)
+        );
+
+        let decoded = Field::from(&pb::Field::from(&field));
+        assert_eq!(decoded, field);
+        assert_eq!(ArrowField::from(&decoded), arrow_field);
+
+        let plain = Field::try_from(&ArrowField::new("a", DataType::Int32, true)).unwrap();
+        assert!(plain.extension_name.is_empty());
+        assert!(plain.extension_metadata.is_none());
+        assert!(ArrowField::from(&plain).metadata().is_empty());
+    }
+
+    #[test]
+    fn test_nested_extension_type_round_trip() {
+        let extension = |field: ArrowField, name: &str| {
+            field.with_metadata(HashMap::from([
+                ("ARROW:extension:name".to_string(), name.to_string()),
+                ("ARROW:extension:metadata".to_string(), String::new()),
+            ]))
+        };
+        let point = extension(
+            ArrowField::new("point", DataType::Binary, true),
+            "geoarrow.wkb",
+        );
+        let arrow_field = ArrowField::new(
+            "s",
+            DataType::Struct(Fields::from(vec![
+                point.clone(),
+                ArrowField::new("id", DataType::Int64, true),
+                ArrowField::new(
+                    "path",
+                    DataType::List(Arc::new(extension(
+                        ArrowField::new("item", DataType::Binary, true),
+                        "geoarrow.wkb",
+                    ))),
+                    true,
+                ),
+            ])),
+            true,
+        );
+
+        let field = Field::try_from(&arrow_field).unwrap();
+        assert_eq!(field.child("point").unwrap().extension_name, "geoarrow.wkb");
+        // An empty metadata value is read as no metadata.
+        assert!(field.child("point").unwrap().extension_metadata.is_none());
+        assert!(field.child("id").unwrap().extension_name.is_empty());
+        assert_eq!(
+            field.child("path").unwrap().children[0].extension_name,
+            "geoarrow.wkb"
+        );
+
+        assert_eq!(ArrowField::from(&field), arrow_field);
+
+        // Through the protobuf fields of the manifest too.
+        let schema = Schema::try_from(&ArrowSchema::new(vec![arrow_field.clone()])).unwrap();
+        let protos: Vec<pb::Field> = (&schema).into();
+        assert_eq!(
+            ArrowSchema::from(&Schema::from(&protos)).field(0),
+            &arrow_field
+        );
+
+        // The metadata value is written even if the field has none.
+        let mut field = Field::try_from(&ArrowField::new("wkb", DataType::Binary, true)).unwrap();
+        field.extension_name = "geoarrow.wkb".to_string();
+        assert_eq!(
+            ArrowField::from(&field),
+            extension(
+                ArrowField::new("wkb", DataType::Binary, true),
+                "geoarrow.wkb"
+            )
+        );
+    }
+
+    #[test]
+    fn test_decode_dictionary() {
+        let field: Field = ArrowField::new(
+            "d",
+            DataType::Dictionary(Box::new(DataType::UInt16), Box::new(DataType::Utf8)),
+            false,
+        )
+        .try_into()
+        .unwrap();
+        let decoded = field.decode_dictionary().unwrap();
+        assert_eq!(decoded.name, "d");
+        assert_eq!(decoded.data_type(), DataType::Utf8);
+        assert_eq!(decoded.encoding, Some(Encoding::VarBinary));
+        assert!(!decoded.nullable);
+        assert!(decoded.dictionary.is_none());
+
+        let field: Field = ArrowField::new(
+            "d",
+            DataType::Dictionary(Box::new(DataType::Int8), Box::new(DataType::Int32)),
+            true,
+        )
+        .try_into()
+        .unwrap();
+        let decoded = field.decode_dictionary().unwrap();
+        assert_eq!(decoded.data_type(), DataType::Int32);
+        assert_eq!(decoded.encoding, Some(Encoding::Plain));
+
+        let field: Field = ArrowField::new("i", DataType::Int32, true)
+            .try_into()
+            .unwrap();
+        assert_eq!(field.decode_dictionary().unwrap(), field);
+    }
+
+    #[test]
+    fn test_is_vector() {
+        let field: Field = ArrowField::new(
+            "vec",
+            DataType::FixedSizeList(
+                Arc::new(ArrowField::new("item", DataType::Float32, true)),
+                128,
+            ),
+            true,
+        )
+        .try_into()
+        .unwrap();
+        assert!(field.is_vector());
+        assert_eq!(field.vector_dimension(), Some(128));
+
+        let field: Field = ArrowField::new(
+            "structs",
+            DataType::FixedSizeList(
+                Arc::new(ArrowField::new(
+                    "item",
+                    DataType::Struct(Fields::from(vec![ArrowField::new(
+                        "a",
+                        DataType::Float32,
+                        true,
+                    )])),
+                    true,
+                )),
+                4,
+            ),
+            true,
+        )
+        .try_into()
+        .unwrap();
+        assert!(!field.is_vector());
+        assert_eq!(field.vector_dimension(), None);
+
+        let field: Field = ArrowField::new("f", DataType::Float32, true)
+            .try_into()
+            .unwrap();
+        assert!(!field.is_vector());
+    }
+
+    #[test]
+    fn test_without_dictionary_values() {
+        let dict_type = DataType::Dictionary(Box::new(DataType::UInt16), Box::new(DataType::Utf8));
+        let mut field: Field = ArrowField::new(
+            "s",
+            DataType::Struct(Fields::from(vec![
+                ArrowField::new("d", dict_type.clone(), true),
+                ArrowField::new("i", DataType::Int32, true),
+            ])),
+            true,
+        )
+        .try_into()
+        .unwrap();
+        let values: ArrayRef = Arc::new(StringArray::from(vec!["a", "b"]));
+        let child = field.child_mut("d").unwrap();
+        child.set_dictionary_values(&values);
+        child.dictionary.as_mut().unwrap().offset = 100;
+        child.dictionary.as_mut().unwrap().length = 20;
+
+        let stripped = field.without_dictionary_values();
+        let dict = stripped.child("d").unwrap().dictionary.as_ref().unwrap();
+        assert_eq!(dict.offset, 100);
+        assert_eq!(dict.length, 20);
+        assert!(dict.loaded_values().is_none());
+        assert_eq!(stripped.data_type(), field.data_type());
+        assert_eq!(stripped.child("i"), field.child("i"));
+
+        // The original field keeps its values.
+        assert_eq!(
+            field
+                .child("d")
+                .unwrap()
+                .dictionary
+                .as_ref()
+                .unwrap()
+                .values,
+            Some(values)
+        );
+    }
+
+    fn validated_field(arrow_field: ArrowField) -> Field {
+        let mut field = Field::try_from(&arrow_field).unwrap();
+        field.set_id(-1, &mut 0);
+        field
+    }
+
+    #[test]
+    fn test_validate() {
+        let field = validated_field(ArrowField::new(
+            "s",
+            DataType::Struct(Fields::from(vec![
+                ArrowField::new("a", DataType::Int32, true),
+                ArrowField::new(
+                    "l",
+                    DataType::List(Arc::new(ArrowField::new("item", DataType::Utf8, true))),
+                    true,
+                ),
+            ])),
+            true,
+        ));
+        field.validate().unwrap();
+
+        // Negative id.
+        let mut f = field.clone();
+        f.children[0].id = -1;
+        assert!(f.validate().unwrap_err().to_string().contains("s.a"));
+
+        // Duplicated ids.
+        let mut f = field.clone();
+        f.children[1].children[0].id = f.children[0].id;
+        assert!(f.validate().unwrap_err().to_string().contains("s.l.item"));
+
+        // Dangling parent id.
+        let mut f = field.clone();
+        f.children[1].parent_id = 100;
+        assert!(f.validate().unwrap_err().to_string().contains("s.l"));
+
+        // List without child.
+        let mut f = field.clone();
+        f.children[1].children.clear();
+        assert!(f.validate().unwrap_err().to_string().contains("s.l"));
+
+        // Duplicated child names in struct.
+        let mut f = field;
+        f.children[1].name = "a".to_string();
+        assert!(f
+            .validate()
+            .unwrap_err()
+            .to_string()
+            .contains("duplicated child name a"));
+    }
+
+    #[test]
+    fn test_validate_dictionary() {
+        let mut field = validated_field(ArrowField::new(
+            "d",
+            DataType::Dictionary(Box::new(DataType::UInt16), Box::new(DataType::Utf8)),
+            false,
+        ));
+        assert!(field.validate().is_err());
+
+        let arr: ArrayRef = Arc::new(DictionaryArray::<UInt16Type>::from_iter(["a", "b"]));
+        field.set_dictionary(&arr).unwrap();
+        field.validate().unwrap();
+    }
+
+    #[test]
+    fn test_all_ids() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("x", DataType::Int32, true),
+            ArrowField::new(
+                "a",
+                DataType::Struct(Fields::from(vec![
+                    ArrowField::new("b", DataType::Int32, true),
+                    ArrowField::new(
+                        "c",
+                        DataType::Struct(Fields::from(vec![
+                            ArrowField::new("d", DataType::Utf8, true),
+                            ArrowField::new("e", DataType::Float32, true),
+                        ])),
+                        true,
+                    ),
+                ])),
+                true,
+            ),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+
+        let field = schema.field("a").unwrap();
+        assert_eq!(field.all_ids(), vec![1, 2, 3, 4, 5]);
+        assert_eq!(*field.all_ids().iter().max().unwrap(), field.max_id());
+        assert_eq!(field.children[1].all_ids(), vec![3, 4, 5]);
+        assert_eq!(schema.field("x").unwrap().all_ids(), vec![0]);
+    }
+
+    #[test]
+    fn test_map_leaves_mut() {
+        let mut field = Field::try_from(&ArrowField::new(
+            "root",
+            DataType::Struct(Fields::from(vec![
+                ArrowField::new("a", DataType::Utf8, true),
+                ArrowField::new(
+                    "b",
+                    DataType::Struct(Fields::from(vec![
+                        ArrowField::new("c", DataType::Float32, true),
+                        ArrowField::new(
+                            "d",
+                            DataType::List(Arc::new(ArrowField::new("item", DataType::Utf8, true))),
+                            true,
+                        ),
+                    ])),
+                    true,
+                ),
+            ])),
+            true,
+        ))
+        .unwrap();
+
+        let mut num_leaves = 0;
+        field.map_leaves_mut(|f| {
+            f.extension_name = "marker".to_string();
+            num_leaves += 1;
+        });
+        assert_eq!(num_leaves, 3);
+
+        fn marked(field: &Field) -> Vec<String> {
+            let mut names = vec![];
+            if field.extension_name == "marker" {
+                names.push(field.name.clone());
+            }
+            names.extend(field.children.iter().flat_map(marked));
+            names
+        }
+        assert_eq!(marked(&field), vec!["a", "c", "item"]);
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let arrow_field = |names: &[&str]| {
+            ArrowField::new(
+                "s",
+                DataType::Struct(Fields::from(
+                    names
+                        .iter()
+                        .map(|name| {
+                            ArrowField::new(
+                                *name,
+                                DataType::Struct(Fields::from(vec![
+                                    ArrowField::new("z", DataType::Int32, true),
+                                    ArrowField::new("y", DataType::Utf8, true),
+                                ])),
+                                true,
+                            )
+                        })
+                        .collect::<Vec<_>>(),
+                )),
+                true,
+            )
+        };
+        let field = Field::try_from(&arrow_field(&["c", "a", "b"])).unwrap();
+        let names = |field: &Field| {
+            field
+                .children
+                .iter()
+                .map(|c| c.name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            field
+                .sorted_children()
+                .iter()
+                .map(|c| c.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["a", "b", "c"]
+        );
+        assert_eq!(names(&field), vec!["c", "a", "b"]);
+
+        let mut canonical = field.clone();
+        canonical.set_id(-1, &mut 0);
+        let ids = canonical
+            .children
+            .iter()
+            .map(|c| (c.name.clone(), c.id))
+            .collect::<HashMap<_, _>>();
+        canonical.canonicalize();
+        assert_eq!(names(&canonical), vec!["a", "b", "c"]);
+        for child in canonical.children.iter() {
+            assert_eq!(child.id, ids[&child.name]);
+            assert_eq!(names(child), vec!["y", "z"]);
+        }
+
+        // Idempotent, and the same for any input order.
+        let mut twice = canonical.clone();
+        twice.canonicalize();
+        assert_eq!(twice, canonical);
+        let mut other = Field::try_from(&arrow_field(&["b", "c", "a"])).unwrap();
+        other.canonicalize();
+        let mut expected = field.clone();
+        expected.canonicalize();
+        assert_eq!(other, expected);
+    }
+
+    #[test]
+    fn test_with_fresh_ids() {
+        let host = Schema::try_from(&ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, true),
+            ArrowField::new(
+                "b",
+                DataType::Struct(Fields::from(vec![ArrowField::new(
+                    "c",
+                    DataType::Utf8,
+                    true,
+                )])),
+                true,
+            ),
+        ]))
+        .unwrap();
+        let other = Schema::try_from(&ArrowSchema::new(vec![ArrowField::new(
+            "s",
+            DataType::Struct(Fields::from(vec![
+                ArrowField::new("x", DataType::Float32, true),
+                ArrowField::new(
+                    "y",
+                    DataType::List(Arc::new(ArrowField::new("item", DataType::Int64, true))),
+                    true,
+                ),
+            ])),
+            true,
+        )]))
+        .unwrap();
+        let subtree = &other.fields[0];
+        // Both schemas assign ids from 0.
+        assert_eq!(subtree.id, 0);
+
+        let start_id = host.max_field_id().unwrap() + 1;
+        let (grafted, next_id) = subtree.with_fresh_ids(start_id);
+        assert_eq!(next_id, start_id + 4);
+        assert_eq!(grafted.name, "s");
+        assert_eq!(grafted.data_type(), subtree.data_type());
+
+        fn ids(field: &Field) -> Vec<i32> {
+            let mut ids = vec![field.id];
+            ids.extend(field.children.iter().flat_map(ids));
+            ids
+        }
+        let host_ids = host.fields.iter().flat_map(ids).collect::<HashSet<_>>();
+        let grafted_ids = ids(&grafted);
+        assert_eq!(grafted_ids, (start_id..next_id).collect::<Vec<_>>());
+        assert!(grafted_ids.iter().all(|id| !host_ids.contains(id)));
+
+        assert_eq!(grafted.parent_id, -1);
+        assert!(grafted.children.iter().all(|c| c.parent_id == grafted.id));
+        let y = grafted.child("y").unwrap();
+        assert_eq!(y.children[0].parent_id, y.id);
+
+        // The original field is unchanged.
+        assert_eq!(ids(subtree), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let s = |x_type: DataType| {
+            ArrowField::new(
+                "s",
+                DataType::Struct(Fields::from(vec![
+                    ArrowField::new("x", x_type, true),
+                    ArrowField::new(
+                        "y",
+                        DataType::List(Arc::new(ArrowField::new("item", DataType::Int64, true))),
+                        true,
+                    ),
+                ])),
+                true,
+            )
+        };
+        let alone = Schema::try_from(&ArrowSchema::new(vec![s(DataType::Float32)])).unwrap();
+        let after = Schema::try_from(&ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, true),
+            s(DataType::Float32),
+        ]))
+        .unwrap();
+        let field = &alone.fields[0];
+        assert_ne!(field.id, after.fields[1].id);
+        assert_eq!(field.fingerprint(), after.fields[1].fingerprint());
+        assert_eq!(
+            field.fingerprint(),
+            field.with_fresh_ids(100).0.fingerprint()
+        );
+
+        let changed = Field::try_from(&s(DataType::Float64)).unwrap();
+        assert_ne!(field.fingerprint(), changed.fingerprint());
+        let mut renamed = field.clone();
+        renamed.children[0].name = "z".to_string();
+        assert_ne!(field.fingerprint(), renamed.fingerprint());
+
+        // Stable across builds, platforms and Rust releases.
+        let field = Field::try_from(&ArrowField::new("a", DataType::Int32, true)).unwrap();
+        assert_eq!(field.fingerprint(), 0x9e02_eccb_de98_e7da);
+    }
+
+    #[test]
+    fn test_extract() {
+        let c = ArrowField::new(
+            "c",
+            DataType::List(Arc::new(ArrowField::new("item", DataType::Int32, true))),
+            true,
+        );
+        let arrow_field = ArrowField::new(
+            "a",
+            DataType::Struct(Fields::from(vec![
+                ArrowField::new("b", DataType::Struct(Fields::from(vec![c.clone()])), true),
+                ArrowField::new("d", DataType::Utf8, true),
+            ])),
+            true,
+        );
+        let mut field = Field::try_from(&arrow_field).unwrap();
+        field.set_id(-1, &mut 0);
+
+        let extracted = field.extract(&["b", "c"]).unwrap();
+        assert_eq!(extracted.name, "c");
+        assert_eq!(extracted.parent_id(), -1);
+        assert_eq!(extracted.id, 2);
+        // The children are kept, with their parent.
+        assert_eq!(extracted.children[0].parent_id(), extracted.id);
+        assert_eq!(ArrowField::from(&extracted), c);
+
+        assert_eq!(field.extract(&[]).unwrap().name, "a");
+        assert!(field.extract(&["b", "x"]).is_err());
+        assert!(field.extract(&["d", "c"]).is_err());
+    }
+
+    #[test]
+    fn test_project_glob() {
+        let arrow_field = ArrowField::new(
+            "metrics",
+            DataType::Struct(Fields::from(vec![
+                ArrowField::new(
+                    "cpu",
+                    DataType::Struct(Fields::from(vec![
+                        ArrowField::new("user", DataType::Float64, true),
+                        ArrowField::new("sys", DataType::Float64, true),
+                    ])),
+                    true,
+                ),
+                ArrowField::new(
+                    "cache",
+                    DataType::Struct(Fields::from(vec![
+                        ArrowField::new("hits", DataType::Int64, true),
+                        ArrowField::new("user", DataType::Int64, true),
+                    ])),
+                    true,
+                ),
+                ArrowField::new("mem", DataType::Int64, true),
+            ])),
+            true,
+        );
+        let mut field = Field::try_from(&arrow_field).unwrap();
+        field.set_id(-1, &mut 0);
+        let names = |f: &Field| {
+            f.children
+                .iter()
+                .map(|c| c.name.as_str())
+                .collect::<Vec<_>>()
+        };
+
+        // `*` keeps all children, with their subtrees.
+        let projected = field.project_glob("*").unwrap().unwrap();
+        assert_eq!(projected, field);
+
+        let projected = field.project_glob("c*").unwrap().unwrap();
+        assert_eq!(names(&projected), vec!["cpu", "cache"]);
+        assert_eq!(projected.child("cpu"), field.child("cpu"));
+
+        // One segment per level.
+        let projected = field.project_glob("*.user").unwrap().unwrap();
+        assert_eq!(names(&projected), vec!["cpu", "cache"]);
+        assert_eq!(names(projected.child("cpu").unwrap()), vec!["user"]);
+        assert_eq!(projected.child("cpu").unwrap().children[0].id, 2);
+        assert_eq!(names(projected.child("cache").unwrap()), vec!["user"]);
+
+        let projected = field.project_glob("c?c*.h*").unwrap().unwrap();
+        assert_eq!(names(&projected), vec!["cache"]);
+        assert_eq!(names(projected.child("cache").unwrap()), vec!["hits"]);
+
+        // `*` does not cross levels.
+        assert!(field.project_glob("user").unwrap().is_none());
+        assert!(field.project_glob("mem.*").unwrap().is_none());
+        assert!(field.project_glob("x*").unwrap().is_none());
+
+        assert!(field.project_glob("").is_err());
+        assert!(field.project_glob("cpu..user").is_err());
+    }
+
+    #[test]
+    fn test_exclude_by_id() {
+        let arrow_field = ArrowField::new(
+            "metrics",
+            DataType::Struct(Fields::from(vec![
+                ArrowField::new(
+                    "cpu",
+                    DataType::Struct(Fields::from(vec![
+                        ArrowField::new("user", DataType::Float64, true),
+                        ArrowField::new("sys", DataType::Float64, true),
+                    ])),
+                    true,
+                ),
+                ArrowField::new("mem", DataType::Int64, true),
+            ])),
+            true,
+        );
+        let mut field = Field::try_from(&arrow_field).unwrap();
+        field.set_id(-1, &mut 0);
+        let cpu = field.child("cpu").unwrap();
+        let user_id = cpu.child("user").unwrap().id;
+
+        // The nested field goes, its siblings stay, even after a rename.
+        field.children[0].children[0].name = "usr".to_string();
+        let excluded = field.exclude_by_id(&HashSet::from([user_id])).unwrap();
+        assert_eq!(excluded.children.len(), 2);
+        let cpu = excluded.child("cpu").unwrap();
+        assert_eq!(cpu.children.len(), 1);
+        assert_eq!(cpu.children[0].name, "sys");
+        assert_eq!(excluded.child("mem"), field.child("mem"));
+
+        // Excluding a struct drops its subtree.
+        let cpu_id = field.child("cpu").unwrap().id;
+        let excluded = field.exclude_by_id(&HashSet::from([cpu_id])).unwrap();
+        assert_eq!(excluded.children.len(), 1);
+        assert_eq!(excluded.children[0].name, "mem");
+
+        // A struct left without children is dropped too.
+        let sys_id = field.child("cpu").unwrap().child("sys").unwrap().id;
+        let excluded = field
+            .exclude_by_id(&HashSet::from([user_id, sys_id]))
+            .unwrap();
+        assert_eq!(excluded.children.len(), 1);
+        assert_eq!(excluded.children[0].name, "mem");
+
+        assert!(field.exclude_by_id(&HashSet::from([field.id])).is_none());
+        assert_eq!(field.exclude_by_id(&HashSet::new()).unwrap(), field);
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*", "abc"));
+        assert!(glob_match("*c", "abc"));
+        assert!(glob_match("a?c", "abc"));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(!glob_match("a?c", "ac"));
+        assert!(!glob_match("a*d", "abc"));
+        assert!(!glob_match("abc", "abcd"));
+    }
+
     #[test]
     fn test_field_intersection() {
         let f1: Field = ArrowField::new("a", DataType::Int32, true)
@@ -645,4 +2785,119 @@ mod tests {
         .unwrap();
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn test_diff() {
+        let field = |b_type: DataType, b_nullable: bool| -> Field {
+            ArrowField::new(
+                "a",
+                DataType::Struct(Fields::from(vec![
+                    ArrowField::new("b", b_type, b_nullable),
+                    ArrowField::new("c", DataType::Utf8, true),
+                ])),
+                true,
+            )
+            .try_into()
+            .unwrap()
+        };
+        let expected = field(DataType::Int32, true);
+        assert_eq!(field(DataType::Int32, true).diff(&expected), None);
+
+        let diff = field(DataType::Int64, true).diff(&expected).unwrap();
+        assert_eq!(diff.path, "a.b");
+        assert_eq!(
+            diff.reason,
+            DiffReason::Type(DataType::Int64, DataType::Int32)
+        );
+        assert_eq!(diff.to_string(), "column `a.b` is Int64, expected: Int32");
+
+        let diff = field(DataType::Int32, false).diff(&expected).unwrap();
+        assert_eq!(diff.path, "a.b");
+        assert_eq!(diff.reason, DiffReason::Nullability(false, true));
+
+        let mut renamed = expected.clone();
+        renamed.children[1].name = "d".to_string();
+        let diff = renamed.diff(&expected).unwrap();
+        assert_eq!(diff.path, "a.d");
+        assert_eq!(
+            diff.reason,
+            DiffReason::Name("d".to_string(), "c".to_string())
+        );
+
+        let mut encoded = expected.clone();
+        encoded.children[1].encoding = Some(Encoding::Dictionary);
+        let diff = encoded.diff(&expected).unwrap();
+        assert_eq!(diff.path, "a.c");
+        assert_eq!(
+            diff.reason,
+            DiffReason::Encoding(Some(Encoding::Dictionary), Some(Encoding::VarBinary))
+        );
+
+        let mut pruned = expected.clone();
+        pruned.children.pop();
+        let diff = pruned.diff(&expected).unwrap();
+        assert_eq!(diff.path, "a");
+        assert_eq!(diff.reason, DiffReason::ChildCount(1, 2));
+
+        // Ids are not compared.
+        let (renumbered, _) = expected.with_fresh_ids(10);
+        assert_eq!(renumbered.diff(&expected), None);
+    }
+
+    #[test]
+    fn test_physical_layout() {
+        let layout = |data_type: DataType| {
+            Field::try_from(&ArrowField::new("a", data_type, true))
+                .unwrap()
+                .physical_layout()
+        };
+        let buffer = |kind, data_type| BufferDescriptor { kind, data_type };
+
+        assert_eq!(
+            layout(DataType::Float32),
+            LayoutDescriptor {
+                encoding: Some(Encoding::Plain),
+                buffers: vec![buffer(BufferKind::Values, DataType::Float32)],
+            }
+        );
+        assert_eq!(
+            layout(DataType::Utf8),
+            LayoutDescriptor {
+                encoding: Some(Encoding::VarBinary),
+                buffers: vec![
+                    buffer(BufferKind::Values, DataType::UInt8),
+                    buffer(BufferKind::Positions, DataType::Int64),
+                ],
+            }
+        );
+        assert_eq!(
+            layout(DataType::FixedSizeList(
+                Arc::new(ArrowField::new("item", DataType::Float32, true)),
+                8,
+            ))
+            .buffers,
+            vec![buffer(BufferKind::Values, DataType::Float32)]
+        );
+
+        let list = Field::try_from(&ArrowField::new(
+            "a",
+            DataType::List(Arc::new(ArrowField::new("item", DataType::Utf8, true))),
+            true,
+        ))
+        .unwrap();
+        assert_eq!(
+            list.physical_layout(),
+            LayoutDescriptor {
+                encoding: Some(Encoding::Plain),
+                buffers: vec![buffer(BufferKind::Offsets, DataType::Int32)],
+            }
+        );
+        assert_eq!(
+            list.children[0].physical_layout().buffers,
+            vec![
+                buffer(BufferKind::Values, DataType::UInt8),
+                buffer(BufferKind::Positions, DataType::Int64),
+            ]
+        );
+    }
 }
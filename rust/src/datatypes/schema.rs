@@ -17,6 +17,7 @@
 use std::{
     collections::HashMap,
     fmt::{self, Debug, Formatter},
+    sync::Arc,
 };
 
 use arrow_array::RecordBatch;
@@ -153,6 +154,43 @@ impl Schema {
         protos.iter().map(|f| f.id).collect()
     }
 
+    /// The vector fields, at any depth, on which a vector index is built.
+    pub fn indexed_vector_fields(&self) -> Vec<&Field> {
+        fn collect<'a>(field: &'a Field, fields: &mut Vec<&'a Field>) {
+            if field.indexed && field.is_vector() {
+                fields.push(field);
+            }
+            field.children.iter().for_each(|c| collect(c, fields));
+        }
+        let mut fields = vec![];
+        self.fields.iter().for_each(|f| collect(f, &mut fields));
+        fields
+    }
+
+    /// Get a field, at any depth, by its id.
+    pub fn field_by_id(&self, id: i32) -> Option<&Field> {
+        self.fields.iter().find_map(|f| f.field_by_id(id))
+    }
+
+    /// Full path of a field, i.e., the names of the fields from the root down to
+    /// this field, joined by `.`.
+    ///
+    /// Returns `None` if the field, or one of its ancestors, does not exist.
+    pub fn path_of(&self, field_id: i32) -> Option<String> {
+        if field_id < 0 {
+            return None;
+        }
+        let mut names = vec![];
+        let mut id = field_id;
+        while id >= 0 {
+            let field = self.field_by_id(id)?;
+            names.push(field.name.as_str());
+            id = field.parent_id();
+        }
+        names.reverse();
+        Some(names.join("."))
+    }
+
     pub(crate) fn mut_field_by_id(&mut self, id: i32) -> Option<&mut Field> {
         for field in self.fields.as_mut_slice() {
             if field.id == id {
@@ -169,12 +207,12 @@ impl Schema {
         self.fields.iter().map(|f| f.max_id()).max()
     }
 
-    /// Load dictionary value array from manifest files.
-    pub(crate) async fn load_dictionary<'a>(&mut self, reader: &dyn ObjectReader) -> Result<()> {
+    /// Attach the reader of the manifest file, from which the dictionary value
+    /// arrays are lazily loaded.
+    pub(crate) fn set_dictionary_reader(&mut self, reader: &Arc<dyn ObjectReader>) {
         for field in self.fields.as_mut_slice() {
-            field.load_dictionary(reader).await?;
+            field.set_dictionary_reader(reader);
         }
-        Ok(())
     }
 
     /// Recursively attach set up dictionary values to the dictionary fields.
@@ -186,7 +224,23 @@ impl Schema {
                     field.name
                 ))
             })?;
-            field.set_dictionary(column);
+            field.set_dictionary(column)?;
+        }
+        Ok(())
+    }
+
+    /// Check that the dictionaries of the schema are worth it for `batch`.
+    ///
+    /// See [`Field::check_dictionary_cardinality`].
+    pub(crate) fn check_dictionary_cardinality(
+        &self,
+        batch: &RecordBatch,
+        max_ratio: f64,
+    ) -> Result<()> {
+        for field in self.fields.iter() {
+            if let Some(column) = batch.column_by_name(&field.name) {
+                field.check_dictionary_cardinality(column, max_ratio)?;
+            }
         }
         Ok(())
     }
@@ -261,21 +315,10 @@ impl From<&Self> for Schema {
 /// Convert list of protobuf `Field` to a Schema.
 impl From<&Vec<pb::Field>> for Schema {
     fn from(fields: &Vec<pb::Field>) -> Self {
-        let mut schema = Self {
-            fields: vec![],
+        Self {
+            fields: Field::from_proto_list(fields).unwrap(),
             metadata: HashMap::default(),
-        };
-
-        fields.iter().for_each(|f| {
-            if f.parent_id == -1 {
-                schema.fields.push(Field::from(f));
-            } else {
-                let parent = schema.mut_field_by_id(f.parent_id).unwrap();
-                parent.children.push(Field::from(f));
-            }
-        });
-
-        schema
+        }
     }
 }
 
@@ -403,6 +446,82 @@ mod tests {
         assert_eq!(field.data_type(), DataType::Boolean);
     }
 
+    #[test]
+    fn test_field_by_id() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new(
+                        "c",
+                        DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+                            "d",
+                            DataType::List(Arc::new(ArrowField::new(
+                                "item",
+                                DataType::Int64,
+                                true,
+                            ))),
+                            true,
+                        )])),
+                        true,
+                    ),
+                ])),
+                true,
+            ),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+
+        let item = schema.field_by_id(5).unwrap();
+        assert_eq!(item.name, "item");
+        assert_eq!(item.data_type(), DataType::Int64);
+        assert_eq!(schema.field_by_id(3).unwrap().name, "c");
+        assert!(schema.field_by_id(6).is_none());
+
+        let b = &schema.fields[1];
+        assert_eq!(b.field_by_id(1).unwrap(), b);
+        assert_eq!(b.field_by_id(4).unwrap().name, "d");
+        assert!(schema.fields[0].field_by_id(5).is_none());
+    }
+
+    #[test]
+    fn test_path_of() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new(
+                        "c",
+                        DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+                            "f2",
+                            DataType::Boolean,
+                            false,
+                        )])),
+                        true,
+                    ),
+                ])),
+                true,
+            ),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+
+        for path in ["a", "b", "b.f1", "b.c", "b.c.f2"] {
+            let field = schema.field(path).unwrap();
+            assert_eq!(schema.path_of(field.id).unwrap(), path);
+        }
+        // Also from the flat fields of the manifest.
+        let protos: Vec<pb::Field> = (&schema).into();
+        let schema = Schema::from(&protos);
+        let id = schema.field("b.c.f2").unwrap().id;
+        assert_eq!(schema.path_of(id).unwrap(), "b.c.f2");
+
+        assert!(schema.path_of(schema.max_field_id().unwrap() + 1).is_none());
+        assert!(schema.path_of(-1).is_none());
+    }
+
     #[test]
     fn test_exclude_fields() {
         let arrow_schema = ArrowSchema::new(vec![
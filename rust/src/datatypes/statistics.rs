@@ -0,0 +1,392 @@
+// Copyright 2023 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Statistics of the values of a field, written along its data.
+
+use std::hash::Hasher;
+
+use arrow::compute::{
+    max, max_binary, max_boolean, max_string, min, min_binary, min_boolean, min_string,
+};
+use arrow_array::{
+    cast::{
+        as_boolean_array, as_generic_binary_array, as_largestring_array, as_primitive_array,
+        as_string_array,
+    },
+    make_array,
+    types::*,
+    ArrayRef,
+};
+use arrow_buffer::Buffer;
+use arrow_data::ArrayData;
+use arrow_schema::{DataType, TimeUnit};
+use datafusion::scalar::ScalarValue;
+use siphasher::sip::SipHasher13;
+
+use super::LogicalType;
+use crate::format::pb;
+use crate::Result;
+
+/// Statistics of the values of a field in a file, to plan a query without reading
+/// the data pages, e.g., to skip the files whose values can not match a predicate.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FieldStatistics {
+    /// Number of null values.
+    pub null_count: u64,
+
+    /// Estimate of the number of distinct non-null values, from a HyperLogLog
+    /// sketch. Exact for a few values, within a few percent for many. Only
+    /// collected for the types of [`Self::min`].
+    pub distinct_count: u64,
+
+    /// Smallest non-null value. Only collected for the primitive, string and binary
+    /// types.
+    pub min: Option<ScalarValue>,
+
+    /// Largest non-null value, see [`Self::min`].
+    pub max: Option<ScalarValue>,
+}
+
+/// Number of bits of the hash selecting a register of a [`DistinctSketch`].
+const SKETCH_BITS: u32 = 12;
+
+/// HyperLogLog sketch of the distinct values of a field, with `2^SKETCH_BITS`
+/// one-byte registers, i.e., a standard error of 1.6%.
+#[derive(Debug, Clone, Default)]
+struct DistinctSketch {
+    /// Allocated on the first value added.
+    registers: Vec<u8>,
+}
+
+impl DistinctSketch {
+    /// Add the bytes of a value.
+    fn insert(&mut self, value: &[u8]) {
+        if self.registers.is_empty() {
+            self.registers = vec![0; 1 << SKETCH_BITS];
+        }
+        let mut hasher = SipHasher13::new();
+        hasher.write(value);
+        let hash = hasher.finish();
+        let index = (hash >> (64 - SKETCH_BITS)) as usize;
+        // Position of the first set bit of the rest of the hash, at most 64 - SKETCH_BITS + 1.
+        let rank = ((hash << SKETCH_BITS) | (1 << (SKETCH_BITS - 1))).leading_zeros() + 1;
+        self.registers[index] = self.registers[index].max(rank as u8);
+    }
+
+    /// Estimate the number of distinct values added, counting the empty registers
+    /// for the small counts.
+    fn estimate(&self) -> u64 {
+        if self.registers.is_empty() {
+            return 0;
+        }
+        let m = self.registers.len() as f64;
+        let sum = self
+            .registers
+            .iter()
+            .map(|&r| 2_f64.powi(-(r as i32)))
+            .sum::<f64>();
+        let estimate = 0.7213 / (1.0 + 1.079 / m) * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+}
+
+/// Min and max of a primitive array as `$scalar` values, adding its values to
+/// `$sketch`.
+macro_rules! primitive_stats {
+    ($sketch:expr, $array:expr, $arrow_type:ty, $scalar:ident $(, $tz:expr)?) => {{
+        let array = as_primitive_array::<$arrow_type>($array);
+        for value in array.iter().flatten() {
+            $sketch.insert(&value.to_le_bytes());
+        }
+        (
+            ScalarValue::$scalar(min(array) $(, $tz.clone())?),
+            ScalarValue::$scalar(max(array) $(, $tz.clone())?),
+        )
+    }};
+}
+
+/// Collect the [`FieldStatistics`] of the arrays written to a field, in one linear
+/// pass over each array.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct StatisticsCollector {
+    null_count: u64,
+
+    min: Option<ScalarValue>,
+
+    max: Option<ScalarValue>,
+
+    sketch: DistinctSketch,
+}
+
+impl StatisticsCollector {
+    /// Add the values of an array.
+    pub(crate) fn update(&mut self, array: &ArrayRef) -> Result<()> {
+        self.null_count += array.null_count() as u64;
+        if array.null_count() == array.len() {
+            return Ok(());
+        }
+        let sketch = &mut self.sketch;
+        let (min_value, max_value) = match array.data_type() {
+            DataType::Boolean => {
+                let array = as_boolean_array(array);
+                for value in array.iter().flatten() {
+                    sketch.insert(&[value as u8]);
+                }
+                (
+                    ScalarValue::Boolean(min_boolean(array)),
+                    ScalarValue::Boolean(max_boolean(array)),
+                )
+            }
+            DataType::Int8 => primitive_stats!(sketch, array, Int8Type, Int8),
+            DataType::Int16 => primitive_stats!(sketch, array, Int16Type, Int16),
+            DataType::Int32 => primitive_stats!(sketch, array, Int32Type, Int32),
+            DataType::Int64 => primitive_stats!(sketch, array, Int64Type, Int64),
+            DataType::UInt8 => primitive_stats!(sketch, array, UInt8Type, UInt8),
+            DataType::UInt16 => primitive_stats!(sketch, array, UInt16Type, UInt16),
+            DataType::UInt32 => primitive_stats!(sketch, array, UInt32Type, UInt32),
+            DataType::UInt64 => primitive_stats!(sketch, array, UInt64Type, UInt64),
+            DataType::Float32 => primitive_stats!(sketch, array, Float32Type, Float32),
+            DataType::Float64 => primitive_stats!(sketch, array, Float64Type, Float64),
+            DataType::Date32 => primitive_stats!(sketch, array, Date32Type, Date32),
+            DataType::Date64 => primitive_stats!(sketch, array, Date64Type, Date64),
+            DataType::Timestamp(TimeUnit::Second, tz) => {
+                primitive_stats!(sketch, array, TimestampSecondType, TimestampSecond, tz)
+            }
+            DataType::Timestamp(TimeUnit::Millisecond, tz) => primitive_stats!(
+                sketch,
+                array,
+                TimestampMillisecondType,
+                TimestampMillisecond,
+                tz
+            ),
+            DataType::Timestamp(TimeUnit::Microsecond, tz) => primitive_stats!(
+                sketch,
+                array,
+                TimestampMicrosecondType,
+                TimestampMicrosecond,
+                tz
+            ),
+            DataType::Timestamp(TimeUnit::Nanosecond, tz) => primitive_stats!(
+                sketch,
+                array,
+                TimestampNanosecondType,
+                TimestampNanosecond,
+                tz
+            ),
+            DataType::Utf8 => {
+                let array = as_string_array(array);
+                for value in array.iter().flatten() {
+                    sketch.insert(value.as_bytes());
+                }
+                (
+                    ScalarValue::Utf8(min_string(array).map(str::to_string)),
+                    ScalarValue::Utf8(max_string(array).map(str::to_string)),
+                )
+            }
+            DataType::LargeUtf8 => {
+                let array = as_largestring_array(array);
+                for value in array.iter().flatten() {
+                    sketch.insert(value.as_bytes());
+                }
+                (
+                    ScalarValue::LargeUtf8(min_string(array).map(str::to_string)),
+                    ScalarValue::LargeUtf8(max_string(array).map(str::to_string)),
+                )
+            }
+            DataType::Binary => {
+                let array = as_generic_binary_array::<i32>(array);
+                for value in array.iter().flatten() {
+                    sketch.insert(value);
+                }
+                (
+                    ScalarValue::Binary(min_binary(array).map(<[u8]>::to_vec)),
+                    ScalarValue::Binary(max_binary(array).map(<[u8]>::to_vec)),
+                )
+            }
+            DataType::LargeBinary => {
+                let array = as_generic_binary_array::<i64>(array);
+                for value in array.iter().flatten() {
+                    sketch.insert(value);
+                }
+                (
+                    ScalarValue::LargeBinary(min_binary(array).map(<[u8]>::to_vec)),
+                    ScalarValue::LargeBinary(max_binary(array).map(<[u8]>::to_vec)),
+                )
+            }
+            _ => return Ok(()),
+        };
+        self.min = Some(match self.min.take() {
+            Some(current) if current <= min_value => current,
+            _ => min_value,
+        });
+        self.max = Some(match self.max.take() {
+            Some(current) if current >= max_value => current,
+            _ => max_value,
+        });
+        Ok(())
+    }
+
+    /// The statistics of all the arrays added.
+    pub(crate) fn finish(&self) -> FieldStatistics {
+        FieldStatistics {
+            null_count: self.null_count,
+            distinct_count: self.sketch.estimate(),
+            min: self.min.clone(),
+            max: self.max.clone(),
+        }
+    }
+}
+
+impl FieldStatistics {
+    /// Decode the statistics of a field of type `logical_type`.
+    pub(crate) fn from_pb(proto: &pb::FieldStatistics, logical_type: &LogicalType) -> Result<Self> {
+        let decode = |bytes: &Vec<u8>| {
+            let data_type = DataType::try_from(logical_type)?;
+            decode_scalar(bytes, &data_type)
+        };
+        Ok(Self {
+            null_count: proto.null_count,
+            distinct_count: proto.distinct_count,
+            min: proto.min.as_ref().map(decode).transpose()?,
+            max: proto.max.as_ref().map(decode).transpose()?,
+        })
+    }
+}
+
+impl From<&FieldStatistics> for pb::FieldStatistics {
+    fn from(stats: &FieldStatistics) -> Self {
+        Self {
+            null_count: stats.null_count,
+            distinct_count: stats.distinct_count,
+            min: stats.min.as_ref().map(encode_scalar),
+            max: stats.max.as_ref().map(encode_scalar),
+        }
+    }
+}
+
+/// The little-endian value of a fixed-width scalar, or the bytes of a string or
+/// binary scalar.
+fn encode_scalar(value: &ScalarValue) -> Vec<u8> {
+    let data = value.to_array().to_data();
+    match data.data_type() {
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Binary | DataType::LargeBinary => {
+            data.buffers()[1].as_slice().to_vec()
+        }
+        _ => data.buffers()[0].as_slice().to_vec(),
+    }
+}
+
+/// Decode a scalar encoded by [`encode_scalar`].
+fn decode_scalar(bytes: &[u8], data_type: &DataType) -> Result<ScalarValue> {
+    let builder = ArrayData::builder(data_type.clone()).len(1);
+    let builder = match data_type {
+        DataType::Utf8 | DataType::Binary => builder
+            .add_buffer(Buffer::from_slice_ref([0, bytes.len() as i32]))
+            .add_buffer(Buffer::from(bytes)),
+        DataType::LargeUtf8 | DataType::LargeBinary => builder
+            .add_buffer(Buffer::from_slice_ref([0, bytes.len() as i64]))
+            .add_buffer(Buffer::from(bytes)),
+        _ => builder.add_buffer(Buffer::from(bytes)),
+    };
+    let array = make_array(builder.build()?);
+    Ok(ScalarValue::try_from_array(&array, 0)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use arrow_array::{BooleanArray, Float64Array, Int32Array, Int64Array, StringArray};
+
+    fn collect(arrays: &[ArrayRef]) -> FieldStatistics {
+        let mut collector = StatisticsCollector::default();
+        for array in arrays {
+            collector.update(array).unwrap();
+        }
+        collector.finish()
+    }
+
+    #[test]
+    fn test_collect() {
+        let first: ArrayRef = Arc::new(Int32Array::from(vec![
+            Some(3),
+            None,
+            Some(-1),
+            Some(3),
+            Some(7),
+            None,
+        ]));
+        assert_eq!(
+            collect(&[first.clone()]),
+            FieldStatistics {
+                null_count: 2,
+                distinct_count: 3,
+                min: Some(ScalarValue::Int32(Some(-1))),
+                max: Some(ScalarValue::Int32(Some(7))),
+            }
+        );
+
+        let second: ArrayRef = Arc::new(Int32Array::from(vec![Some(10), None, Some(3)]));
+        let stats = collect(&[first, second]);
+        assert_eq!(stats.null_count, 3);
+        assert_eq!(stats.distinct_count, 4);
+        assert_eq!(stats.min, Some(ScalarValue::Int32(Some(-1))));
+        assert_eq!(stats.max, Some(ScalarValue::Int32(Some(10))));
+
+        let array: ArrayRef = Arc::new(Int32Array::from(vec![None, None]));
+        let stats = collect(&[array]);
+        assert_eq!(stats.null_count, 2);
+        assert_eq!(stats.distinct_count, 0);
+        assert!(stats.min.is_none() && stats.max.is_none());
+    }
+
+    #[test]
+    fn test_distinct_count_estimate() {
+        // 10000 distinct values over two overlapping arrays.
+        let arrays: Vec<ArrayRef> = vec![
+            Arc::new(Int64Array::from_iter_values(0..6000)),
+            Arc::new(Int64Array::from_iter_values(4000..10000)),
+        ];
+        let distinct_count = collect(&arrays).distinct_count as f64;
+        assert!(
+            (distinct_count - 10000.0).abs() < 500.0,
+            "distinct count {}",
+            distinct_count
+        );
+    }
+
+    #[test]
+    fn test_scalar_round_trip() {
+        let arrays: Vec<ArrayRef> = vec![
+            Arc::new(Int32Array::from(vec![5, -2, 9])),
+            Arc::new(Float64Array::from(vec![0.5, -2.25])),
+            Arc::new(BooleanArray::from(vec![true, false])),
+            Arc::new(StringArray::from(vec!["pear", "apple", "fig"])),
+        ];
+        for array in arrays {
+            let stats = collect(&[array.clone()]);
+            let logical_type = LogicalType::try_from(array.data_type()).unwrap();
+            let proto = pb::FieldStatistics::from(&stats);
+            let decoded = FieldStatistics::from_pb(&proto, &logical_type).unwrap();
+            assert_eq!(decoded, stats);
+        }
+    }
+}
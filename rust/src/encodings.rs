@@ -5,6 +5,7 @@ use arrow_array::{Array, ArrayRef, UInt32Array};
 use async_trait::async_trait;
 
 pub mod binary;
+pub mod compression;
 pub mod dictionary;
 pub mod plain;
 pub mod rle;
@@ -14,7 +15,7 @@ use crate::format::pb;
 use crate::io::ReadBatchParams;
 
 /// Encoding enum.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Encoding {
     /// Plain encoding.
     Plain,
@@ -192,6 +192,7 @@ impl<'a, T: ByteArrayType> BinaryDecoder<'a, T> {
         assert!(positions.len() >= range.end);
         let start = positions.value(range.start);
         let end = positions.value(range.end);
+        check_range_size::<T>(end - start)?;
 
         let slice = positions.slice(range.start, range.len() + 1);
         let position_slice: &Int64Array = as_primitive_array(slice.borrow());
@@ -255,6 +256,20 @@ impl<'a, T: ByteArrayType> BinaryDecoder<'a, T> {
     }
 }
 
+/// Check that the offsets of `T` can address `size` bytes, i.e., at most
+/// `i32::MAX` bytes for the non-large types.
+fn check_range_size<T: ByteArrayType>(size: i64) -> Result<()> {
+    if !T::Offset::IS_LARGE && size > i32::MAX as i64 {
+        return Err(crate::Error::IO(format!(
+            "{} array of {} bytes overflows its 32-bit offsets, store it as Large{} instead",
+            T::DATA_TYPE,
+            size,
+            T::DATA_TYPE
+        )));
+    }
+    Ok(())
+}
+
 fn plan_take_chunks(
     positions: &Int64Array,
     indices: &UInt32Array,
@@ -466,6 +481,22 @@ mod tests {
         .await;
     }
 
+    #[tokio::test]
+    async fn test_large_utf8_long_strings() {
+        let long = "x".repeat(1024 * 1024);
+        test_round_trips(&[
+            &LargeStringArray::from(vec![Some(long.as_str()), None, Some("a")]),
+            &LargeStringArray::from(vec![Some(long.as_str()), Some(long.as_str())]),
+        ])
+        .await;
+
+        assert!(check_range_size::<Utf8Type>(i32::MAX as i64).is_ok());
+        assert!(check_range_size::<Utf8Type>(i32::MAX as i64 + 1).is_err());
+        assert!(check_range_size::<BinaryType>(i32::MAX as i64 + 1).is_err());
+        assert!(check_range_size::<LargeUtf8Type>(i32::MAX as i64 + 1).is_ok());
+        assert!(check_range_size::<LargeBinaryType>(i32::MAX as i64 + 1).is_ok());
+    }
+
     #[tokio::test]
     async fn test_write_binary_data_with_offset() {
         let array: StringArray = StringArray::from(vec![Some("d"), Some("e")]).slice(1, 1);
@@ -0,0 +1,196 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Block compression of the pages of a field.
+//!
+//! The compression is applied on top of the encoding: a page is encoded in memory
+//! first, then the encoded bytes are compressed as one block. A compressed page is
+//! stored as:
+//!
+//! - the length of the compressed block, as a little-endian `u64`.
+//! - the position returned by the encoder within the encoded page, as a
+//!   little-endian `u64`.
+//! - the compressed block.
+//!
+//! Compressed pages lose random access: the whole page is read and decompressed
+//! to read any of its values.
+
+use std::ops::Range;
+
+use async_trait::async_trait;
+use byteorder::{ByteOrder, LittleEndian};
+use bytes::Bytes;
+use object_store::path::Path;
+use tokio::io::AsyncWriteExt;
+
+use crate::format::pb;
+use crate::io::{object_reader::ObjectReader, object_writer::ObjectWriter, ObjectStore};
+use crate::{Error, Result};
+
+/// Size of the header of a compressed page.
+const HEADER_SIZE: usize = 16;
+
+/// Block compression codec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Compression {
+    /// Zstandard, with its compression level.
+    Zstd { level: i32 },
+    /// LZ4 block format.
+    Lz4,
+}
+
+impl Compression {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Self::Zstd { level } => Ok(zstd::bulk::compress(data, *level)?),
+            Self::Lz4 => Ok(lz4_flex::compress_prepend_size(data)),
+        }
+    }
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Self::Zstd { .. } => Ok(zstd::stream::decode_all(data)?),
+            Self::Lz4 => lz4_flex::decompress_size_prepended(data)
+                .map_err(|e| Error::IO(format!("Failed to decompress LZ4 page: {}", e))),
+        }
+    }
+}
+
+impl From<&Compression> for pb::Compression {
+    fn from(compression: &Compression) -> Self {
+        match compression {
+            Compression::Zstd { level } => Self {
+                codec: pb::compression::Codec::Zstd as i32,
+                level: *level,
+            },
+            Compression::Lz4 => Self {
+                codec: pb::compression::Codec::Lz4 as i32,
+                level: 0,
+            },
+        }
+    }
+}
+
+impl From<&pb::Compression> for Option<Compression> {
+    fn from(compression: &pb::Compression) -> Self {
+        match compression.codec() {
+            pb::compression::Codec::None => None,
+            pb::compression::Codec::Zstd => Some(Compression::Zstd {
+                level: compression.level,
+            }),
+            pb::compression::Codec::Lz4 => Some(Compression::Lz4),
+        }
+    }
+}
+
+/// In-memory buffer of a page to compress.
+///
+/// The encoders write to [`PageBuffer::writer`], at positions relative to the
+/// beginning of the page.
+pub(crate) struct PageBuffer {
+    store: ObjectStore,
+    path: Path,
+    pub(crate) writer: ObjectWriter,
+}
+
+impl PageBuffer {
+    pub(crate) async fn try_new() -> Result<Self> {
+        let store = ObjectStore::memory();
+        let path = Path::from("page");
+        let writer = store.create(&path).await?;
+        Ok(Self {
+            store,
+            path,
+            writer,
+        })
+    }
+
+    /// Compress the encoded page and write it to `writer`.
+    ///
+    /// `position` is the position returned by the encoder. Returns the position of
+    /// the compressed page in `writer`.
+    pub(crate) async fn write_compressed(
+        mut self,
+        writer: &mut ObjectWriter,
+        compression: &Compression,
+        position: usize,
+    ) -> Result<usize> {
+        self.writer.shutdown().await?;
+        let encoded = self.store.inner.get(&self.path).await?.bytes().await?;
+        let compressed = compression.compress(&encoded)?;
+
+        let page_position = writer.tell();
+        writer.write_u64_le(compressed.len() as u64).await?;
+        writer.write_u64_le(position as u64).await?;
+        writer.write_all(&compressed).await?;
+        Ok(page_position)
+    }
+}
+
+/// A decompressed page, read like a file whose content is the encoded page.
+pub(crate) struct DecompressedPage {
+    data: Bytes,
+}
+
+impl DecompressedPage {
+    /// Read and decompress the page at `position`.
+    ///
+    /// Returns the page with the position returned by the encoder when it was
+    /// written, to read the page from.
+    pub(crate) async fn read(
+        reader: &dyn ObjectReader,
+        compression: &Compression,
+        position: usize,
+    ) -> Result<(Self, usize)> {
+        let header = reader.get_range(position..position + HEADER_SIZE).await?;
+        if header.len() != HEADER_SIZE {
+            return Err(Error::IO(format!(
+                "Compressed page at {} is truncated",
+                position
+            )));
+        }
+        let compressed_len = LittleEndian::read_u64(&header[..8]) as usize;
+        let inner_position = LittleEndian::read_u64(&header[8..]) as usize;
+
+        let start = position + HEADER_SIZE;
+        let compressed = reader.get_range(start..start + compressed_len).await?;
+        let data = Bytes::from(compression.decompress(&compressed)?);
+        Ok((Self { data }, inner_position))
+    }
+}
+
+#[async_trait]
+impl ObjectReader for DecompressedPage {
+    fn prefetch_size(&self) -> usize {
+        self.data.len()
+    }
+
+    async fn size(&self) -> Result<usize> {
+        Ok(self.data.len())
+    }
+
+    async fn get_range(&self, range: Range<usize>) -> Result<Bytes> {
+        if range.start > range.end || range.end > self.data.len() {
+            return Err(Error::IO(format!(
+                "Range {:?} is out of the decompressed page of {} bytes",
+                range,
+                self.data.len()
+            )));
+        }
+        Ok(self.data.slice(range))
+    }
+}
@@ -34,7 +34,7 @@ use crate::dataset::write_manifest_file;
 use crate::format::Index;
 use crate::{dataset::Dataset, Error, Result};
 
-use self::vector::{build_vector_index, VectorIndexParams};
+use self::vector::{build_vector_index, diskann::DiskANNParams, VectorIndexParams};
 
 /// Index Type
 pub enum IndexType {
@@ -86,6 +86,14 @@ pub trait DatasetIndexExt {
         name: Option<String>,
         params: &dyn IndexParams,
     ) -> Result<Dataset>;
+
+    /// Build a DiskANN graph over the vector `column`, and persist it as the index
+    /// `{column}_idx` of a new dataset version. The nearest neighbor searches of
+    /// the column, e.g., after reopening the dataset, then use it.
+    ///
+    /// A shortcut for [`Self::create_index`] with
+    /// [`VectorIndexParams::with_diskann_params`].
+    async fn create_diskann_index(&self, column: &str, params: &DiskANNParams) -> Result<Dataset>;
 }
 
 #[async_trait]
@@ -144,6 +152,9 @@ impl DatasetIndexExt for Dataset {
         let latest_manifest = self.latest_manifest().await?;
         let mut new_manifest = self.manifest.as_ref().clone();
         new_manifest.version = latest_manifest.version + 1;
+        if let Some(indexed_field) = new_manifest.schema.mut_field_by_id(field.id) {
+            indexed_field.indexed = true;
+        }
 
         // Write index metadata down
         let new_idx = Index::new(index_id, &index_name, &[field.id], new_manifest.version);
@@ -157,4 +168,11 @@ impl DatasetIndexExt for Dataset {
             manifest: Arc::new(new_manifest),
         })
     }
+
+    async fn create_diskann_index(&self, column: &str, params: &DiskANNParams) -> Result<Self> {
+        let params =
+            VectorIndexParams::with_diskann_params(params.metric_type.clone(), params.clone());
+        self.create_index(&[column], IndexType::Vector, None, &params)
+            .await
+    }
 }
@@ -42,7 +42,7 @@ use crate::{
     index::{
         pb::vector_index_stage::Stage,
         vector::{
-            diskann::DiskANNParams,
+            diskann::{DiskANNGraph, DiskANNParams},
             ivf::Ivf,
             opq::{OPQIndex, OptimizedProductQuantizer},
             pq::ProductQuantizer,
@@ -54,7 +54,9 @@ use crate::{
     },
     linalg::{
         cosine::{cosine_distance, cosine_distance_batch},
-        l2::{l2_distance, l2_distance_batch},
+        l2::{
+            l2_distance, l2_distance_batch, weighted_l2_distance, weighted_l2_distance_batch,
+        },
     },
     Error, Result,
 };
@@ -90,10 +92,21 @@ pub struct Query {
 }
 
 /// Distance metrics type.
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum MetricType {
+    /// Squared Euclidean distance.
+    ///
+    /// The square root is skipped, as it does not change the ranking: the
+    /// distances returned by the searches are squared too.
     L2,
     Cosine,
+
+    /// Squared Euclidean distance with one weight per dimension,
+    /// `sum(w_i * (a_i - b_i)^2)`.
+    ///
+    /// Not supported by the PQ based indices, whose sub-vectors are compared
+    /// without the weights.
+    WeightedL2(Arc<Vec<f32>>),
 }
 
 impl MetricType {
@@ -104,14 +117,55 @@ impl MetricType {
         match self {
             Self::L2 => Arc::new(l2_distance_batch),
             Self::Cosine => Arc::new(cosine_distance_batch),
+            Self::WeightedL2(weights) => {
+                let weights = weights.clone();
+                Arc::new(move |from: &[f32], to: &[f32], dimension: usize| {
+                    weighted_l2_distance_batch(from, to, dimension, &weights)
+                })
+            }
         }
     }
 
     /// Returns the distance function between two vectors.
     pub fn func(&self) -> Arc<dyn Fn(&[f32], &[f32]) -> f32 + Send + Sync + 'static> {
         match self {
-            Self::L2 => Arc::new(l2_distance),
+            Self::L2 => Arc::new(l2_distance::<f32>),
             Self::Cosine => Arc::new(cosine_distance),
+            Self::WeightedL2(weights) => {
+                let weights = weights.clone();
+                Arc::new(move |from: &[f32], to: &[f32]| {
+                    weighted_l2_distance(from, to, &weights)
+                })
+            }
+        }
+    }
+
+    /// Check that the metric applies to vectors of `dimension`.
+    pub fn validate(&self, dimension: usize) -> Result<()> {
+        match self {
+            Self::WeightedL2(weights) if weights.len() != dimension => Err(Error::Index(format!(
+                "WeightedL2 has {} weights, but the vectors have {} dimensions",
+                weights.len(),
+                dimension
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Build the metric from its protobuf type, and its weights if any.
+    pub(crate) fn from_pb(metric_type: pb::VectorMetricType, weights: &[f32]) -> Self {
+        match metric_type {
+            pb::VectorMetricType::L2 => Self::L2,
+            pb::VectorMetricType::Cosine => Self::Cosine,
+            pb::VectorMetricType::WeightedL2 => Self::WeightedL2(Arc::new(weights.to_vec())),
+        }
+    }
+
+    /// The weights to persist along with the protobuf type.
+    pub(crate) fn weights(&self) -> Vec<f32> {
+        match self {
+            Self::WeightedL2(weights) => weights.as_ref().clone(),
+            _ => vec![],
         }
     }
 }
@@ -124,29 +178,36 @@ impl std::fmt::Display for MetricType {
             match self {
                 Self::L2 => "l2",
                 Self::Cosine => "cosine",
+                Self::WeightedL2(_) => "weighted_l2",
             }
         )
     }
 }
 
 impl From<super::pb::VectorMetricType> for MetricType {
+    /// The protobuf type has no weights, so [`MetricType::WeightedL2`] is built
+    /// without any. Use [`MetricType::from_pb`] to restore them.
     fn from(proto: super::pb::VectorMetricType) -> Self {
-        match proto {
-            super::pb::VectorMetricType::L2 => Self::L2,
-            super::pb::VectorMetricType::Cosine => Self::Cosine,
-        }
+        Self::from_pb(proto, &[])
     }
 }
 
-impl From<MetricType> for super::pb::VectorMetricType {
-    fn from(mt: MetricType) -> Self {
+impl From<&MetricType> for super::pb::VectorMetricType {
+    fn from(mt: &MetricType) -> Self {
         match mt {
             MetricType::L2 => Self::L2,
             MetricType::Cosine => Self::Cosine,
+            MetricType::WeightedL2(_) => Self::WeightedL2,
         }
     }
 }
 
+impl From<MetricType> for super::pb::VectorMetricType {
+    fn from(mt: MetricType) -> Self {
+        Self::from(&mt)
+    }
+}
+
 impl TryFrom<&str> for MetricType {
     type Error = Error;
 
@@ -201,7 +262,7 @@ impl VectorIndexParams {
         pq_params.num_bits = num_bits as usize;
         pq_params.num_sub_vectors = num_sub_vectors as usize;
         pq_params.use_opq = use_opq;
-        pq_params.metric_type = metric_type;
+        pq_params.metric_type = metric_type.clone();
         pq_params.max_iters = max_iterations;
         pq_params.max_opq_iters = max_iterations;
         stages.push(StageParams::PQ(pq_params));
@@ -286,12 +347,17 @@ pub(crate) async fn build_vector_index(
                 format!("Build Vector Index: invalid stages: {:?}", stages),
             ));
         };
+        if let MetricType::WeightedL2(_) = params.metric_type {
+            return Err(Error::Index(
+                "Build Vector Index: IVF_PQ does not support WeightedL2".to_string(),
+            ));
+        }
         build_ivf_pq_index(
             dataset,
             column,
             &name,
             &uuid,
-            params.metric_type,
+            params.metric_type.clone(),
             ivf_params,
             pq_params,
         )
@@ -315,8 +381,24 @@ pub(crate) async fn build_vector_index(
     Ok(())
 }
 
-/// Open the Vector index on dataset, specified by the `uuid`.
-pub(crate) async fn open_index(dataset: &Dataset, uuid: &str) -> Result<Arc<dyn VectorIndex>> {
+/// Metadata of a vector index, read from the end of its index file.
+pub(crate) struct VectorIndexMetadata {
+    /// Reader of the index file.
+    pub reader: Arc<dyn ObjectReader>,
+
+    /// The indexed column.
+    pub column: String,
+
+    pub index: pb::VectorIndex,
+
+    pub metric_type: MetricType,
+}
+
+/// Read the metadata of the vector index on dataset, specified by the `uuid`.
+pub(crate) async fn read_index_metadata(
+    dataset: &Dataset,
+    uuid: &str,
+) -> Result<VectorIndexMetadata> {
     let index_dir = dataset.indices_dir().child(uuid);
     let index_file = index_dir.child(INDEX_FILE_NAME);
 
@@ -355,18 +437,51 @@ pub(crate) async fn open_index(dataset: &Dataset, uuid: &str) -> Result<Arc<dyn
         pb::index::Implementation::VectorIndex(vi) => vi,
     };
 
+    let metric_type = MetricType::from_pb(
+        pb::VectorMetricType::from_i32(vec_idx.metric_type).ok_or(Error::Index(format!(
+            "Unsupported metric type value: {}",
+            vec_idx.metric_type
+        )))?,
+        &vec_idx.metric_weights,
+    );
+
+    Ok(VectorIndexMetadata {
+        reader,
+        column: proto.columns[0].clone(),
+        index: vec_idx.clone(),
+        metric_type,
+    })
+}
+
+/// Open the Vector index on dataset, specified by the `uuid`.
+pub(crate) async fn open_index(dataset: &Dataset, uuid: &str) -> Result<Arc<dyn VectorIndex>> {
+    let VectorIndexMetadata {
+        reader,
+        column,
+        index: vec_idx,
+        metric_type,
+    } = read_index_metadata(dataset, uuid).await?;
+
+    if let [pb::VectorIndexStage {
+        stage: Some(Stage::Diskann(diskann)),
+    }] = vec_idx.stages.as_slice()
+    {
+        let graph = DiskANNGraph::load(
+            dataset,
+            &column,
+            diskann,
+            vec_idx.dimension as usize,
+            metric_type,
+        )
+        .await?;
+        return Ok(Arc::new(graph));
+    }
+
     let num_stages = vec_idx.stages.len();
     if num_stages != 2 && num_stages != 3 {
-        return Err(Error::IO("Only support IVF_(O)PQ now".to_string()));
+        return Err(Error::IO("Only support IVF_(O)PQ and DiskANN now".to_string()));
     };
 
-    let metric_type = pb::VectorMetricType::from_i32(vec_idx.metric_type)
-        .ok_or(Error::Index(format!(
-            "Unsupported metric type value: {}",
-            vec_idx.metric_type
-        )))?
-        .into();
-
     let mut last_stage: Option<Arc<dyn VectorIndex>> = None;
     for stg in vec_idx.stages.iter().rev() {
         match stg.stage.as_ref() {
@@ -408,7 +523,7 @@ pub(crate) async fn open_index(dataset: &Dataset, uuid: &str) -> Result<Arc<dyn
                     ivf,
                     reader.clone(),
                     last_stage.unwrap(),
-                    metric_type,
+                    metric_type.clone(),
                 )?));
             }
             Some(Stage::Pq(pq_proto)) => {
@@ -419,7 +534,7 @@ pub(crate) async fn open_index(dataset: &Dataset, uuid: &str) -> Result<Arc<dyn
                     )));
                 };
                 let pq = Arc::new(ProductQuantizer::try_from(pq_proto).unwrap());
-                last_stage = Some(Arc::new(PQIndex::new(pq, metric_type)));
+                last_stage = Some(Arc::new(PQIndex::new(pq, metric_type.clone())));
             }
             _ => {}
         }
@@ -16,15 +16,59 @@
 ///
 /// Modified from diskann paper. The vector store is backed by the `lance` dataset.
 mod builder;
+mod checkpoint;
+mod lazy;
 mod row_vertex;
 mod search;
 
+use std::time::Duration;
+
+use object_store::path::Path;
+
 use super::{
     graph::{Vertex, VertexSerDe},
     MetricType,
 };
 use crate::index::vector::pq::PQBuildParams;
+use crate::{Error, Result};
 pub(crate) use builder::build_diskann_index;
+pub use builder::VECTOR_DIM_METADATA_KEY;
+pub use lazy::LazyVectorGraph;
+pub use search::{
+    DiskANNGraph, EarlyTermination, Fusion, SearchParams, SearchResults, SearchScratch, SearchStats,
+};
+
+/// How the neighbors of a vertex are pruned down to `r`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PruneStrategy {
+    /// RobustPrune of the DiskANN paper, keeping neighbors in diverse directions.
+    Robust { alpha: f32 },
+
+    /// Keep the `r` nearest neighbors. Faster, but builds a lower quality graph.
+    Nearest,
+}
+
+/// How the random initial edges of the graph are wired.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InitStrategy {
+    /// Each random edge is added in both directions, as in the DiskANN paper.
+    #[default]
+    Bidirectional,
+
+    /// Only the `r` random out-edges of each vertex. The initial degree is about
+    /// halved, which makes the first prunes cheaper for large `r`.
+    Directed,
+}
+
+/// Draw one random sample of the vectors, to initialize the graph.
+///
+/// The medoid is estimated over the sample, and the random initial neighbors
+/// are drawn from it, instead of from all the vectors.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SampleConfig {
+    /// Number of vectors in the sample.
+    pub size: usize,
+}
 
 #[derive(Clone, Debug)]
 pub struct DiskANNParams {
@@ -42,6 +86,90 @@ pub struct DiskANNParams {
 
     /// Metric type.
     pub metric_type: MetricType,
+
+    /// Only re-process, in each pass, the vertices whose neighbors have changed
+    /// during the previous pass.
+    pub skip_converged: bool,
+
+    /// Alpha of each indexing pass. If not set, it runs two passes,
+    /// with alpha = 1.0 and then with `alpha`.
+    pub alpha_schedule: Option<Vec<f32>>,
+
+    /// Search list size of each indexing pass, aligned with the alphas. If not set,
+    /// all the passes use `l`.
+    pub l_schedule: Option<Vec<usize>>,
+
+    /// Path of the checkpoint file in the dataset object store.
+    /// If set, the build is checkpointed after each pass, and resumed from the
+    /// checkpoint if it already exists.
+    pub checkpoint_path: Option<Path>,
+
+    /// Also checkpoint the build every N vertices within a pass.
+    pub checkpoint_interval: Option<usize>,
+
+    /// Prune with [`PruneStrategy::Nearest`] instead of RobustPrune.
+    /// The alphas are then ignored.
+    pub prune_nearest: bool,
+
+    /// Initialize the graph from a sample of the vectors, see [`SampleConfig`].
+    pub sample: Option<SampleConfig>,
+
+    /// Only run the first indexing pass, for quick previews of an index.
+    ///
+    /// The build is about twice as fast, but the graph lacks the long range edges
+    /// added with `alpha > 1`, so searches need a larger search size to reach the
+    /// same recall. Do not use it for production indices.
+    pub quick: bool,
+
+    /// Maximum number of vertices pruned concurrently. Defaults to the number of CPUs.
+    pub concurrency: Option<usize>,
+
+    /// Keep the f64 vectors of a `FixedSizeList<Float64>` column in memory, to re-rank
+    /// the search results with exact f64 distances.
+    ///
+    /// The graph is built over the vectors downcast to f32 either way.
+    pub exact_rerank: bool,
+
+    /// How the random initial edges are wired.
+    pub init_strategy: InitStrategy,
+
+    /// The medoid of a prior build, e.g., [`DiskANNGraph::medoid`], to skip its
+    /// computation. It is the vertex id, i.e., the position of the row in the scan
+    /// of the dataset, which appends do not change.
+    pub precomputed_medoid: Option<usize>,
+
+    /// Seed of every random draw of the build: the random initial edges, the sample
+    /// they are drawn from, the order of the indexing passes and the order of the
+    /// pass stitching merged shards. Random by default.
+    ///
+    /// With a seed and a [`DiskANNParams::concurrency`] of 1, the same vectors
+    /// always yield the same graph.
+    pub seed: Option<u64>,
+
+    /// With [`MetricType::Cosine`], check on a sample that the vectors are normalized,
+    /// and fail the build otherwise.
+    pub require_normalized: bool,
+
+    /// Stop the indexing passes once the build has run for this long. The vertices
+    /// not processed yet keep their random initial neighbors, so the graph is rough
+    /// but still searchable.
+    pub time_budget: Option<Duration>,
+
+    /// Build the graph over a normalized copy of the vectors, e.g., to index the cosine
+    /// similarity with [`MetricType::L2`]. The vectors stored in the dataset are not
+    /// modified.
+    ///
+    /// The graph traversal and [`SearchParams::refine`] use the normalized vectors,
+    /// while [`DiskANNParams::exact_rerank`] re-ranks with the original f64 vectors
+    /// read from the dataset.
+    pub normalize: bool,
+
+    /// Capacity reserved for the neighbor list of each vertex, to bound the memory
+    /// per vertex. Defaults to `r`, and must not be less.
+    ///
+    /// The indexing passes never grow a neighbor list beyond it. The initial random
+    /// edges may, until the first pass prunes them.
+    pub max_degree: Option<usize>,
 }
 
 // Default values from DiskANN paper.
@@ -53,6 +181,23 @@ impl Default for DiskANNParams {
             l: 100,
             pq_params: PQBuildParams::default(),
             metric_type: MetricType::L2,
+            skip_converged: false,
+            alpha_schedule: None,
+            l_schedule: None,
+            checkpoint_path: None,
+            checkpoint_interval: None,
+            prune_nearest: false,
+            sample: None,
+            quick: false,
+            concurrency: None,
+            exact_rerank: false,
+            init_strategy: InitStrategy::Bidirectional,
+            precomputed_medoid: None,
+            seed: None,
+            require_normalized: false,
+            time_budget: None,
+            normalize: false,
+            max_degree: None,
         }
     }
 }
@@ -65,6 +210,23 @@ impl DiskANNParams {
             l,
             pq_params: PQBuildParams::default(),
             metric_type: MetricType::L2,
+            skip_converged: false,
+            alpha_schedule: None,
+            l_schedule: None,
+            checkpoint_path: None,
+            checkpoint_interval: None,
+            prune_nearest: false,
+            sample: None,
+            quick: false,
+            concurrency: None,
+            exact_rerank: false,
+            init_strategy: InitStrategy::Bidirectional,
+            precomputed_medoid: None,
+            seed: None,
+            require_normalized: false,
+            time_budget: None,
+            normalize: false,
+            max_degree: None,
         }
     }
 
@@ -103,14 +265,202 @@ impl DiskANNParams {
         self.metric_type = metric_type;
         self
     }
+
+    /// Skip re-processing the vertices that have converged in the previous pass.
+    pub fn skip_converged(&mut self, skip: bool) -> &mut Self {
+        self.skip_converged = skip;
+        self
+    }
+
+    /// Run one indexing pass per alpha value, in order.
+    pub fn alpha_schedule(&mut self, alphas: Vec<f32>) -> &mut Self {
+        self.alpha_schedule = Some(alphas);
+        self
+    }
+
+    /// Use these search list sizes in the indexing passes, one per alpha, e.g., a
+    /// large `l` to explore in the first pass and a smaller one to refine.
+    pub fn l_schedule(&mut self, search_sizes: Vec<usize>) -> &mut Self {
+        self.l_schedule = Some(search_sizes);
+        self
+    }
+
+    /// Checkpoint the build into this file, see [`DiskANNParams::checkpoint_path`].
+    pub fn checkpoint_path(&mut self, path: Path) -> &mut Self {
+        self.checkpoint_path = Some(path);
+        self
+    }
+
+    pub fn checkpoint_interval(&mut self, num_vertices: usize) -> &mut Self {
+        self.checkpoint_interval = Some(num_vertices);
+        self
+    }
+
+    pub fn prune_nearest(&mut self, nearest: bool) -> &mut Self {
+        self.prune_nearest = nearest;
+        self
+    }
+
+    /// Initialize the graph from a sample of `size` vectors, see [`SampleConfig`].
+    pub fn sample(&mut self, size: usize) -> &mut Self {
+        self.sample = Some(SampleConfig { size });
+        self
+    }
+
+    /// Wire the random initial edges with `init_strategy`, see [`InitStrategy`].
+    pub fn init_strategy(&mut self, init_strategy: InitStrategy) -> &mut Self {
+        self.init_strategy = init_strategy;
+        self
+    }
+
+    /// Reuse the medoid of a prior build, see [`DiskANNParams::precomputed_medoid`].
+    pub fn precomputed_medoid(&mut self, medoid: usize) -> &mut Self {
+        self.precomputed_medoid = Some(medoid);
+        self
+    }
+
+    /// Seed the random draws of the build, see [`DiskANNParams::seed`].
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Require normalized vectors, see [`DiskANNParams::require_normalized`].
+    pub fn require_normalized(&mut self, require_normalized: bool) -> &mut Self {
+        self.require_normalized = require_normalized;
+        self
+    }
+
+    /// Bound the build time, see [`DiskANNParams::time_budget`].
+    pub fn time_budget(&mut self, budget: Duration) -> &mut Self {
+        self.time_budget = Some(budget);
+        self
+    }
+
+    /// Build over normalized vectors, see [`DiskANNParams::normalize`].
+    pub fn normalize(&mut self, normalize: bool) -> &mut Self {
+        self.normalize = normalize;
+        self
+    }
+
+    /// Cap the neighbor lists, see [`DiskANNParams::max_degree`].
+    pub fn max_degree(&mut self, max_degree: usize) -> &mut Self {
+        self.max_degree = Some(max_degree);
+        self
+    }
+
+    /// Build a rough index with a single pass, see [`DiskANNParams::quick`].
+    pub fn quick(&mut self, quick: bool) -> &mut Self {
+        self.quick = quick;
+        self
+    }
+
+    /// Bound the number of vertices pruned concurrently, e.g., to the CPU quota of
+    /// a container, or when several builds share the process.
+    pub fn concurrency(&mut self, concurrency: usize) -> &mut Self {
+        self.concurrency = Some(concurrency);
+        self
+    }
+
+    /// Re-rank the search results with the f64 vectors, see [`DiskANNParams::exact_rerank`].
+    pub fn exact_rerank(&mut self, exact_rerank: bool) -> &mut Self {
+        self.exact_rerank = exact_rerank;
+        self
+    }
+
+    /// The capacity of the neighbor lists, see [`DiskANNParams::max_degree`].
+    pub(crate) fn degree_limit(&self) -> Result<usize> {
+        let max_degree = self.max_degree.unwrap_or(self.r);
+        if max_degree < self.r {
+            return Err(Error::Index(format!(
+                "DiskANN: max_degree ({}) must not be less than r ({})",
+                max_degree, self.r
+            )));
+        }
+        Ok(max_degree)
+    }
+
+    /// The number of vertices to prune concurrently.
+    pub(crate) fn concurrency_limit(&self) -> usize {
+        self.concurrency.unwrap_or_else(num_cpus::get).max(1)
+    }
+
+    /// The prune strategy of each indexing pass.
+    pub(crate) fn prune_strategies(&self) -> Result<Vec<PruneStrategy>> {
+        Ok(self
+            .alphas()?
+            .into_iter()
+            .map(|alpha| {
+                if self.prune_nearest {
+                    PruneStrategy::Nearest
+                } else {
+                    PruneStrategy::Robust { alpha }
+                }
+            })
+            .collect())
+    }
+
+    /// The alpha of each indexing pass.
+    pub(crate) fn alphas(&self) -> Result<Vec<f32>> {
+        let mut alphas = self
+            .alpha_schedule
+            .clone()
+            .unwrap_or_else(|| vec![1.0, self.alpha]);
+        if alphas.is_empty() {
+            return Err(Error::Index(
+                "DiskANN: alpha schedule must not be empty".to_string(),
+            ));
+        }
+        if let Some(alpha) = alphas.iter().skip(1).find(|&&alpha| alpha < 1.0) {
+            return Err(Error::Index(format!(
+                "DiskANN: alpha must be >= 1.0 after the first pass, got {}",
+                alpha
+            )));
+        }
+        if self.quick {
+            alphas.truncate(1);
+        }
+        Ok(alphas)
+    }
+
+    /// The search list size of each indexing pass.
+    pub(crate) fn search_sizes(&self) -> Result<Vec<usize>> {
+        let num_passes = self.alphas()?.len();
+        let Some(schedule) = self.l_schedule.as_ref() else {
+            return Ok(vec![self.l; num_passes]);
+        };
+        let num_alphas = self
+            .alpha_schedule
+            .as_ref()
+            .map_or(2, |alphas| alphas.len());
+        if schedule.len() != num_alphas {
+            return Err(Error::Index(format!(
+                "DiskANN: l schedule has {} values, but there are {} alphas",
+                schedule.len(),
+                num_alphas
+            )));
+        }
+        if let Some(l) = schedule.iter().find(|&&l| l < self.r) {
+            return Err(Error::Index(format!(
+                "DiskANN: l must be >= r ({}), got {}",
+                self.r, l
+            )));
+        }
+        Ok(schedule[..num_passes].to_vec())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
 
-    use arrow_array::{FixedSizeListArray, RecordBatch, RecordBatchReader};
+    use arrow_array::{
+        cast::as_primitive_array, types::Float32Type, FixedSizeListArray, Float32Array,
+        RecordBatch, RecordBatchReader,
+    };
     use arrow_schema::{DataType, Field, Schema as ArrowSchema};
+    use arrow_select::concat::concat_batches;
+    use futures::TryStreamExt;
     use tempfile::tempdir;
 
     use super::*;
@@ -165,4 +515,64 @@ mod tests {
         let expected = dataset.manifest.version;
         assert_eq!(actual, expected);
     }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_create_diskann_index() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let dimension = 16;
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "embeddings",
+            DataType::FixedSizeList(
+                Arc::new(Field::new("item", DataType::Float32, true)),
+                dimension,
+            ),
+            false,
+        )]));
+        let float_arr = generate_random_array(512 * dimension as usize);
+        let vectors = Arc::new(FixedSizeListArray::try_new(&float_arr, dimension).unwrap());
+        let batches = RecordBatchBuffer::new(vec![RecordBatch::try_new(
+            schema.clone(),
+            vec![vectors.clone()],
+        )
+        .unwrap()]);
+        let mut reader: Box<dyn RecordBatchReader> = Box::new(batches);
+        let dataset = Dataset::write(&mut reader, test_uri, None).await.unwrap();
+
+        let mut params = DiskANNParams::default();
+        params.r(20).l(40);
+        let dataset = dataset
+            .create_diskann_index("embeddings", &params)
+            .await
+            .unwrap();
+        assert_eq!(
+            dataset.load_indices().await.unwrap()[0].name,
+            "embeddings_idx"
+        );
+
+        let dataset = Dataset::open(test_uri).await.unwrap();
+        let mut found = 0;
+        for i in (0..512).step_by(51) {
+            let key = Float32Array::from(
+                float_arr.values()[i * dimension as usize..(i + 1) * dimension as usize].to_vec(),
+            );
+            let mut scan = dataset.scan();
+            scan.nearest("embeddings", &key, 5).unwrap();
+            let results = scan
+                .try_into_stream()
+                .await
+                .unwrap()
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+            let batch = concat_batches(&results[0].schema(), &results).unwrap();
+            assert_eq!(batch.num_rows(), 5);
+            let scores = as_primitive_array::<Float32Type>(batch.column_by_name("score").unwrap());
+            if scores.values().iter().any(|s| *s == 0.0) {
+                found += 1;
+            }
+        }
+        assert!(found >= 9, "found {} of 11 vectors", found);
+    }
 }
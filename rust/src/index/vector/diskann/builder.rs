@@ -12,32 +12,47 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::{BinaryHeap, HashSet};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use arrow_array::{cast::as_primitive_array, types::UInt64Type};
-use arrow_select::concat::concat_batches;
+use arrow_array::{
+    cast::{as_primitive_array, AsArray},
+    types::{Float32Type, Float64Type, UInt64Type},
+    Array, BooleanArray, FixedSizeListArray, Float32Array, RecordBatch,
+};
+use arrow_cast::cast::cast;
+use arrow_schema::DataType;
+use arrow_select::{concat::concat_batches, filter::filter_record_batch};
 use futures::stream::{self, StreamExt, TryStreamExt};
+use object_store::path::Path;
 use ordered_float::OrderedFloat;
 use rand::distributions::Uniform;
 use rand::prelude::SliceRandom;
+use rand::rngs::SmallRng;
 use rand::{Rng, SeedableRng};
 
 use crate::arrow::{linalg::MatrixView, *};
 use crate::dataset::{Dataset, ROW_ID};
 use crate::index::pb;
 use crate::index::vector::diskann::row_vertex::RowVertexSerDe;
-use crate::index::vector::diskann::DiskANNParams;
+use crate::index::vector::diskann::{DiskANNParams, InitStrategy, PruneStrategy, SampleConfig};
 use crate::index::vector::graph::{
-    builder::GraphBuilder, write_graph, VertexWithDistance, WriteGraphParams,
+    builder::GraphBuilder, read_graph, write_graph, VertexWithDistance, WriteGraphParams,
 };
 use crate::index::vector::graph::{Graph, Vertex};
 use crate::index::vector::{MetricType, INDEX_FILE_NAME};
-use crate::linalg::l2::l2_distance;
+use crate::linalg::{cosine::cosine_distance_scalar, l2::l2_distance_scalar, norm_l2::norm_l2};
 use crate::{Error, Result};
 
+use super::checkpoint::{BuildProgress, Checkpoint};
 use super::row_vertex::RowVertex;
 use super::search::greedy_search;
 
+/// Field metadata key declaring the dimension of the vectors stored in a `List` column.
+pub const VECTOR_DIM_METADATA_KEY: &str = "lance:vector_dim";
+
 pub(crate) async fn build_diskann_index(
     dataset: &Dataset,
     column: &str,
@@ -45,34 +60,14 @@ pub(crate) async fn build_diskann_index(
     uuid: &str,
     params: DiskANNParams,
 ) -> Result<()> {
-    let rng = rand::rngs::SmallRng::from_entropy();
-
-    // Randomly initialize the graph with r random neighbors for each vertex.
-    let mut graph = init_graph(dataset, column, params.r, params.metric_type, rng.clone()).await?;
-
-    // Find medoid
-    let medoid = {
-        let vectors = graph.data.clone();
-        find_medoid(&vectors, params.metric_type).await?
+    let columns = [(column, 1.0)];
+    let (graph, report) = match params.checkpoint_path.as_ref() {
+        Some(path) if dataset.object_store().exists(path).await? => {
+            resume_graph(dataset, &columns, &params, path).await?
+        }
+        _ => build_graph(dataset, &columns, &params).await?,
     };
 
-    // First pass.
-    let now = std::time::Instant::now();
-    index_once(&mut graph, medoid, 1.0, params.r, params.l, rng.clone()).await?;
-    println!("DiskANN: first pass: {}s", now.elapsed().as_secs_f32());
-    // Second pass.
-    let now = std::time::Instant::now();
-    index_once(
-        &mut graph,
-        medoid,
-        params.alpha,
-        params.r,
-        params.l,
-        rng.clone(),
-    )
-    .await?;
-    println!("DiskANN: second pass: {}s", now.elapsed().as_secs_f32());
-
     let index_dir = dataset.indices_dir().child(uuid);
     let graph_file = index_dir.child("diskann_graph.lance");
 
@@ -96,8 +91,8 @@ pub(crate) async fn build_diskann_index(
         uuid,
         graph.data.num_columns(),
         graph_file.to_string().as_str(),
-        &[medoid],
-        params.metric_type,
+        &[report.medoid],
+        params.metric_type.clone(),
         &params,
     )
     .await?;
@@ -105,58 +100,656 @@ pub(crate) async fn build_diskann_index(
     Ok(())
 }
 
-/// Randomly initialize the graph with r random neighbors for each vertex.
+/// Metrics collected while building a DiskANN graph.
+#[derive(Debug, Clone)]
+pub struct BuildReport {
+    /// Time spent to randomly initialize the graph.
+    pub init_duration: Duration,
+
+    /// Time spent to find the medoid.
+    pub medoid_duration: Duration,
+
+    /// Time spent in each indexing pass.
+    pub pass_durations: Vec<Duration>,
+
+    /// Number of vertices in the graph.
+    pub num_vertices: usize,
+
+    /// Id of the medoid vertex, the entry point of the searches.
+    pub medoid: usize,
+
+    /// Parameters used to build the graph.
+    pub params: DiskANNParams,
+
+    /// Minimal out-degree of the final graph.
+    pub min_degree: usize,
+
+    /// Maximal out-degree of the final graph.
+    pub max_degree: usize,
+
+    /// Average out-degree of the final graph.
+    pub avg_degree: f32,
+
+    /// Fraction of the vertices with fewer than `r / 2` neighbors in the final graph.
+    ///
+    /// A large fraction means that the pruning is too aggressive, i.e., that `alpha`
+    /// is too low: fewer candidates are kept as `alpha` decreases.
+    pub under_degree_fraction: f32,
+
+    /// Number of vertices processed by at least one indexing pass. Less than
+    /// `num_vertices` if [`DiskANNParams::time_budget`] ran out in the first pass.
+    pub num_processed: usize,
+}
+
+impl BuildReport {
+    fn new(
+        graph: &GraphBuilder<RowVertex>,
+        params: &DiskANNParams,
+        progress: &BuildProgress,
+        init_duration: Duration,
+        medoid_duration: Duration,
+        pass_durations: Vec<Duration>,
+    ) -> Self {
+        let degrees = graph
+            .nodes
+            .iter()
+            .map(|node| node.neighbors.len())
+            .collect::<Vec<_>>();
+        Self {
+            init_duration,
+            medoid_duration,
+            pass_durations,
+            num_vertices: graph.len(),
+            medoid: progress.medoid,
+            params: params.clone(),
+            min_degree: degrees.iter().copied().min().unwrap_or(0),
+            max_degree: degrees.iter().copied().max().unwrap_or(0),
+            avg_degree: if degrees.is_empty() {
+                0.0
+            } else {
+                degrees.iter().sum::<usize>() as f32 / degrees.len() as f32
+            },
+            under_degree_fraction: if degrees.is_empty() {
+                0.0
+            } else {
+                degrees.iter().filter(|&&d| d * 2 < params.r).count() as f32 / degrees.len() as f32
+            },
+            num_processed: if progress.pass == 0 {
+                progress.position
+            } else {
+                graph.len()
+            },
+        }
+    }
+}
+
+/// Build the DiskANN graph in memory, over the weighted concatenation of the
+/// `(column, weight)` vector columns.
 ///
-/// Parameters
-/// ----------
-///  - dataset: the dataset to index.
-///  - column: the vector column to index.
-///  - r: the number of neighbors to connect to.
-///  - rng: the random number generator.
+/// If [`DiskANNParams::checkpoint_path`] is set, the build is checkpointed after
+/// each pass, and every [`DiskANNParams::checkpoint_interval`] vertices, so it can
+/// be resumed by [`resume_graph`].
 ///
-async fn init_graph(
+/// Returns the graph and the [`BuildReport`] of the build.
+pub(crate) async fn build_graph(
+    dataset: &Dataset,
+    columns: &[(&str, f32)],
+    params: &DiskANNParams,
+) -> Result<(GraphBuilder<RowVertex>, BuildReport)> {
+    let start = Instant::now();
+    let deadline = params.time_budget.map(|budget| start + budget);
+    let strategies = params.prune_strategies()?;
+    let seed = params
+        .seed
+        .unwrap_or_else(|| rand::thread_rng().gen::<u64>());
+    let rng = SmallRng::seed_from_u64(seed);
+
+    // Randomly initialize the graph with r random neighbors for each vertex.
+    let now = Instant::now();
+    let (mut graph, sample) = init_graph(
+        dataset,
+        columns,
+        params.r,
+        params.metric_type.clone(),
+        params.normalize,
+        params.sample.as_ref(),
+        params.init_strategy,
+        rng,
+    )
+    .await?;
+    let init_duration = now.elapsed();
+    params.metric_type.validate(graph.data.num_columns())?;
+    if params.require_normalized && params.metric_type == MetricType::Cosine {
+        check_normalized(&graph.data)?;
+    }
+
+    // Find medoid
+    let now = Instant::now();
+    let medoid = match (params.precomputed_medoid, sample) {
+        (Some(medoid), _) => {
+            if medoid >= graph.len() {
+                return Err(Error::Index(format!(
+                    "DiskANN: precomputed medoid {} is out of range, the graph has {} vertices",
+                    medoid,
+                    graph.len()
+                )));
+            }
+            medoid
+        }
+        (None, Some(sample)) => {
+            sample.ids[find_medoid(&sample.vectors, params.metric_type.clone()).await?]
+        }
+        (None, None) => {
+            let vectors = graph.data.clone();
+            find_medoid(&vectors, params.metric_type.clone()).await?
+        }
+    };
+    let medoid_duration = now.elapsed();
+
+    let mut progress = BuildProgress::new(seed, medoid, graph.len());
+    let pass_durations = index_passes(
+        dataset,
+        &mut graph,
+        &mut progress,
+        &strategies,
+        params,
+        deadline,
+    )
+    .await?;
+
+    let report = BuildReport::new(
+        &graph,
+        params,
+        &progress,
+        init_duration,
+        medoid_duration,
+        pass_durations,
+    );
+    Ok((graph, report))
+}
+
+/// Build the DiskANN graph in one pass over the scan of the vector `column`,
+/// inserting the vectors of each batch into the graph built so far.
+///
+/// The medoid is the one of the first batch. Only the graph and the current batch
+/// are held in memory, the batch being appended to the vectors of the graph in
+/// place, see [`GraphBuilder::insert`]. But the graph is built with a single pass of the last prune
+/// strategy of `params`, so its recall is a bit lower than the one of
+/// [`build_graph`]: within 0.1 on random vectors.
+///
+/// Returns the graph and its medoid.
+pub(crate) async fn build_graph_streaming(
     dataset: &Dataset,
     column: &str,
-    r: usize,
+    params: &DiskANNParams,
+) -> Result<(GraphBuilder<RowVertex>, usize)> {
+    let strategy = *params
+        .prune_strategies()?
+        .last()
+        .ok_or_else(|| Error::Index("DiskANN: no prune strategy to build with".to_string()))?;
+    let l = *params.search_sizes()?.last().unwrap();
+    let mut stream = dataset
+        .scan()
+        .project(&[column])?
+        .with_row_id()
+        .try_into_stream()
+        .await?;
+
+    let mut graph: Option<(GraphBuilder<RowVertex>, usize)> = None;
+    let mut changed = vec![];
+    while let Some(batch) = stream.try_next().await? {
+        let batch = filter_null_vectors(&batch, &[column])?;
+        if batch.num_rows() == 0 {
+            continue;
+        }
+        let row_ids = as_primitive_array::<UInt64Type>(batch.column_by_name(ROW_ID).unwrap());
+        let nodes = row_ids
+            .values()
+            .iter()
+            .map(|&row_id| RowVertex::new(row_id, None))
+            .collect::<Vec<_>>();
+        let vectors = concat_vector_columns(&batch, &[(column, 1.0)])?;
+        check_vector_count(&vectors, nodes.len())?;
+
+        let ids = if let Some((builder, _)) = graph.as_mut() {
+            let offset = builder.insert(&nodes, &vectors)?;
+            (offset..builder.len()).collect::<Vec<_>>()
+        } else {
+            params.metric_type.validate(vectors.num_columns())?;
+            if params.require_normalized && params.metric_type == MetricType::Cosine {
+                check_normalized(&vectors)?;
+            }
+            let mut first = GraphBuilder::new(&nodes, vectors, params.metric_type.clone());
+            if params.normalize {
+                first.normalize();
+            }
+            let medoid = find_medoid(&first.data, params.metric_type.clone()).await?;
+            // The other vertices are linked to the medoid by their searches.
+            let ids = (0..first.len()).filter(|&id| id != medoid).collect();
+            graph = Some((first, medoid));
+            ids
+        };
+        let (builder, medoid) = graph.as_mut().unwrap();
+        changed.resize(builder.len(), false);
+        index_once(
+            builder,
+            *medoid,
+            strategy,
+            l,
+            params,
+            &ids,
+            None,
+            &mut changed,
+            None,
+        )
+        .await?;
+    }
+    graph.ok_or_else(|| Error::Index("no vectors to index".to_string()))
+}
+
+/// Resume a DiskANN graph build from the checkpoint written by [`build_graph`].
+///
+/// The dataset, the columns and the parameters must be the same as the ones of the
+/// interrupted build. The remaining passes are run from where the checkpoint was taken.
+pub(crate) async fn resume_graph(
+    dataset: &Dataset,
+    columns: &[(&str, f32)],
+    params: &DiskANNParams,
+    checkpoint_path: &Path,
+) -> Result<(GraphBuilder<RowVertex>, BuildReport)> {
+    let deadline = params.time_budget.map(|budget| Instant::now() + budget);
+    let strategies = params.prune_strategies()?;
+    let checkpoint = Checkpoint::read(dataset.object_store(), checkpoint_path).await?;
+    if checkpoint.progress.pass > strategies.len() {
+        return Err(Error::Index(format!(
+            "DiskANN checkpoint is at pass {}, but there are only {} passes",
+            checkpoint.progress.pass,
+            strategies.len()
+        )));
+    }
+
+    let now = Instant::now();
+    let mut graph = load_graph(
+        dataset,
+        columns,
+        params.metric_type.clone(),
+        params.normalize,
+    )
+    .await?;
+    checkpoint.restore(&mut graph)?;
+    let init_duration = now.elapsed();
+
+    let mut progress = checkpoint.progress;
+    let pass_durations = index_passes(
+        dataset,
+        &mut graph,
+        &mut progress,
+        &strategies,
+        params,
+        deadline,
+    )
+    .await?;
+
+    let report = BuildReport::new(
+        &graph,
+        params,
+        &progress,
+        init_duration,
+        Duration::ZERO,
+        pass_durations,
+    );
+    Ok((graph, report))
+}
+
+/// Run the indexing passes from the current progress, one pass per prune strategy.
+///
+/// The passes stop early once `deadline` has passed, with the progress pointing
+/// to the first vertex not processed.
+///
+/// Returns the duration of each pass run.
+async fn index_passes(
+    dataset: &Dataset,
+    graph: &mut GraphBuilder<RowVertex>,
+    progress: &mut BuildProgress,
+    strategies: &[PruneStrategy],
+    params: &DiskANNParams,
+    deadline: Option<Instant>,
+) -> Result<Vec<Duration>> {
+    let max_degree = params.degree_limit()?;
+    let search_sizes = params.search_sizes()?;
+    graph.reserve_neighbors(max_degree);
+    let mut pass_durations = vec![];
+    while progress.pass < strategies.len() {
+        let now = Instant::now();
+
+        // All the passes visit the vertices in the same random order.
+        let mut ids = (0..graph.len()).collect::<Vec<_>>();
+        ids.shuffle(&mut SmallRng::seed_from_u64(progress.seed));
+
+        let batch_size = params.checkpoint_interval.unwrap_or(ids.len()).max(1);
+        while progress.position < ids.len() {
+            let end = (progress.position + batch_size).min(ids.len());
+            let num_processed = index_once(
+                graph,
+                progress.medoid,
+                strategies[progress.pass],
+                search_sizes[progress.pass],
+                params,
+                &ids[progress.position..end],
+                params.skip_converged.then_some(progress.dirty.as_slice()),
+                &mut progress.changed,
+                deadline,
+            )
+            .await?;
+            if progress.position + num_processed < end {
+                // Out of time: keep the graph as it is.
+                progress.position += num_processed;
+                write_checkpoint(dataset, graph, progress, params).await?;
+                pass_durations.push(now.elapsed());
+                return Ok(pass_durations);
+            }
+            progress.position = end;
+            if end < ids.len() {
+                write_checkpoint(dataset, graph, progress, params).await?;
+            }
+        }
+
+        // Only the vertices whose neighbors have changed are processed in the next pass.
+        if params.skip_converged {
+            progress.dirty = std::mem::replace(&mut progress.changed, vec![false; graph.len()]);
+        } else {
+            progress.changed.fill(false);
+        }
+        debug_assert!(graph.nodes.iter().all(|n| n.neighbors.len() <= max_degree));
+        // Release the memory of the initial neighbor lists longer than `max_degree`.
+        graph.reserve_neighbors(max_degree);
+        progress.pass += 1;
+        progress.position = 0;
+        write_checkpoint(dataset, graph, progress, params).await?;
+        pass_durations.push(now.elapsed());
+    }
+    Ok(pass_durations)
+}
+
+async fn write_checkpoint(
+    dataset: &Dataset,
+    graph: &GraphBuilder<RowVertex>,
+    progress: &BuildProgress,
+    params: &DiskANNParams,
+) -> Result<()> {
+    if let Some(path) = params.checkpoint_path.as_ref() {
+        Checkpoint::new(progress, graph)
+            .write(dataset.object_store(), path)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Load the vectors of the dataset into a graph without any edge.
+///
+/// The vectors of the `(column, weight)` columns of a row are scaled by their
+/// weights and concatenated into one vector, and normalized if `normalize` is set.
+/// The rows with null vectors are skipped.
+async fn load_graph(
+    dataset: &Dataset,
+    columns: &[(&str, f32)],
     metric_type: MetricType,
-    mut rng: impl Rng,
+    normalize: bool,
 ) -> Result<GraphBuilder<RowVertex>> {
-    let stream = dataset
+    let column_names = columns.iter().map(|(c, _)| *c).collect::<Vec<_>>();
+    let mut stream = dataset
         .scan()
-        .project(&[column])?
+        .project(&column_names)?
         .with_row_id()
         .try_into_stream()
-        .await
-        .unwrap();
+        .await?;
 
-    let batches = stream.try_collect::<Vec<_>>().await?;
+    // Row ids are moved into the vertices batch by batch, so only the vectors
+    // are concatenated.
+    let mut nodes = vec![];
+    let mut batches = vec![];
+    while let Some(batch) = stream.try_next().await? {
+        let batch = filter_null_vectors(&batch, &column_names)?;
+        let row_id_idx = batch.schema().index_of(ROW_ID)?;
+        let row_ids = as_primitive_array::<UInt64Type>(batch.column(row_id_idx));
+        nodes.extend(
+            row_ids
+                .values()
+                .iter()
+                .map(|&row_id| RowVertex::new(row_id, None)),
+        );
+        let vector_indices = (0..batch.num_columns())
+            .filter(|&i| i != row_id_idx)
+            .collect::<Vec<_>>();
+        batches.push(batch.project(&vector_indices)?);
+    }
+    if batches.is_empty() {
+        return Err(Error::Index("no vectors to index".to_string()));
+    }
     let batch = concat_batches(&batches[0].schema(), &batches)?;
+    let matrix = concat_vector_columns(&batch, columns)?;
+    check_vector_count(&matrix, nodes.len())?;
+    let mut graph = GraphBuilder::new(&nodes, matrix, metric_type);
+    if normalize {
+        graph.normalize();
+    }
+    Ok(graph)
+}
 
-    let row_ids = as_primitive_array::<UInt64Type>(
-        batch
-            .column_by_qualified_name(ROW_ID)
-            .ok_or(Error::Index("row_id not found".to_string()))?,
-    );
-    let vectors = as_fixed_size_list_array(
-        batch
+/// Check that the matrix holds exactly one vector per vertex, otherwise the vector
+/// of a vertex would be read at the offset of another one.
+fn check_vector_count(vectors: &MatrixView, num_vertices: usize) -> Result<()> {
+    let expected = num_vertices * vectors.num_columns();
+    let actual = vectors.data().len();
+    if actual != expected {
+        return Err(Error::Index(format!(
+            "Vector count mismatch: {} values for {} vertices of dimension {}",
+            actual,
+            num_vertices,
+            vectors.num_columns()
+        )));
+    }
+    Ok(())
+}
+
+/// Load the f64 vectors of a `FixedSizeList<Float64>` column, in the order of the
+/// vertices of the graph built over it.
+///
+/// The vectors are flattened, with the dimension of the graph.
+pub(crate) async fn load_f64_vectors(
+    dataset: &Dataset,
+    column: &str,
+    graph: &GraphBuilder<RowVertex>,
+) -> Result<Vec<f64>> {
+    let dim = graph.data.num_columns();
+    let mut values = vec![0.0; graph.len() * dim];
+    let mut stream = dataset
+        .scan()
+        .project(&[column])?
+        .with_row_id()
+        .try_into_stream()
+        .await?;
+    while let Some(batch) = stream.try_next().await? {
+        let array = batch
             .column_by_qualified_name(column)
-            .ok_or(Error::Index(format!("column {} not found", column)))?,
-    );
-    let matrix: MatrixView = vectors.try_into()?;
-    let nodes = row_ids
-        .values()
+            .ok_or(Error::Index(format!("column {} not found", column)))?;
+        let vectors = match array.data_type() {
+            DataType::FixedSizeList(field, d)
+                if field.data_type() == &DataType::Float64 && *d as usize == dim =>
+            {
+                as_fixed_size_list_array(array)
+            }
+            dt => {
+                return Err(Error::Index(format!(
+                    "Vector column {} must be a FixedSizeList<Float64> of dimension {}, got: {}",
+                    column, dim, dt
+                )))
+            }
+        };
+        let row_ids = as_primitive_array::<UInt64Type>(
+            batch
+                .column_by_name(ROW_ID)
+                .ok_or(Error::Index("row id column not found".to_string()))?,
+        );
+        for (i, row_id) in row_ids.values().iter().enumerate() {
+            // Null vectors are not in the graph.
+            let Some(id) = graph.vertex_for_row(*row_id) else {
+                continue;
+            };
+            let vector = vectors.value(i);
+            let vector = as_primitive_array::<Float64Type>(vector.as_ref());
+            values[id * dim..(id + 1) * dim].copy_from_slice(vector.values());
+        }
+    }
+    Ok(values)
+}
+
+/// Load the graph of a DiskANN index written by [`build_diskann_index`] to
+/// `graph_file`, with the vectors of its vertices read from the `column` of the
+/// dataset.
+///
+/// The rows added after the index was built are not in the graph.
+pub(crate) async fn load_diskann_graph(
+    dataset: &Dataset,
+    column: &str,
+    graph_file: &str,
+    dimension: usize,
+    metric_type: MetricType,
+) -> Result<GraphBuilder<RowVertex>> {
+    let (vertices, neighbors) = read_graph(
+        dataset.object_store(),
+        &Path::from(graph_file),
+        &RowVertexSerDe {},
+    )
+    .await?;
+    let row_index = vertices
         .iter()
-        .map(|&row_id| RowVertex::new(row_id, None))
-        .collect::<Vec<_>>();
-    let mut graph = GraphBuilder::new(&nodes, matrix, metric_type);
+        .enumerate()
+        .map(|(id, v)| (v.row_id, id))
+        .collect::<HashMap<_, _>>();
+
+    let mut values = vec![0.0; vertices.len() * dimension];
+    let mut stream = dataset
+        .scan()
+        .project(&[column])?
+        .with_row_id()
+        .try_into_stream()
+        .await?;
+    while let Some(batch) = stream.try_next().await? {
+        let batch = filter_null_vectors(&batch, &[column])?;
+        let (_, vectors) = vector_column_to_matrix(&batch, column)?;
+        if vectors.num_columns() != dimension {
+            return Err(Error::Index(format!(
+                "Vector dimension {} of column {} does not match the index: {}",
+                vectors.num_columns(),
+                column,
+                dimension
+            )));
+        }
+        let row_ids = as_primitive_array::<UInt64Type>(
+            batch
+                .column_by_name(ROW_ID)
+                .ok_or(Error::Index("row id column not found".to_string()))?,
+        );
+        for (i, row_id) in row_ids.values().iter().enumerate() {
+            let Some(&id) = row_index.get(row_id) else {
+                continue;
+            };
+            values[id * dimension..(id + 1) * dimension].copy_from_slice(vectors.row(i).unwrap());
+        }
+    }
+
+    let vectors = MatrixView::new(Arc::new(Float32Array::from(values)), dimension);
+    let mut graph = GraphBuilder::new(&vertices, vectors, metric_type);
+    for (id, neighbors) in neighbors.iter().enumerate() {
+        graph.set_neighbors(id, neighbors);
+    }
+    Ok(graph)
+}
+
+/// Vectors sampled from the graph, see [`SampleConfig`].
+struct Sample {
+    /// Sampled vertex ids, sorted.
+    ids: Vec<usize>,
+
+    /// Vectors of the sampled vertices, cached for the initialization.
+    vectors: MatrixView,
+}
+
+impl Sample {
+    fn draw(graph: &GraphBuilder<RowVertex>, size: usize, rng: &mut impl Rng) -> Result<Self> {
+        if size == 0 {
+            return Err(Error::Index(
+                "DiskANN: sample size must be greater than 0".to_string(),
+            ));
+        }
+        let mut ids = rand::seq::index::sample(rng, graph.len(), size.min(graph.len())).into_vec();
+        ids.sort_unstable();
+        let values = ids
+            .iter()
+            .flat_map(|&id| graph.data.row(id).unwrap().iter().copied())
+            .collect::<Vec<_>>();
+        let vectors = MatrixView::new(
+            Arc::new(Float32Array::from(values)),
+            graph.data.num_columns(),
+        );
+        Ok(Self { ids, vectors })
+    }
+}
+
+/// Randomly initialize the graph with r random neighbors for each vertex.
+///
+/// This is the only scan of the dataset of a build: the sample, if any, is drawn
+/// from the loaded vectors.
+///
+/// Parameters
+/// ----------
+///  - dataset: the dataset to index.
+///  - columns: the vector columns to index, with their weights. The vectors of
+///    a row are scaled by their weights and concatenated into one vector.
+///  - r: the number of neighbors to connect to.
+///  - normalize: build over a normalized copy of the vectors, see
+///    [`DiskANNParams::normalize`].
+///  - sample: if set, the neighbors are drawn from a random sample of the vertices,
+///    returned with the graph.
+///  - init_strategy: whether the random edges are added in both directions.
+///  - rng: the random number generator.
+///
+#[allow(clippy::too_many_arguments)]
+async fn init_graph(
+    dataset: &Dataset,
+    columns: &[(&str, f32)],
+    r: usize,
+    metric_type: MetricType,
+    normalize: bool,
+    sample: Option<&SampleConfig>,
+    init_strategy: InitStrategy,
+    mut rng: impl Rng,
+) -> Result<(GraphBuilder<RowVertex>, Option<Sample>)> {
+    let mut graph = load_graph(dataset, columns, metric_type, normalize).await?;
 
-    let distribution = Uniform::new(0, batch.num_rows());
+    if graph.is_empty() {
+        return Ok((graph, None));
+    }
+    let sample = sample
+        .map(|config| Sample::draw(&graph, config.size, &mut rng))
+        .transpose()?;
+    let candidates = sample.as_ref().map_or(graph.len(), |s| s.ids.len());
+    let distribution = Uniform::new(0, candidates);
     // Randomly connect to r neighbors.
     for i in 0..graph.len() {
+        // Can not connect to more neighbors than the other candidates.
+        let is_candidate = sample
+            .as_ref()
+            .map_or(true, |s| s.ids.binary_search(&i).is_ok());
+        let r = r.min(candidates - is_candidate as usize);
         let mut neighbor_ids: HashSet<u32> = graph.neighbors(i)?.iter().copied().collect();
 
         while neighbor_ids.len() < r {
-            let neighbor_id = rng.sample(distribution);
+            let neighbor_id = match sample.as_ref() {
+                Some(sample) => sample.ids[rng.sample(distribution)],
+                None => rng.sample(distribution),
+            };
             if neighbor_id != i {
                 neighbor_ids.insert(neighbor_id as u32);
             }
@@ -169,18 +762,245 @@ async fn init_graph(
             n.extend(neighbor_ids.iter().copied());
             // Release mutable borrow on graph.
         }
-        {
+        // Not with a sample, whose vertices would get a huge in-degree. The reverse
+        // edges are added while indexing instead.
+        if sample.is_none() && init_strategy == InitStrategy::Bidirectional {
             for neighbor_id in neighbor_ids.iter() {
                 graph.add_neighbor(*neighbor_id as usize, i);
             }
         }
     }
 
-    Ok(graph)
+    Ok((graph, sample))
+}
+
+/// Remove the rows where any of the vector columns is null.
+fn filter_null_vectors(batch: &RecordBatch, columns: &[&str]) -> Result<RecordBatch> {
+    let arrays = columns
+        .iter()
+        .map(|column| {
+            batch
+                .column_by_qualified_name(column)
+                .ok_or(Error::Index(format!("column {} not found", column)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if arrays.iter().all(|arr| arr.null_count() == 0) {
+        return Ok(batch.clone());
+    }
+    let mask = (0..batch.num_rows())
+        .map(|i| Some(arrays.iter().all(|arr| arr.is_valid(i))))
+        .collect::<BooleanArray>();
+    Ok(filter_record_batch(batch, &mask)?)
+}
+
+/// Concatenate the vectors of each row over the `(column, weight)` columns,
+/// scaling each vector by its weight.
+///
+/// All the columns must be vector columns of the same element type, see
+/// [`vector_column_to_matrix`]. The dimension of the result is the sum of the
+/// dimensions of the columns.
+fn concat_vector_columns(batch: &RecordBatch, columns: &[(&str, f32)]) -> Result<MatrixView> {
+    let mut matrices = Vec::with_capacity(columns.len());
+    let mut value_type: Option<DataType> = None;
+    for (column, _) in columns {
+        let (elem_type, matrix) = vector_column_to_matrix(batch, column)?;
+        if let Some(value_type) = value_type.as_ref() {
+            if value_type != &elem_type {
+                return Err(Error::Index(format!(
+                    "Vector column {} has element type {}, expected: {}",
+                    column, elem_type, value_type
+                )));
+            }
+        }
+        value_type = Some(elem_type);
+        matrices.push(matrix);
+    }
+
+    match columns {
+        [] => Err(Error::Index("No vector column to index".to_string())),
+        [(_, weight)] if *weight == 1.0 => Ok(matrices.pop().unwrap()),
+        _ => {
+            let num_rows = batch.num_rows();
+            let dimension = matrices.iter().map(|m| m.num_columns()).sum::<usize>();
+            let mut values = Vec::with_capacity(num_rows * dimension);
+            for i in 0..num_rows {
+                for (matrix, (_, weight)) in matrices.iter().zip(columns.iter()) {
+                    let row = matrix.row(i).unwrap();
+                    values.extend(row.iter().map(|v| v * weight));
+                }
+            }
+            Ok(MatrixView::new(
+                Arc::new(Float32Array::from(values)),
+                dimension,
+            ))
+        }
+    }
+}
+
+/// Read a vector column of the batch as a matrix, and returns it with its element type.
+///
+/// The column is either a FixedSizeList, or a List whose field metadata declares the
+/// dimension of the vectors under [`VECTOR_DIM_METADATA_KEY`]. In the latter case,
+/// all the lists must be non-null and have the declared length.
+fn vector_column_to_matrix(batch: &RecordBatch, column: &str) -> Result<(DataType, MatrixView)> {
+    let array = batch
+        .column_by_qualified_name(column)
+        .ok_or(Error::Index(format!("column {} not found", column)))?;
+    match array.data_type() {
+        DataType::FixedSizeList(field, dim) => Ok((
+            field.data_type().clone(),
+            fixed_size_list_to_matrix(as_fixed_size_list_array(array), field.data_type(), *dim)?,
+        )),
+        // Some producers dictionary-encode identical vectors. They are decoded,
+        // one vector per row.
+        DataType::Dictionary(_, value_type) => match value_type.as_ref() {
+            DataType::FixedSizeList(field, dim) => {
+                let decoded = cast(array, value_type)?;
+                Ok((
+                    field.data_type().clone(),
+                    fixed_size_list_to_matrix(
+                        as_fixed_size_list_array(decoded.as_ref()),
+                        field.data_type(),
+                        *dim,
+                    )?,
+                ))
+            }
+            dt => Err(Error::Index(format!(
+                "Vector column {} must be a dictionary of FixedSizeList, got values: {}",
+                column, dt
+            ))),
+        },
+        DataType::List(field) => {
+            let schema = batch.schema();
+            let dimension = schema
+                .field_with_name(column)
+                .ok()
+                .and_then(|f| f.metadata().get(VECTOR_DIM_METADATA_KEY))
+                .ok_or_else(|| {
+                    Error::Index(format!(
+                        "List column {} does not declare its vector dimension in metadata {}",
+                        column, VECTOR_DIM_METADATA_KEY
+                    ))
+                })?
+                .parse::<usize>()
+                .map_err(|e| {
+                    Error::Index(format!(
+                        "Invalid vector dimension of column {}: {}",
+                        column, e
+                    ))
+                })?;
+            let list = array.as_list::<i32>();
+            if list.null_count() > 0 {
+                return Err(Error::Index(format!(
+                    "Vector column {} contains null vectors",
+                    column
+                )));
+            }
+            let offsets = list.value_offsets();
+            if let Some(i) = offsets
+                .windows(2)
+                .position(|w| (w[1] - w[0]) as usize != dimension)
+            {
+                return Err(Error::Index(format!(
+                    "Vector {} of column {} has length {}, expected: {}",
+                    i,
+                    column,
+                    offsets[i + 1] - offsets[i],
+                    dimension
+                )));
+            }
+            let start = offsets[0] as usize;
+            let end = offsets[offsets.len() - 1] as usize;
+            let values = list.values().slice(start, end - start);
+            let values = values
+                .as_any()
+                .downcast_ref::<Float32Array>()
+                .ok_or_else(|| {
+                    Error::Index(format!(
+                        "Vector column {} must contain Float32 values, got: {}",
+                        column,
+                        values.data_type()
+                    ))
+                })?;
+            Ok((
+                field.data_type().clone(),
+                MatrixView::new(Arc::new(values.clone()), dimension),
+            ))
+        }
+        dt => Err(Error::Index(format!(
+            "Vector column {} must be a FixedSizeList or a List with a declared dimension, got: {}",
+            column, dt
+        ))),
+    }
+}
+
+/// Vectors of a FixedSizeList array of `elem_type` values, widening integers and f16
+/// to f32 and downcasting f64 to f32.
+///
+/// The graph only holds f32 vectors, so the coordinates of integer vectors are exact
+/// up to 2^24 in magnitude, and rounded to the nearest f32 beyond.
+fn fixed_size_list_to_matrix(
+    fsl: &FixedSizeListArray,
+    elem_type: &DataType,
+    dim: i32,
+) -> Result<MatrixView> {
+    match elem_type {
+        // Integer vectors are widened to f32, which is exact up to 2^24, f16 vectors
+        // exactly. f64 vectors lose precision, see [`DiskANNParams::exact_rerank`]
+        // to keep it.
+        DataType::Int32 | DataType::Int64 | DataType::Float16 | DataType::Float64 => {
+            let values = cast(fsl.values(), &DataType::Float32)?;
+            Ok(MatrixView::new(
+                Arc::new(as_primitive_array::<Float32Type>(values.as_ref()).clone()),
+                dim as usize,
+            ))
+        }
+        _ => fsl.try_into(),
+    }
+}
+
+/// Maximum number of vectors checked by [`check_normalized`].
+const NORMALIZED_SAMPLE_SIZE: usize = 1024;
+
+/// Check that evenly spaced vectors of the matrix have a unit norm.
+fn check_normalized(vectors: &MatrixView) -> Result<()> {
+    let step = (vectors.num_rows() / NORMALIZED_SAMPLE_SIZE).max(1);
+    for i in (0..vectors.num_rows()).step_by(step) {
+        let norm = norm_l2(vectors.row(i).unwrap());
+        if (norm - 1.0).abs() > 1e-3 {
+            return Err(Error::Index(format!(
+                "DiskANN: vector {} has norm {}, but the cosine metric requires normalized \
+                 vectors. Normalize the vectors before indexing them.",
+                i, norm
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The distance function to prune the neighbors with, for the metric of the graph.
+///
+/// The scalar kernels are used, so that the comparisons of [`robust_prune`] do not
+/// depend on the SIMD width of the CPU: the same vectors yield the same graph on
+/// every machine.
+fn prune_distance_func(
+    metric_type: &MetricType,
+) -> Arc<dyn Fn(&[f32], &[f32]) -> f32 + Send + Sync> {
+    match metric_type {
+        MetricType::L2 => Arc::new(l2_distance_scalar),
+        MetricType::Cosine => Arc::new(cosine_distance_scalar),
+        // Already summed in order.
+        MetricType::WeightedL2(_) => metric_type.func(),
+    }
 }
 
-/// Distance between two vectors in the matrix.
-fn distance(matrix: &MatrixView, i: usize, j: usize) -> Result<f32> {
+/// Distance between two vectors in the matrix, to prune the neighbors.
+fn distance(
+    matrix: &MatrixView,
+    distance_func: &dyn Fn(&[f32], &[f32]) -> f32,
+    i: usize,
+    j: usize,
+) -> Result<f32> {
     let vector_i = matrix
         .row(i)
         .ok_or(Error::Index("Invalid row index".to_string()))?;
@@ -188,71 +1008,153 @@ fn distance(matrix: &MatrixView, i: usize, j: usize) -> Result<f32> {
         .row(j)
         .ok_or(Error::Index("Invalid row index".to_string()))?;
 
-    Ok(l2_distance(vector_i, vector_j))
+    Ok(distance_func(vector_i, vector_j))
 }
 
+/// Above this estimated work, `|visited| * r` distances, [`robust_prune`] runs on
+/// the blocking thread pool. Smaller prunes are cheaper than scheduling a task.
+const ROBUST_PRUNE_BLOCKING_WORK: usize = 16 * 1024;
+
 /// Algorithm 2 in the paper.
 async fn robust_prune<V: Vertex + Clone>(
+    graph: &GraphBuilder<V>,
+    id: usize,
+    visited: HashSet<usize>,
+    alpha: f32,
+    r: usize,
+) -> Result<Vec<u32>> {
+    robust_prune_with_threshold(graph, id, visited, alpha, r, ROBUST_PRUNE_BLOCKING_WORK).await
+}
+
+/// [`robust_prune`], running on the blocking thread pool if `|visited| * r` is
+/// above `blocking_work`.
+async fn robust_prune_with_threshold<V: Vertex + Clone>(
     graph: &GraphBuilder<V>,
     id: usize,
     mut visited: HashSet<usize>,
     alpha: f32,
     r: usize,
+    blocking_work: usize,
 ) -> Result<Vec<u32>> {
     visited.remove(&id);
     let neighbors = graph.neighbors(id)?;
     visited.extend(neighbors.iter().map(|id| *id as usize));
 
-    let mut heap: BinaryHeap<VertexWithDistance> = visited
+    // Min-heap, to pop `argmin(Visited)` first.
+    let distance_func = prune_distance_func(&graph.metric_type());
+    let heap: BinaryHeap<Reverse<VertexWithDistance>> = visited
         .iter()
         .map(|v| {
-            let dist = distance(&graph.data, id, *v).unwrap();
-            VertexWithDistance {
+            let dist = distance(&graph.data, distance_func.as_ref(), id, *v).unwrap();
+            Reverse(VertexWithDistance {
                 id: *v,
                 distance: OrderedFloat(dist),
-            }
+            })
         })
         .collect();
 
-    let matrix = graph.data.clone();
-    let new_neighbours = tokio::task::spawn_blocking(move || {
-        let mut new_neighbours: Vec<usize> = vec![];
-        while !visited.is_empty() {
-            let mut p = heap.pop().unwrap();
-            while !visited.contains(&p.id) {
-                // Because we are using a heap for `argmin(Visited)` in the original
-                // algorithm, we need to pop out the vertices that are not in `visited` anymore.
-                p = heap.pop().unwrap();
-            }
+    let new_neighbours = if visited.len() * r > blocking_work {
+        let matrix = graph.data.clone();
+        tokio::task::spawn_blocking(move || {
+            select_robust_neighbors(&matrix, distance_func.as_ref(), id, visited, heap, alpha, r)
+        })
+        .await??
+    } else {
+        select_robust_neighbors(
+            &graph.data,
+            distance_func.as_ref(),
+            id,
+            visited,
+            heap,
+            alpha,
+            r,
+        )?
+    };
 
-            new_neighbours.push(p.id);
-            if new_neighbours.len() >= r {
-                break;
-            }
-            let mut to_remove: HashSet<usize> = HashSet::new();
-            for pv in visited.iter() {
-                let dist_prime = distance(&matrix, p.id, *pv)?;
-                let dist_query = distance(&matrix, id, *pv)?;
-                if alpha * dist_prime <= dist_query {
-                    to_remove.insert(*pv);
-                }
-            }
-            for pv in to_remove.iter() {
-                visited.remove(pv);
+    Ok(new_neighbours.iter().map(|id| *id as u32).collect())
+}
+
+/// The selection loop of [`robust_prune`], over the `visited` vertices in `heap`.
+fn select_robust_neighbors(
+    matrix: &MatrixView,
+    distance_func: &dyn Fn(&[f32], &[f32]) -> f32,
+    id: usize,
+    mut visited: HashSet<usize>,
+    mut heap: BinaryHeap<Reverse<VertexWithDistance>>,
+    alpha: f32,
+    r: usize,
+) -> Result<Vec<usize>> {
+    let mut new_neighbours: Vec<usize> = vec![];
+    while !visited.is_empty() {
+        let Reverse(mut p) = heap.pop().unwrap();
+        while !visited.contains(&p.id) {
+            // Because we are using a heap for `argmin(Visited)` in the original
+            // algorithm, we need to pop out the vertices that are not in `visited` anymore.
+            p = heap.pop().unwrap().0;
+        }
+
+        new_neighbours.push(p.id);
+        if new_neighbours.len() >= r {
+            break;
+        }
+        let mut to_remove: HashSet<usize> = HashSet::new();
+        for pv in visited.iter() {
+            let dist_prime = distance(matrix, distance_func, p.id, *pv)?;
+            let dist_query = distance(matrix, distance_func, id, *pv)?;
+            if alpha * dist_prime <= dist_query {
+                to_remove.insert(*pv);
             }
         }
-        Ok::<_, Error>(new_neighbours)
-    })
-    .await??;
+        for pv in to_remove.iter() {
+            visited.remove(pv);
+        }
+    }
+    Ok(new_neighbours)
+}
 
-    Ok(new_neighbours.iter().map(|id| *id as u32).collect())
+/// Keep the `r` nearest vertices among the visited vertices and the current neighbors.
+fn nearest_prune<V: Vertex + Clone>(
+    graph: &GraphBuilder<V>,
+    id: usize,
+    mut visited: HashSet<usize>,
+    r: usize,
+) -> Result<Vec<u32>> {
+    visited.extend(graph.neighbors(id)?.iter().map(|id| *id as usize));
+    visited.remove(&id);
+
+    let distance_func = prune_distance_func(&graph.metric_type());
+    let mut candidates = visited
+        .into_iter()
+        .map(|v| {
+            let dist = distance(&graph.data, distance_func.as_ref(), id, v)?;
+            Ok(VertexWithDistance::new(v, dist))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    candidates.sort();
+    candidates.truncate(r);
+    Ok(candidates.iter().map(|c| c.id as u32).collect())
+}
+
+/// Prune the neighbors of the vertex `id` down to `r` with the given strategy.
+async fn prune<V: Vertex + Clone>(
+    graph: &GraphBuilder<V>,
+    id: usize,
+    visited: HashSet<usize>,
+    strategy: PruneStrategy,
+    r: usize,
+) -> Result<Vec<u32>> {
+    match strategy {
+        PruneStrategy::Robust { alpha } => robust_prune(graph, id, visited, alpha, r).await,
+        PruneStrategy::Nearest => nearest_prune(graph, id, visited, r),
+    }
 }
 
-/// Find the index of the medoid vector in all vectors.
+/// Find the index of the medoid vector in all vectors, i.e., the closest to their
+/// centroid. Among equally close vectors, e.g., duplicates, the lowest index wins.
 async fn find_medoid(vectors: &MatrixView, metric_type: MetricType) -> Result<usize> {
     let centroid = vectors
         .centroid()
-        .ok_or_else(|| Error::Index("Cannot find the medoid of an empty matrix".to_string()))?;
+        .ok_or_else(|| Error::Index("no vectors to compute medoid".to_string()))?;
 
     let dist_func = metric_type.batch_func();
     // Find the closest vertex to the centroid.
@@ -265,32 +1167,62 @@ async fn find_medoid(vectors: &MatrixView, metric_type: MetricType) -> Result<us
     Ok(medoid_idx as usize)
 }
 
-/// One pass of index building.
+/// Returns true if two neighbor lists contain the same vertices, regardless of their order.
+fn same_neighbors(a: &[u32], b: &[u32]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut a = a.to_vec();
+    let mut b = b.to_vec();
+    a.sort_unstable();
+    b.sort_unstable();
+    a == b
+}
+
+/// Index a batch of vertices, in the given order, as part of an indexing pass.
+///
+/// If `dirty` is provided, only the vertices flagged as dirty are processed.
+/// The vertices whose neighbors have changed are flagged in `changed`, they are
+/// the only ones to process in the next pass if [`DiskANNParams::skip_converged`] is set.
+///
+/// Once `deadline` has passed, the remaining vertices are left as they are.
+/// Returns the number of vertices of `ids` processed or skipped before that.
+#[allow(clippy::too_many_arguments)]
 async fn index_once<V: Vertex + Clone>(
     graph: &mut GraphBuilder<V>,
     medoid: usize,
-    alpha: f32,
-    r: usize,
+    strategy: PruneStrategy,
     l: usize,
-    mut rng: impl Rng,
-) -> Result<()> {
-    let mut ids = (0..graph.len()).collect::<Vec<_>>();
-    ids.shuffle(&mut rng);
-
+    params: &DiskANNParams,
+    ids: &[usize],
+    dirty: Option<&[bool]>,
+    changed: &mut [bool],
+    deadline: Option<Instant>,
+) -> Result<usize> {
+    let r = params.r;
     for (i, &id) in ids.iter().enumerate() {
+        if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+            return Ok(i);
+        }
+        if let Some(dirty) = dirty {
+            if !dirty[id] {
+                continue;
+            }
+        }
+
         let vector = graph
             .data
-            .row(i)
+            .row(id)
             .ok_or_else(|| Error::Index(format!("Cannot find vector with id {}", id)))?;
 
         let state = greedy_search(graph, medoid, vector, 1, l)?;
 
-        graph
-            .neighbors_mut(id)
-            .extend(state.visited.iter().map(|id| *id as u32));
-
-        let neighbors = robust_prune(graph, id, state.visited, alpha, r).await?;
-        graph.set_neighbors(id, neighbors.to_vec());
+        // The prune considers the current neighbors along with the visited vertices.
+        let neighbors = prune(graph, id, state.visited, strategy, r).await?;
+        if !same_neighbors(graph.neighbors(id)?, &neighbors) {
+            changed[id] = true;
+        }
+        graph.set_neighbors(id, &neighbors);
 
         let fixed_graph: &GraphBuilder<V> = graph;
         let neighbours = stream::iter(neighbors)
@@ -303,7 +1235,7 @@ async fn index_once<V: Vertex + Clone>(
                 neighbor_set.insert(id);
                 if neighbor_set.len() + 1 > r {
                     let new_neighbours =
-                        robust_prune(fixed_graph, j as usize, neighbor_set, alpha, r).await?;
+                        prune(fixed_graph, j as usize, neighbor_set, strategy, r).await?;
                     Ok::<_, Error>((j as usize, new_neighbours))
                 } else {
                     Ok::<_, Error>((
@@ -312,15 +1244,257 @@ async fn index_once<V: Vertex + Clone>(
                     ))
                 }
             })
-            .buffered(num_cpus::get())
+            .buffered(params.concurrency_limit())
             .try_collect::<Vec<_>>()
             .await?;
         for (j, nbs) in neighbours {
+            if !same_neighbors(graph.neighbors(j)?, &nbs) {
+                changed[j] = true;
+            }
             graph.set_neighbors(j, nbs);
         }
     }
 
-    Ok(())
+    Ok(ids.len())
+}
+
+/// Merge the graphs built over disjoint shards of the rows into one graph.
+///
+/// The vertices of the shards are concatenated in order, keeping their row ids,
+/// and the neighbors are renumbered accordingly. The vectors are copied once, into
+/// one allocation. The entry point of each shard is linked to the medoid of the
+/// merged graph, then the shards are stitched by one indexing pass with the last
+/// prune strategy of `params`, in an order drawn from `params.seed`. The pass runs
+/// over all the vertices: unless the shards are spatially partitioned, every vertex
+/// is close to the boundary of the other shards.
+///
+/// Returns the merged graph and its medoid.
+pub(crate) async fn merge_shards(
+    shards: Vec<GraphBuilder<RowVertex>>,
+    params: &DiskANNParams,
+) -> Result<(GraphBuilder<RowVertex>, usize)> {
+    let strategy = *params
+        .prune_strategies()?
+        .last()
+        .ok_or_else(|| Error::Index("DiskANN: no prune strategy to merge shards".to_string()))?;
+    let l = *params.search_sizes()?.last().unwrap();
+    let first = shards
+        .first()
+        .ok_or_else(|| Error::Index("DiskANN: no shard to merge".to_string()))?;
+    let metric_type = first.metric_type();
+    let normalized = first.is_normalized();
+    let dim = first.data.num_columns();
+    for shard in shards.iter() {
+        if shard.metric_type() != metric_type {
+            return Err(Error::Index(format!(
+                "DiskANN: cannot merge shards with different metric types: {:?} and {:?}",
+                metric_type,
+                shard.metric_type()
+            )));
+        }
+        if shard.is_normalized() != normalized {
+            return Err(Error::Index(
+                "DiskANN: cannot merge normalized and not normalized shards".to_string(),
+            ));
+        }
+        if shard.data.num_columns() != dim {
+            return Err(Error::Index(format!(
+                "DiskANN: cannot merge shards of dimensions {} and {}",
+                dim,
+                shard.data.num_columns()
+            )));
+        }
+    }
+
+    // The vectors of all the shards are copied once, into one allocation, and each
+    // shard is dropped once copied.
+    let num_vertices = shards.iter().map(|shard| shard.len()).sum::<usize>();
+    let mut values = Vec::with_capacity(num_vertices * dim);
+    let mut vertices = Vec::with_capacity(num_vertices);
+    let mut neighbors = Vec::with_capacity(num_vertices);
+    let mut row_ids = HashSet::with_capacity(num_vertices);
+    let mut entries = vec![];
+    for shard in shards {
+        let offset = vertices.len();
+        entries.push(offset + find_medoid(&shard.data, metric_type.clone()).await?);
+        // The merged graph normalizes the vectors again, with their original norms.
+        values.extend_from_slice(shard.original_data().data().values());
+        for node in shard.nodes {
+            if let Some(row_id) = node.vertex.row_id() {
+                if !row_ids.insert(row_id) {
+                    return Err(Error::Index(format!(
+                        "DiskANN: row {} is in more than one shard",
+                        row_id
+                    )));
+                }
+            }
+            vertices.push(node.vertex);
+            neighbors.push(
+                node.neighbors
+                    .into_iter()
+                    .map(|n| n + offset as u32)
+                    .collect::<Vec<_>>(),
+            );
+        }
+    }
+    let vectors = MatrixView::new(Arc::new(Float32Array::from(values)), dim);
+    let mut graph = GraphBuilder::new(&vertices, vectors, metric_type);
+    if normalized {
+        graph.normalize();
+    }
+    for (id, neighbors) in neighbors.iter().enumerate() {
+        graph.set_neighbors(id, neighbors);
+    }
+
+    // Link the entry points of the shards, so that the searches of the stitching
+    // pass reach every shard from the medoid.
+    let medoid = find_medoid(&graph.data, graph.metric_type()).await?;
+    for entry in entries {
+        if entry != medoid && !graph.neighbors(medoid)?.contains(&(entry as u32)) {
+            graph.add_neighbor(medoid, entry);
+            graph.add_neighbor(entry, medoid);
+        }
+    }
+
+    let seed = params
+        .seed
+        .unwrap_or_else(|| rand::thread_rng().gen::<u64>());
+    let mut ids = (0..graph.len()).collect::<Vec<_>>();
+    ids.shuffle(&mut SmallRng::seed_from_u64(seed));
+    let mut changed = vec![false; graph.len()];
+    index_once(
+        &mut graph,
+        medoid,
+        strategy,
+        l,
+        params,
+        &ids,
+        None,
+        &mut changed,
+        None,
+    )
+    .await?;
+    Ok((graph, medoid))
+}
+
+/// Prune the vertices with more than `params.r` neighbors, e.g., after many
+/// inserts, with the last prune strategy of `params`.
+///
+/// Returns the number of vertices pruned.
+pub(crate) async fn rebalance<V: Vertex + Clone>(
+    graph: &mut GraphBuilder<V>,
+    params: &DiskANNParams,
+) -> Result<usize> {
+    let strategy = *params
+        .prune_strategies()?
+        .last()
+        .ok_or_else(|| Error::Index("DiskANN: no prune strategy to rebalance".to_string()))?;
+    let r = params.r;
+    let mut over_degree = vec![];
+    for id in 0..graph.len() {
+        if graph.neighbors(id)?.len() > r {
+            over_degree.push(id);
+        }
+    }
+
+    // Each prune only reads the neighbors of its own vertex, so they all run on
+    // the graph before the rebalancing.
+    let fixed_graph: &GraphBuilder<V> = graph;
+    let pruned = stream::iter(over_degree)
+        .map(|id| async move {
+            let visited = fixed_graph
+                .neighbors(id)?
+                .iter()
+                .map(|&n| n as usize)
+                .collect::<HashSet<_>>();
+            Ok::<_, Error>((id, prune(fixed_graph, id, visited, strategy, r).await?))
+        })
+        .buffered(params.concurrency_limit())
+        .try_collect::<Vec<_>>()
+        .await?;
+    let num_pruned = pruned.len();
+    for (id, neighbors) in pruned {
+        graph.set_neighbors(id, neighbors);
+    }
+    Ok(num_pruned)
+}
+
+/// Remove the vertices of deleted rows, repairing only the neighborhoods that
+/// referenced them, instead of building the graph again.
+///
+/// Each vertex that had a deleted neighbor is pruned, with the last prune strategy
+/// of `params`, over its remaining neighbors and the remaining neighbors of its
+/// deleted ones, as in FreshDiskANN. The other vertices keep their neighbors. The
+/// rows not in the graph are ignored.
+///
+/// The entry point of the searches, `medoid`, is kept unless its row is deleted, in
+/// which case the medoid of the remaining vectors replaces it.
+///
+/// The vertices are renumbered as by [`GraphBuilder::compact`]. Returns its mapping
+/// from the old vertex ids to the new ones, and the new id of the entry point.
+pub(crate) async fn repair_after_delete<V: Vertex + Clone>(
+    graph: &mut GraphBuilder<V>,
+    medoid: usize,
+    deleted_row_ids: &[u64],
+    params: &DiskANNParams,
+) -> Result<(Vec<Option<u32>>, usize)> {
+    if medoid >= graph.len() {
+        return Err(Error::Index(format!(
+            "DiskANN: medoid {} is out of range: {} vertices",
+            medoid,
+            graph.len()
+        )));
+    }
+    let strategy = *params
+        .prune_strategies()?
+        .last()
+        .ok_or_else(|| Error::Index("DiskANN: no prune strategy to repair".to_string()))?;
+    let r = params.r;
+    let deleted = deleted_row_ids
+        .iter()
+        .filter_map(|&row_id| graph.vertex_for_row(row_id))
+        .collect::<HashSet<_>>();
+
+    let mut affected = vec![];
+    for id in (0..graph.len()).filter(|id| !deleted.contains(id)) {
+        let neighbors = graph.neighbors(id)?;
+        if !neighbors.iter().any(|&n| deleted.contains(&(n as usize))) {
+            continue;
+        }
+        let mut candidates = HashSet::new();
+        for &neighbor in neighbors {
+            let neighbor = neighbor as usize;
+            if deleted.contains(&neighbor) {
+                let hops = graph.neighbors(neighbor)?.iter().map(|&n| n as usize);
+                candidates.extend(hops.filter(|n| !deleted.contains(n)));
+            } else {
+                candidates.insert(neighbor);
+            }
+        }
+        affected.push((id, candidates));
+    }
+
+    // The prunes also read the current neighbors, which are all candidates.
+    for (id, _) in affected.iter() {
+        graph.neighbors_mut(*id).clear();
+    }
+    let fixed_graph: &GraphBuilder<V> = graph;
+    let pruned = stream::iter(affected)
+        .map(|(id, candidates)| async move {
+            Ok::<_, Error>((id, prune(fixed_graph, id, candidates, strategy, r).await?))
+        })
+        .buffered(params.concurrency_limit())
+        .try_collect::<Vec<_>>()
+        .await?;
+    for (id, neighbors) in pruned {
+        graph.set_neighbors(id, neighbors);
+    }
+    let id_map = graph.compact(&deleted);
+    let medoid = match id_map[medoid] {
+        Some(id) => id as usize,
+        None => find_medoid(&graph.data, graph.metric_type()).await?,
+    };
+    Ok((id_map, medoid))
 }
 
 async fn write_index_file(
@@ -357,10 +1531,8 @@ async fn write_index_file(
             spec_version: 1,
             dimension: dimension as u32,
             stages,
-            metric_type: match metric_type {
-                MetricType::L2 => pb::VectorMetricType::L2.into(),
-                MetricType::Cosine => pb::VectorMetricType::Cosine.into(),
-            },
+            metric_type: pb::VectorMetricType::from(&metric_type).into(),
+            metric_weights: metric_type.weights(),
         })),
     };
 
@@ -372,19 +1544,32 @@ async fn write_index_file(
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
 
-    use std::sync::Arc;
+    use std::collections::HashMap;
 
-    use arrow_array::{FixedSizeListArray, RecordBatch, RecordBatchReader};
-    use arrow_schema::{DataType, Field, Schema as ArrowSchema};
+    use approx::assert_relative_eq;
+    use arrow_array::{
+        cast::as_fixed_size_list_array,
+        types::{Float32Type, Int32Type},
+        DictionaryArray, FixedSizeListArray, Float16Array, Float64Array, Int32Array, ListArray,
+        RecordBatchReader, UInt64Array,
+    };
+    use arrow_schema::{Field, Schema as ArrowSchema};
+    use half::f16;
     use tempfile;
 
     use crate::dataset::WriteParams;
+    use crate::index::vector::diskann::search::{
+        evaluate_recall, greedy_search_with_early_termination, search, DiskANNGraph, SearchParams,
+    };
+    use crate::index::vector::flat::brute_force_knn;
+    use crate::index::vector::SearchIndex;
+    use crate::linalg::l2::L2;
     use crate::utils::testing::generate_random_array;
 
-    async fn create_dataset(uri: &str, n: usize, dim: usize) -> Arc<Dataset> {
+    pub(crate) async fn create_dataset(uri: &str, n: usize, dim: usize) -> Arc<Dataset> {
         let schema = Arc::new(ArrowSchema::new(vec![Field::new(
             "vector",
             DataType::FixedSizeList(
@@ -415,20 +1600,1601 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_init() {
+    async fn test_tune_search_size() {
         let tmp_dir = tempfile::tempdir().unwrap();
         let uri = tmp_dir.path().to_str().unwrap();
-        let dataset = create_dataset(uri, 200, 64).await;
+        let dataset = create_dataset(uri, 200, 16).await;
 
-        let rng = rand::thread_rng();
-        let graph = init_graph(dataset.as_ref(), "vector", 10, MetricType::L2, rng)
+        let mut params = DiskANNParams::default();
+        params.r(10).l(20);
+        let index = DiskANNGraph::build(dataset.as_ref(), "vector", &params)
             .await
             .unwrap();
 
-        for (id, node) in graph.nodes.iter().enumerate() {
-            // Statistically， each node should have 10 neighbors.
-            assert!(!node.neighbors.is_empty());
-            assert_eq!(node.vertex.row_id as usize, id);
+        let k = 5;
+        let queries = MatrixView::random(10, 16);
+        let ground_truth = brute_force_knn(dataset.as_ref(), "vector", &queries, k, MetricType::L2)
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| r.iter().map(|(row_id, _)| *row_id).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        // Any search size satisfies a zero recall, so the smallest one is picked.
+        let search_size = index
+            .tune_search_size(&queries, &ground_truth, k, 0.0)
+            .unwrap();
+        assert_eq!(search_size, k);
+
+        // The largest search size gives the best recall of this graph.
+        let max_recall = index
+            .evaluate_recall(&queries, &ground_truth, k, 200)
+            .unwrap();
+        let search_size = index
+            .tune_search_size(&queries, &ground_truth, k, max_recall)
+            .unwrap();
+        assert!((k..=200).contains(&search_size));
+        let recall = index
+            .evaluate_recall(&queries, &ground_truth, k, search_size)
+            .unwrap();
+        assert!(recall >= max_recall);
+    }
+
+    #[tokio::test]
+    async fn test_avg_hops() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let dataset = create_dataset(uri, 200, 16).await;
+
+        let mut params = DiskANNParams::default();
+        params.r(10).l(20);
+        let index = DiskANNGraph::build(dataset.as_ref(), "vector", &params)
+            .await
+            .unwrap();
+
+        let queries = MatrixView::random(10, 16);
+        let hops = index.avg_hops(&queries, 5, 20).unwrap();
+        assert!(hops.is_finite());
+        // At least the medoid is expanded, and each vertex at most once.
+        assert!((1.0..=200.0).contains(&hops));
+
+        let empty = MatrixView::new(Arc::new(Float32Array::from(Vec::<f32>::new())), 16);
+        assert!(index.avg_hops(&empty, 5, 20).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_early_termination() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let dataset = create_dataset(uri, 500, 16).await;
+
+        let mut params = DiskANNParams::default();
+        params.r(10).l(30);
+        let (graph, report) = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+        let medoid = report.medoid;
+
+        let k = 10;
+        let queries = graph.data.sample(20);
+        let ground_truth = brute_force_knn(dataset.as_ref(), "vector", &queries, k, MetricType::L2)
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| r.iter().map(|(row_id, _)| *row_id).collect::<HashSet<_>>())
+            .collect::<Vec<_>>();
+        let recall = |params: &SearchParams| {
+            let hits = ground_truth
+                .iter()
+                .enumerate()
+                .map(|(i, truth)| {
+                    let query = queries.row(i).unwrap();
+                    search(&graph, medoid, query, k, params)
+                        .unwrap()
+                        .results
+                        .iter()
+                        .filter(|(row_id, _)| truth.contains(row_id))
+                        .count()
+                })
+                .sum::<usize>();
+            hits as f32 / (k * ground_truth.len()) as f32
+        };
+
+        let full_params = SearchParams::new(50);
+        let mut early_params = SearchParams::new(50);
+        early_params.early_termination(0.01, 5);
+        let full_recall = recall(&full_params);
+        let early_recall = recall(&early_params);
+        assert!(
+            early_recall >= full_recall - 0.1,
+            "recall with early termination {} vs {}",
+            early_recall,
+            full_recall
+        );
+
+        // Early termination only cuts the same traversal short.
+        for i in 0..queries.num_rows() {
+            let query = queries.row(i).unwrap();
+            let full = greedy_search(&graph, medoid, query, k, 50).unwrap();
+            let early = greedy_search_with_early_termination(
+                &graph,
+                medoid,
+                query,
+                k,
+                50,
+                early_params.early_termination,
+            )
+            .unwrap();
+            assert!(early.visited.len() <= full.visited.len());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_skip_converged() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let dataset = create_dataset(uri, 200, 16).await;
+
+        let k = 5;
+        let mut params = DiskANNParams::default();
+        params.r(10).l(20);
+        let (graph, report) = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+        let medoid = report.medoid;
+        let queries = graph.data.sample(20);
+        let ground_truth = brute_force_knn(dataset.as_ref(), "vector", &queries, k, MetricType::L2)
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| r.iter().map(|(row_id, _)| *row_id).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let full_recall = evaluate_recall(&graph, medoid, &queries, &ground_truth, k, 20).unwrap();
+
+        params.skip_converged(true);
+        let (graph, report) = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+        let medoid = report.medoid;
+        let recall = evaluate_recall(&graph, medoid, &queries, &ground_truth, k, 20).unwrap();
+        assert!(
+            recall >= full_recall - 0.1,
+            "recall with skipping {} vs full passes {}",
+            recall,
+            full_recall
+        );
+        for node in graph.nodes.iter() {
+            assert!(node.neighbors.len() <= params.r);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_alpha_schedule() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let dataset = create_dataset(uri, 200, 16).await;
+
+        let k = 5;
+        let mut params = DiskANNParams::default();
+        params.r(10).l(20);
+        let (graph, report) = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+        let queries = graph.data.sample(20);
+        let ground_truth = brute_force_knn(dataset.as_ref(), "vector", &queries, k, MetricType::L2)
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| r.iter().map(|(row_id, _)| *row_id).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let default_recall =
+            evaluate_recall(&graph, report.medoid, &queries, &ground_truth, k, 20).unwrap();
+
+        params.alpha_schedule(vec![1.0, 1.1, 1.2]);
+        let (graph, report) = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+        assert_eq!(report.pass_durations.len(), 3);
+        let recall =
+            evaluate_recall(&graph, report.medoid, &queries, &ground_truth, k, 20).unwrap();
+        assert!(
+            recall >= default_recall - 0.1,
+            "recall with schedule {} vs default {}",
+            recall,
+            default_recall
+        );
+
+        params.alpha_schedule(vec![1.0, 0.9]);
+        assert!(build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .is_err());
+        params.alpha_schedule(vec![]);
+        assert!(build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_l_schedule() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let dataset = create_dataset(uri, 200, 16).await;
+
+        let k = 5;
+        let mut params = DiskANNParams::default();
+        params.r(10).l(20);
+        let (graph, report) = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+        let queries = graph.data.sample(20);
+        let ground_truth = brute_force_knn(dataset.as_ref(), "vector", &queries, k, MetricType::L2)
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| r.iter().map(|(row_id, _)| *row_id).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let constant_recall =
+            evaluate_recall(&graph, report.medoid, &queries, &ground_truth, k, 20).unwrap();
+
+        // Explore with a large list first, then refine.
+        params.l_schedule(vec![60, 20]);
+        let (graph, report) = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+        assert_eq!(report.pass_durations.len(), 2);
+        let recall =
+            evaluate_recall(&graph, report.medoid, &queries, &ground_truth, k, 20).unwrap();
+        assert!(
+            recall >= constant_recall - 0.05,
+            "recall with l schedule {} vs constant {}",
+            recall,
+            constant_recall
+        );
+
+        params.l_schedule(vec![60, 5]);
+        let err = params.search_sizes().unwrap_err();
+        assert!(err.to_string().contains(">= r"), "{}", err);
+        params.l_schedule(vec![60, 40, 20]);
+        assert!(build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .is_err());
+        params.alpha_schedule(vec![1.0, 1.1, 1.2]);
+        assert_eq!(params.search_sizes().unwrap(), vec![60, 40, 20]);
+        params.quick(true);
+        assert_eq!(params.search_sizes().unwrap(), vec![60]);
+    }
+
+    #[tokio::test]
+    async fn test_quick_build() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let dataset = create_dataset(uri, 200, 16).await;
+
+        let mut params = DiskANNParams::default();
+        params.r(10).l(20).quick(true);
+        let (graph, report) = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+        assert_eq!(report.pass_durations.len(), 1);
+
+        for i in [0, 42, 199] {
+            let query = graph.data.row(i).unwrap();
+            let results = search(&graph, report.medoid, query, 1, &SearchParams::new(50)).unwrap();
+            assert_eq!(results.results[0].0, graph.vertex(i).row_id);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrency() {
+        let mut params = DiskANNParams::default();
+        assert_eq!(params.concurrency_limit(), num_cpus::get());
+        params.concurrency(0);
+        assert_eq!(params.concurrency_limit(), 1);
+
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let dataset = create_dataset(uri, 100, 8).await;
+        params.r(10).l(20).concurrency(1);
+        let (graph, report) = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+        assert!(graph.nodes.iter().all(|n| n.neighbors.len() <= 10));
+        let query = graph.data.row(42).unwrap();
+        let results = search(&graph, report.medoid, query, 1, &SearchParams::new(50)).unwrap();
+        assert_eq!(results.results[0].0, graph.vertex(42).row_id);
+    }
+
+    #[tokio::test]
+    async fn test_prune_weighted_l2() {
+        let nodes = (0..3).map(|i| RowVertex::new(i, None)).collect::<Vec<_>>();
+        let matrix = MatrixView::new(
+            Arc::new(Float32Array::from(vec![0.0, 0.0, 1.0, 1.0, 2.0, 0.0])),
+            2,
+        );
+        // Vertex 1 is the nearest to vertex 0 and occludes vertex 2, unless the
+        // second dimension weighs more.
+        for (metric_type, expected) in [
+            (MetricType::L2, vec![1]),
+            (MetricType::WeightedL2(Arc::new(vec![1.0, 100.0])), vec![2]),
+        ] {
+            let graph = GraphBuilder::new(&nodes, matrix.clone(), metric_type);
+            let visited = HashSet::from([1, 2]);
+            let neighbors = prune(&graph, 0, visited.clone(), PruneStrategy::Nearest, 1)
+                .await
+                .unwrap();
+            assert_eq!(neighbors, expected);
+            let neighbors = prune(&graph, 0, visited, PruneStrategy::Robust { alpha: 1.0 }, 3)
+                .await
+                .unwrap();
+            assert_eq!(neighbors, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_nearest_prune() {
+        // Vertex i is at distance i^2 from vertex 0.
+        let nodes = (0..10).map(|i| RowVertex::new(i, None)).collect::<Vec<_>>();
+        let matrix = MatrixView::new(
+            Arc::new(Float32Array::from_iter_values((0..10).map(|i| i as f32))),
+            1,
+        );
+        let mut graph = GraphBuilder::new(&nodes, matrix, MetricType::L2);
+        graph.set_neighbors(0, vec![9, 2]);
+
+        let visited = HashSet::from([0, 8, 5, 1, 7, 3]);
+        let neighbors = prune(&graph, 0, visited.clone(), PruneStrategy::Nearest, 3)
+            .await
+            .unwrap();
+        assert_eq!(neighbors, vec![1, 2, 3]);
+
+        let neighbors = prune(&graph, 0, visited, PruneStrategy::Nearest, 20)
+            .await
+            .unwrap();
+        assert_eq!(neighbors, vec![1, 2, 3, 5, 7, 8, 9]);
+
+        // RobustPrune starts from the nearest vertex, which occludes all the others
+        // on the line.
+        let visited = (1..10).collect::<HashSet<_>>();
+        let neighbors = prune(&graph, 0, visited, PruneStrategy::Robust { alpha: 1.0 }, 3)
+            .await
+            .unwrap();
+        assert_eq!(neighbors, vec![1]);
+
+        let dataset_dir = tempfile::tempdir().unwrap();
+        let uri = dataset_dir.path().to_str().unwrap();
+        let dataset = create_dataset(uri, 100, 8).await;
+        let mut params = DiskANNParams::default();
+        params.r(10).l(20).prune_nearest(true);
+        let (graph, _) = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+        assert!(graph.nodes.iter().all(|n| n.neighbors.len() <= 10));
+    }
+
+    #[tokio::test]
+    async fn test_resume_from_checkpoint() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let dataset = create_dataset(uri, 200, 16).await;
+        let columns = [("vector", 1.0)];
+
+        let checkpoint_path = dataset.indices_dir().child("diskann.ckpt");
+        let mut params = DiskANNParams::default();
+        params
+            .r(10)
+            .l(20)
+            .checkpoint_path(checkpoint_path.clone())
+            .checkpoint_interval(50);
+        let (graph, report) = build_graph(dataset.as_ref(), &columns, &params)
+            .await
+            .unwrap();
+
+        // The last checkpoint is taken once all the passes are done.
+        let mut checkpoint = Checkpoint::read(dataset.object_store(), &checkpoint_path)
+            .await
+            .unwrap();
+        assert_eq!(checkpoint.progress.pass, 2);
+        assert_eq!(checkpoint.progress.position, 0);
+        assert_eq!(checkpoint.progress.medoid, report.medoid);
+        assert_eq!(checkpoint, Checkpoint::new(&checkpoint.progress, &graph));
+
+        // Resuming a finished build does not change the graph.
+        let (resumed, report) = resume_graph(dataset.as_ref(), &columns, &params, &checkpoint_path)
+            .await
+            .unwrap();
+        assert!(report.pass_durations.is_empty());
+        assert_eq!(checkpoint, Checkpoint::new(&checkpoint.progress, &resumed));
+
+        // Rewind the checkpoint to the middle of the second pass, as if the build crashed.
+        checkpoint.progress.pass = 1;
+        checkpoint.progress.position = 100;
+        checkpoint
+            .write(dataset.object_store(), &checkpoint_path)
+            .await
+            .unwrap();
+        let (resumed, report) = resume_graph(dataset.as_ref(), &columns, &params, &checkpoint_path)
+            .await
+            .unwrap();
+        assert_eq!(report.pass_durations.len(), 1);
+        assert_eq!(report.medoid, checkpoint.progress.medoid);
+        assert_eq!(resumed.len(), graph.len());
+        for node in resumed.nodes.iter() {
+            assert!(node.neighbors.len() <= params.r);
+        }
+        let checkpoint = Checkpoint::read(dataset.object_store(), &checkpoint_path)
+            .await
+            .unwrap();
+        assert_eq!(checkpoint.progress.pass, 2);
+        assert_eq!(checkpoint.progress.position, 0);
+
+        // The checkpoint must match the vertices of the dataset.
+        let mut checkpoint = checkpoint;
+        checkpoint.row_ids[0] += 1000;
+        checkpoint
+            .write(dataset.object_store(), &checkpoint_path)
+            .await
+            .unwrap();
+        assert!(
+            resume_graph(dataset.as_ref(), &columns, &params, &checkpoint_path)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_build_report() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let dataset = create_dataset(uri, 200, 16).await;
+
+        let mut params = DiskANNParams::default();
+        params.r(10).l(20);
+        let (graph, report) = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+        assert_eq!(report.num_vertices, dataset.count_rows().await.unwrap());
+        assert_eq!(report.num_vertices, graph.len());
+        assert!(report.medoid < graph.len());
+        assert_eq!(report.pass_durations.len(), 2);
+        assert_eq!(report.params.r, 10);
+        assert!(report.min_degree as f32 <= report.avg_degree);
+        assert!(report.avg_degree <= report.max_degree as f32);
+        assert!(report.max_degree <= params.r);
+        assert!((0.0..=1.0).contains(&report.under_degree_fraction));
+    }
+
+    #[tokio::test]
+    async fn test_build_streaming() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let dataset = create_dataset(uri, 200, 16).await;
+
+        let k = 5;
+        let mut params = DiskANNParams::default();
+        params.r(10).l(20);
+        let (graph, report) = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+        let queries = graph.data.sample(10);
+        let ground_truth = brute_force_knn(dataset.as_ref(), "vector", &queries, k, MetricType::L2)
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| r.iter().map(|(row_id, _)| *row_id).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let batch_recall =
+            evaluate_recall(&graph, report.medoid, &queries, &ground_truth, k, 20).unwrap();
+
+        let (streamed, medoid) = build_graph_streaming(dataset.as_ref(), "vector", &params)
+            .await
+            .unwrap();
+        assert_eq!(streamed.len(), 200);
+        let mut row_ids = streamed
+            .nodes
+            .iter()
+            .map(|node| node.vertex.row_id)
+            .collect::<Vec<_>>();
+        row_ids.sort();
+        let mut expected = graph
+            .nodes
+            .iter()
+            .map(|node| node.vertex.row_id)
+            .collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(row_ids, expected);
+        assert!(streamed
+            .nodes
+            .iter()
+            .all(|node| node.neighbors.len() <= params.r));
+
+        let recall = evaluate_recall(&streamed, medoid, &queries, &ground_truth, k, 20).unwrap();
+        assert!(
+            recall >= batch_recall - 0.1,
+            "streaming recall: {}, batch recall: {}",
+            recall,
+            batch_recall
+        );
+    }
+
+    #[tokio::test]
+    async fn test_under_degree_fraction() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let dataset = create_dataset(uri, 200, 16).await;
+
+        let mut params = DiskANNParams::default();
+        params.r(10).l(20).seed(42);
+        let (_, report) = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+
+        // A single pass pruning with a low alpha keeps few neighbors.
+        params.alpha_schedule(vec![0.5]);
+        let (graph, sparse) = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+        assert!(sparse.under_degree_fraction > report.under_degree_fraction);
+        let expected = graph
+            .nodes
+            .iter()
+            .filter(|node| node.neighbors.len() < 5)
+            .count() as f32
+            / graph.len() as f32;
+        assert_eq!(sparse.under_degree_fraction, expected);
+    }
+
+    #[tokio::test]
+    async fn test_init() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let dataset = create_dataset(uri, 200, 64).await;
+
+        let rng = rand::thread_rng();
+        let (graph, _) = init_graph(
+            dataset.as_ref(),
+            &[("vector", 1.0)],
+            10,
+            MetricType::L2,
+            false,
+            None,
+            InitStrategy::Bidirectional,
+            rng,
+        )
+        .await
+        .unwrap();
+
+        for (id, node) in graph.nodes.iter().enumerate() {
+            // Statistically， each node should have 10 neighbors.
+            assert!(!node.neighbors.is_empty());
+            assert_eq!(node.vertex.row_id as usize, id);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_init_directed() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let dataset = create_dataset(uri, 200, 16).await;
+
+        let num_edges = |graph: &GraphBuilder<RowVertex>| {
+            graph.nodes.iter().map(|n| n.neighbors.len()).sum::<usize>()
+        };
+        let mut edges = vec![];
+        for strategy in [InitStrategy::Bidirectional, InitStrategy::Directed] {
+            let (graph, _) = init_graph(
+                dataset.as_ref(),
+                &[("vector", 1.0)],
+                10,
+                MetricType::L2,
+                false,
+                None,
+                strategy,
+                rand::thread_rng(),
+            )
+            .await
+            .unwrap();
+            edges.push(num_edges(&graph));
+            if strategy == InitStrategy::Directed {
+                assert!(graph.nodes.iter().all(|n| n.neighbors.len() == 10));
+            }
+        }
+        // The later vertices reuse the reverse edges of the earlier ones, so the
+        // bidirectional graph has about 1.26x the edges, not twice.
+        assert_eq!(edges[1], 200 * 10);
+        assert!(
+            edges[0] as f32 > edges[1] as f32 * 1.1,
+            "edges: {:?}",
+            edges
+        );
+
+        let mut params = DiskANNParams::default();
+        params.r(10).l(20).init_strategy(InitStrategy::Directed);
+        let (graph, report) = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+        assert!(graph.nodes.iter().all(|n| n.neighbors.len() <= 10));
+        let mut found = 0;
+        for i in 0..10 {
+            let query = graph.data.row(i).unwrap();
+            let results = search(&graph, report.medoid, query, 1, &SearchParams::new(20)).unwrap();
+            if results.results[0].0 == graph.vertex(i).row_id {
+                found += 1;
+            }
+        }
+        assert!(found >= 9, "found {} of 10 vectors", found);
+    }
+
+    #[tokio::test]
+    async fn test_precomputed_medoid() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let dataset = create_dataset(uri, 100, 8).await;
+
+        // Sequential and seeded, to build the same graph twice.
+        let mut params = DiskANNParams::default();
+        params.r(10).l(20).seed(42).concurrency(1);
+        let (graph, report) = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+
+        params.precomputed_medoid(report.medoid);
+        let (reused, reused_report) = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+        assert_eq!(reused_report.medoid, report.medoid);
+        let sorted_neighbors = |graph: &GraphBuilder<RowVertex>| {
+            graph
+                .nodes
+                .iter()
+                .map(|n| {
+                    let mut neighbors = n.neighbors.to_vec();
+                    neighbors.sort_unstable();
+                    neighbors
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(sorted_neighbors(&reused), sorted_neighbors(&graph));
+
+        params.precomputed_medoid(100);
+        assert!(build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cosine_build() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let dataset = create_dataset(uri, 200, 16).await;
+
+        let mut params = DiskANNParams::default();
+        params.r(10).l(20).metric_type(MetricType::Cosine);
+        let (graph, report) = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+        assert_eq!(
+            prune_distance_func(&graph.metric_type())(
+                graph.data.row(3).unwrap(),
+                graph.data.row(42).unwrap()
+            ),
+            cosine_distance_scalar(graph.data.row(3).unwrap(), graph.data.row(42).unwrap())
+        );
+
+        let k = 5;
+        let queries = graph.data.sample(20);
+        let ground_truth =
+            brute_force_knn(dataset.as_ref(), "vector", &queries, k, MetricType::Cosine)
+                .await
+                .unwrap()
+                .iter()
+                .map(|r| r.iter().map(|(row_id, _)| *row_id).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+        let recall =
+            evaluate_recall(&graph, report.medoid, &queries, &ground_truth, k, 50).unwrap();
+        assert!(recall >= 0.9, "recall against the flat index {}", recall);
+    }
+
+    #[tokio::test]
+    async fn test_require_normalized() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        // Random values in [0, 1), far from normalized in 16 dimensions.
+        let dataset = create_dataset(uri, 100, 16).await;
+
+        let mut params = DiskANNParams::default();
+        params
+            .r(10)
+            .l(20)
+            .metric_type(MetricType::Cosine)
+            .require_normalized(true);
+        let err = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("normalized"), "{}", err);
+
+        // Only checked for the cosine metric.
+        params.metric_type(MetricType::L2);
+        assert!(build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .is_ok());
+
+        let normalized = MatrixView::new(
+            Arc::new(Float32Array::from_iter_values(
+                (0..100).flat_map(|i| [(i as f32).cos(), (i as f32).sin()]),
+            )),
+            2,
+        );
+        assert!(check_normalized(&normalized).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_normalize() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let dataset = create_dataset(uri, 100, 16).await;
+        let load_original = || async {
+            load_graph(dataset.as_ref(), &[("vector", 1.0)], MetricType::L2, false)
+                .await
+                .unwrap()
+                .data
+        };
+        let original = load_original().await;
+
+        let mut params = DiskANNParams::default();
+        params.r(10).l(20).normalize(true);
+        let (graph, _) = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+        assert!(graph.is_normalized());
+        for id in 0..graph.len() {
+            assert_relative_eq!(norm_l2(graph.data.row(id).unwrap()), 1.0, epsilon = 1e-5);
+        }
+
+        // The dataset keeps the original vectors, which the graph can restore.
+        let stored = load_original().await;
+        assert_eq!(stored.data().values(), original.data().values());
+        let restored = graph.original_data();
+        for (a, b) in restored
+            .data()
+            .values()
+            .iter()
+            .zip(original.data().values())
+        {
+            assert_relative_eq!(a, b, epsilon = 1e-5);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_max_degree() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let dataset = create_dataset(uri, 200, 16).await;
+
+        let mut params = DiskANNParams::default();
+        params.r(10).l(20);
+        for max_degree in [None, Some(16)] {
+            if let Some(max_degree) = max_degree {
+                params.max_degree(max_degree);
+            }
+            let (graph, _) = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+                .await
+                .unwrap();
+            let max_degree = max_degree.unwrap_or(10);
+            for node in graph.nodes.iter() {
+                assert!(node.neighbors.len() <= max_degree);
+                assert!(node.neighbors.capacity() >= max_degree);
+            }
+        }
+
+        params.max_degree(5);
+        assert!(build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_time_budget() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let dataset = create_dataset(uri, 100, 8).await;
+
+        let mut params = DiskANNParams::default();
+        params.r(10).l(20);
+        let (_, report) = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+        assert_eq!(report.num_processed, 100);
+
+        // The budget is spent before the first pass starts.
+        params.time_budget(Duration::ZERO);
+        let (graph, report) = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+        assert_eq!(report.num_processed, 0);
+        assert_eq!(report.pass_durations.len(), 1);
+        assert_eq!(graph.len(), 100);
+        assert!(report.min_degree > 0);
+
+        // Rough, but searchable from the medoid.
+        let query = graph.data.row(7).unwrap();
+        let state = greedy_search(&graph, report.medoid, query, 5, 20).unwrap();
+        assert_eq!(state.sorted_candidates().count(), 20);
+    }
+
+    #[tokio::test]
+    async fn test_rebalance() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let dataset = create_dataset(uri, 100, 8).await;
+
+        let mut params = DiskANNParams::default();
+        params.r(10).l(20);
+        let (mut graph, report) = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+        assert_eq!(rebalance(&mut graph, &params).await.unwrap(), 0);
+
+        // Skew the degrees, as inserts linking back to a few hubs would.
+        for hub in [3, 42, 77] {
+            for i in 0..100 {
+                if i != hub && !graph.neighbors(hub).unwrap().contains(&(i as u32)) {
+                    graph.add_neighbor(hub, i);
+                }
+            }
+        }
+        assert_eq!(rebalance(&mut graph, &params).await.unwrap(), 3);
+        assert!(graph.nodes.iter().all(|n| n.neighbors.len() <= 10));
+        assert!(graph.nodes.iter().all(|n| !n.neighbors.is_empty()));
+
+        let query = graph.data.row(42).unwrap();
+        let results = search(&graph, report.medoid, query, 1, &SearchParams::new(20)).unwrap();
+        assert_eq!(results.results[0].0, graph.vertex(42).row_id);
+    }
+
+    #[tokio::test]
+    async fn test_repair_after_delete() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let dataset = create_dataset(uri, 100, 8).await;
+
+        let mut params = DiskANNParams::default();
+        params.r(10).l(20);
+        let (mut graph, report) = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+        assert_eq!(graph.reachable_count(report.medoid), 100);
+
+        let deleted_ids = (0..100)
+            .step_by(7)
+            .filter(|&id| id != report.medoid)
+            .collect::<HashSet<_>>();
+        let deleted = deleted_ids
+            .iter()
+            .map(|&id| graph.vertex(id).row_id)
+            .collect::<HashSet<_>>();
+        let row_neighbors = |graph: &GraphBuilder<RowVertex>, id: usize| {
+            graph
+                .neighbors(id)
+                .unwrap()
+                .iter()
+                .map(|&n| graph.vertex(n as usize).row_id)
+                .collect::<Vec<_>>()
+        };
+        let unaffected = (0..100)
+            .filter(|id| !deleted_ids.contains(id))
+            .filter(|&id| {
+                graph
+                    .neighbors(id)
+                    .unwrap()
+                    .iter()
+                    .all(|n| !deleted_ids.contains(&(*n as usize)))
+            })
+            .map(|id| (graph.vertex(id).row_id, row_neighbors(&graph, id)))
+            .collect::<Vec<_>>();
+        assert!(!unaffected.is_empty());
+
+        let mut deleted_row_ids = deleted.iter().copied().collect::<Vec<_>>();
+        // Not in the graph.
+        deleted_row_ids.push(u64::MAX);
+        let (id_map, medoid) =
+            repair_after_delete(&mut graph, report.medoid, &deleted_row_ids, &params)
+                .await
+                .unwrap();
+        assert_eq!(graph.len(), 100 - deleted_ids.len());
+        assert_eq!(id_map.iter().flatten().count(), graph.len());
+
+        // The deleted vertices are unreferenced, the others keep their neighbors.
+        for id in 0..graph.len() {
+            let neighbors = row_neighbors(&graph, id);
+            assert!(!neighbors.is_empty());
+            assert!(neighbors.len() <= 10);
+            assert!(neighbors.iter().all(|row_id| !deleted.contains(row_id)));
+        }
+        for (row_id, neighbors) in unaffected {
+            let id = graph.vertex_for_row(row_id).unwrap();
+            assert_eq!(row_neighbors(&graph, id), neighbors);
+        }
+
+        assert_eq!(id_map[report.medoid], Some(medoid as u32));
+        assert_eq!(graph.reachable_count(medoid), graph.len());
+        let query = graph.data.row(5).unwrap();
+        let results = search(&graph, medoid, query, 1, &SearchParams::new(20)).unwrap();
+        assert_eq!(results.results[0].0, graph.vertex(5).row_id);
+    }
+
+    #[tokio::test]
+    async fn test_repair_after_deleting_medoid() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let dataset = create_dataset(uri, 100, 8).await;
+
+        let mut params = DiskANNParams::default();
+        params.r(10).l(20);
+        let (mut graph, report) = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+        let deleted = [report.medoid, (report.medoid + 1) % 100]
+            .iter()
+            .map(|&id| graph.vertex(id).row_id)
+            .collect::<Vec<_>>();
+
+        let (id_map, medoid) = repair_after_delete(&mut graph, report.medoid, &deleted, &params)
+            .await
+            .unwrap();
+        assert_eq!(graph.len(), 98);
+        assert!(id_map[report.medoid].is_none());
+        // The medoid of the remaining vectors.
+        assert_eq!(
+            medoid,
+            find_medoid(&graph.data, MetricType::L2).await.unwrap()
+        );
+        assert!(!deleted.contains(&graph.vertex(medoid).row_id));
+        assert_eq!(graph.reachable_count(medoid), graph.len());
+        let query = graph.data.row(5).unwrap();
+        let results = search(&graph, medoid, query, 1, &SearchParams::new(20)).unwrap();
+        assert_eq!(results.results[0].0, graph.vertex(5).row_id);
+
+        assert!(repair_after_delete(&mut graph, 98, &[], &params)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_find_medoid_ties() {
+        // All the vectors are at distance 1 from the centroid (0, 0).
+        let vectors = MatrixView::new(
+            Arc::new(Float32Array::from(vec![
+                0.0, 1.0, 1.0, 0.0, 0.0, -1.0, -1.0, 0.0,
+            ])),
+            2,
+        );
+        assert_eq!(find_medoid(&vectors, MetricType::L2).await.unwrap(), 0);
+
+        // Duplicates of the closest vector.
+        let vectors = MatrixView::new(
+            Arc::new(Float32Array::from(vec![5.0, 1.0, 1.0, 1.0, 0.0, 1.0, 1.0])),
+            1,
+        );
+        assert_eq!(find_medoid(&vectors, MetricType::L2).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_load_graph_multiple_batches() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let dataset = create_dataset(uri, 100, 8).await;
+
+        let batches = dataset
+            .scan()
+            .with_row_id()
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert!(batches.len() > 1);
+
+        let graph = load_graph(dataset.as_ref(), &[("vector", 1.0)], MetricType::L2, false)
+            .await
+            .unwrap();
+        assert_eq!(graph.len(), 100);
+        let mut id = 0;
+        for batch in batches.iter() {
+            let row_ids = as_primitive_array::<UInt64Type>(batch.column_by_name(ROW_ID).unwrap());
+            let vectors = as_fixed_size_list_array(batch.column_by_name("vector").unwrap());
+            for (i, row_id) in row_ids.values().iter().enumerate() {
+                assert_eq!(graph.vertex(id).row_id, *row_id);
+                assert_eq!(
+                    graph.data.row(id).unwrap(),
+                    &as_primitive_array::<Float32Type>(vectors.value(i).as_ref()).values()[..]
+                );
+                id += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn test_integer_vector_precision() {
+        let boundary = 1 << 24;
+        let values = Int32Array::from(vec![0, boundary, -boundary, boundary + 1]);
+        let fsl = FixedSizeListArray::try_new(&values, 1).unwrap();
+        let matrix = fixed_size_list_to_matrix(&fsl, &DataType::Int32, 1).unwrap();
+        let native = values.values();
+        let exact = |i: usize, j: usize| ((native[i] as i64 - native[j] as i64) as f64).powi(2);
+
+        // Up to 2^24 in magnitude, the distances are exact.
+        for (i, j) in [(0, 1), (0, 2), (1, 2)] {
+            assert_eq!(
+                distance(&matrix, &l2_distance_scalar, i, j).unwrap() as f64,
+                exact(i, j)
+            );
+        }
+
+        // Beyond, the coordinates are rounded, and so are the distances.
+        assert_eq!(matrix.row(3).unwrap()[0], boundary as f32);
+        assert_ne!(
+            distance(&matrix, &l2_distance_scalar, 0, 3).unwrap() as f64,
+            exact(0, 3)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_int32_vector_column() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+
+        let dim = 8;
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "vector",
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Int32, true)), dim),
+            true,
+        )]));
+        let mut rng = rand::thread_rng();
+        let values = Int32Array::from_iter_values((0..100 * dim).map(|_| rng.gen_range(0..1000)));
+        let batches = RecordBatchBuffer::new(vec![RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(FixedSizeListArray::try_new(&values, dim).unwrap())],
+        )
+        .unwrap()]);
+        let mut batches: Box<dyn RecordBatchReader> = Box::new(batches);
+        Dataset::write(&mut batches, uri, None).await.unwrap();
+        let dataset = Dataset::open(uri).await.unwrap();
+
+        let mut params = DiskANNParams::default();
+        params.r(10).l(20);
+        let (graph, report) = build_graph(&dataset, &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+        assert_eq!(graph.len(), 100);
+
+        // The coordinates are below 2^24, so the distances are exact.
+        let native = |i: usize| &values.values()[i * dim as usize..(i + 1) * dim as usize];
+        let exact = native(0)
+            .iter()
+            .zip(native(1))
+            .map(|(a, b)| (a - b).pow(2))
+            .sum::<i32>();
+        assert_eq!(
+            distance(&graph.data, &l2_distance_scalar, 0, 1).unwrap(),
+            exact as f32
+        );
+
+        let mut found = 0;
+        for i in 0..10 {
+            let query = graph.data.row(i).unwrap();
+            let results = search(&graph, report.medoid, query, 1, &SearchParams::new(20)).unwrap();
+            if results.results[0].0 == graph.vertex(i).row_id {
+                found += 1;
+            }
+        }
+        assert!(found >= 9, "found {} of 10 vectors", found);
+    }
+
+    #[tokio::test]
+    async fn test_float16_vector_column() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+
+        let dim = 8;
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "vector",
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float16, true)), dim),
+            true,
+        )]));
+        let mut rng = rand::thread_rng();
+        let values =
+            Float16Array::from_iter_values((0..100 * dim).map(|_| f16::from_f32(rng.gen::<f32>())));
+        let batches = RecordBatchBuffer::new(vec![RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(FixedSizeListArray::try_new(&values, dim).unwrap())],
+        )
+        .unwrap()]);
+        let mut batches: Box<dyn RecordBatchReader> = Box::new(batches);
+        Dataset::write(&mut batches, uri, None).await.unwrap();
+        let dataset = Dataset::open(uri).await.unwrap();
+
+        let mut params = DiskANNParams::default();
+        params.r(10).l(20);
+        let (graph, report) = build_graph(&dataset, &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+        assert_eq!(graph.len(), 100);
+
+        // The f16 values are widened exactly, so the distances match the f16 kernel.
+        let native = |i: usize| &values.values()[i * dim as usize..(i + 1) * dim as usize];
+        assert_eq!(graph.data.row(3).unwrap()[5], native(3)[5].to_f32());
+        assert_relative_eq!(
+            distance(&graph.data, &l2_distance_scalar, 0, 1).unwrap(),
+            native(0).l2(native(1)),
+            max_relative = 1e-6
+        );
+
+        let query = graph.data.row(7).unwrap();
+        let results = search(&graph, report.medoid, query, 1, &SearchParams::new(20)).unwrap();
+        assert_eq!(results.results[0].0, graph.vertex(7).row_id);
+    }
+
+    #[tokio::test]
+    async fn test_float64_vector_column() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+
+        let dim = 8;
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "vector",
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float64, true)), dim),
+            true,
+        )]));
+        let mut rng = rand::thread_rng();
+        let values = Float64Array::from_iter_values((0..100 * dim).map(|_| rng.gen::<f64>()));
+        let batches = RecordBatchBuffer::new(vec![RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(FixedSizeListArray::try_new(&values, dim).unwrap())],
+        )
+        .unwrap()]);
+        let mut batches: Box<dyn RecordBatchReader> = Box::new(batches);
+        Dataset::write(&mut batches, uri, None).await.unwrap();
+        let dataset = Dataset::open(uri).await.unwrap();
+
+        let mut params = DiskANNParams::default();
+        params.r(10).l(20).exact_rerank(true);
+        let index = DiskANNGraph::build(&dataset, "vector", &params)
+            .await
+            .unwrap();
+
+        let native = |i: usize| &values.values()[i * dim as usize..(i + 1) * dim as usize];
+        let downcast = |i: usize| native(i).iter().map(|&v| v as f32).collect::<Vec<_>>();
+        for i in 0..10 {
+            let query = downcast(i);
+            let results = index
+                .search(&query, 5, &SearchParams::new(20))
+                .await
+                .unwrap();
+            assert_eq!(results.len(), 5);
+            assert_eq!(results[0].0, i as u64);
+            assert!(results.windows(2).all(|w| w[0].1 <= w[1].1));
+            for (row_id, dist) in results {
+                // Same distances as over the vectors downcast to f32, up to their precision.
+                assert_relative_eq!(dist, query.l2(&downcast(row_id as usize)), epsilon = 1e-4);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_init_from_sample() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let dataset = create_dataset(uri, 200, 16).await;
+
+        let (graph, sample) = init_graph(
+            dataset.as_ref(),
+            &[("vector", 1.0)],
+            10,
+            MetricType::L2,
+            false,
+            Some(&SampleConfig { size: 30 }),
+            InitStrategy::Bidirectional,
+            rand::thread_rng(),
+        )
+        .await
+        .unwrap();
+        let sample = sample.unwrap();
+        assert_eq!(sample.ids.len(), 30);
+        assert_eq!(sample.vectors.num_rows(), 30);
+        for (i, &id) in sample.ids.iter().enumerate() {
+            assert_eq!(sample.vectors.row(i).unwrap(), graph.data.row(id).unwrap());
+        }
+        // All the initial neighbors are sampled vertices.
+        let sampled = sample
+            .ids
+            .iter()
+            .map(|&id| id as u32)
+            .collect::<HashSet<_>>();
+        for node in graph.nodes.iter() {
+            assert_eq!(node.neighbors.len(), 10);
+            assert!(node.neighbors.iter().all(|n| sampled.contains(n)));
+        }
+
+        let k = 5;
+        let mut params = DiskANNParams::default();
+        params.r(10).l(20).sample(30);
+        let (graph, report) = build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .unwrap();
+        let queries = graph.data.sample(10);
+        let ground_truth = brute_force_knn(dataset.as_ref(), "vector", &queries, k, MetricType::L2)
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| r.iter().map(|(row_id, _)| *row_id).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let recall =
+            evaluate_recall(&graph, report.medoid, &queries, &ground_truth, k, 50).unwrap();
+        assert!(recall >= 0.5, "recall: {}", recall);
+
+        params.sample(0);
+        assert!(build_graph(dataset.as_ref(), &[("vector", 1.0)], &params)
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_dictionary_vector_column() {
+        let dim = 4;
+        let values =
+            FixedSizeListArray::try_new(&generate_random_array(3 * dim), dim as i32).unwrap();
+        let keys = Int32Array::from(vec![Some(2), Some(0), None, Some(2), Some(1), Some(0)]);
+        let dict = DictionaryArray::<Int32Type>::try_new(&keys, &values).unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("vector", dict.data_type().clone(), true),
+            Field::new(ROW_ID, DataType::UInt64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(dict),
+                Arc::new(UInt64Array::from_iter_values(0..6)),
+            ],
+        )
+        .unwrap();
+
+        let batch = filter_null_vectors(&batch, &["vector"]).unwrap();
+        assert_eq!(batch.num_rows(), 5);
+        let matrix = concat_vector_columns(&batch, &[("vector", 1.0)]).unwrap();
+        assert_eq!(matrix.num_rows(), 5);
+        assert_eq!(matrix.num_columns(), dim);
+        let value = |key: usize| {
+            as_primitive_array::<Float32Type>(values.value(key).as_ref())
+                .values()
+                .to_vec()
+        };
+        for (row, key) in [2, 0, 2, 1, 0].into_iter().enumerate() {
+            assert_eq!(matrix.row(row).unwrap(), value(key).as_slice());
+        }
+    }
+
+    #[test]
+    fn test_vector_count_mismatch() {
+        let dim = 4;
+        let values =
+            FixedSizeListArray::try_new(&generate_random_array(3 * dim), dim as i32).unwrap();
+        // The sliced array still holds the values of all 3 vectors.
+        let sliced = values.slice(1, 2);
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("vector", values.data_type().clone(), true),
+            Field::new(ROW_ID, DataType::UInt64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![sliced, Arc::new(UInt64Array::from_iter_values(0..2))],
+        )
+        .unwrap();
+
+        let matrix = concat_vector_columns(&batch, &[("vector", 1.0)]).unwrap();
+        assert!(matches!(
+            check_vector_count(&matrix, batch.num_rows()),
+            Err(Error::Index(_))
+        ));
+        assert!(check_vector_count(&matrix, 3).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_init_multiple_columns() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+
+        let vector_field = |name: &str, dim: i32| {
+            Field::new(
+                name,
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), dim),
+                true,
+            )
+        };
+        let schema = Arc::new(ArrowSchema::new(vec![
+            vector_field("title", 8),
+            vector_field("body", 16),
+        ]));
+        let title = generate_random_array(100 * 8);
+        let body = generate_random_array(100 * 16);
+        let batches = RecordBatchBuffer::new(vec![RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(FixedSizeListArray::try_new(&title, 8).unwrap()),
+                Arc::new(FixedSizeListArray::try_new(&body, 16).unwrap()),
+            ],
+        )
+        .unwrap()]);
+        let mut batches: Box<dyn RecordBatchReader> = Box::new(batches);
+        let dataset = Dataset::write(&mut batches, uri, None).await.unwrap();
+
+        let rng = rand::thread_rng();
+        let (graph, _) = init_graph(
+            &dataset,
+            &[("title", 1.0), ("body", 0.5)],
+            10,
+            MetricType::L2,
+            false,
+            None,
+            InitStrategy::Bidirectional,
+            rng,
+        )
+        .await
+        .unwrap();
+        assert_eq!(graph.data.num_columns(), 24);
+
+        for (id, node) in graph.nodes.iter().enumerate() {
+            let row_id = node.vertex.row_id as usize;
+            let row = graph.data.row(id).unwrap();
+            assert_eq!(&row[..8], &title.values()[row_id * 8..(row_id + 1) * 8]);
+            let scaled_body = body.values()[row_id * 16..(row_id + 1) * 16]
+                .iter()
+                .map(|v| v * 0.5)
+                .collect::<Vec<_>>();
+            assert_eq!(&row[8..], scaled_body.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_list_vector_column() {
+        let list = ListArray::from_iter_primitive::<Float32Type, _, _>(vec![
+            Some(vec![Some(1.0), Some(2.0), Some(3.0)]),
+            Some(vec![Some(4.0), Some(5.0), Some(6.0)]),
+        ]);
+        let field = Field::new("vector", list.data_type().clone(), true);
+
+        // The dimension must be declared.
+        let batch = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![field.clone()])),
+            vec![Arc::new(list.clone())],
+        )
+        .unwrap();
+        assert!(concat_vector_columns(&batch, &[("vector", 1.0)]).is_err());
+
+        let with_dim = |dim: &str| {
+            let field = field.clone().with_metadata(HashMap::from([(
+                VECTOR_DIM_METADATA_KEY.to_string(),
+                dim.to_string(),
+            )]));
+            RecordBatch::try_new(
+                Arc::new(ArrowSchema::new(vec![field])),
+                vec![Arc::new(list.clone())],
+            )
+            .unwrap()
+        };
+        let matrix = concat_vector_columns(&with_dim("3"), &[("vector", 1.0)]).unwrap();
+        assert_eq!(matrix.num_rows(), 2);
+        assert_eq!(matrix.num_columns(), 3);
+        assert_eq!(matrix.row(1).unwrap(), &[4.0, 5.0, 6.0]);
+
+        // The actual lengths must match the declared dimension.
+        assert!(concat_vector_columns(&with_dim("4"), &[("vector", 1.0)]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_all_null_vectors() {
+        let list = FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+            vec![None::<Vec<Option<f32>>>; 10],
+            4,
+        );
+        let batch = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![Field::new(
+                "vector",
+                list.data_type().clone(),
+                true,
+            )])),
+            vec![Arc::new(list)],
+        )
+        .unwrap();
+
+        let batch = filter_null_vectors(&batch, &["vector"]).unwrap();
+        assert_eq!(batch.num_rows(), 0);
+        let matrix = concat_vector_columns(&batch, &[("vector", 1.0)]).unwrap();
+        let err = find_medoid(&matrix, MetricType::L2).await.unwrap_err();
+        assert!(err.to_string().contains("no vectors to compute medoid"));
+    }
+
+    /// Build a graph in memory over `vectors`, with the given row ids.
+    async fn build_shard(
+        row_ids: std::ops::Range<u64>,
+        vectors: MatrixView,
+        params: &DiskANNParams,
+    ) -> GraphBuilder<RowVertex> {
+        let nodes = row_ids
+            .map(|row_id| RowVertex::new(row_id, None))
+            .collect::<Vec<_>>();
+        let n = nodes.len();
+        let mut graph = GraphBuilder::new(&nodes, vectors, MetricType::L2);
+        for i in 0..n {
+            for j in 1..=params.r.min(n - 1) {
+                graph.add_neighbor(i, (i + j * 7) % n);
+            }
+        }
+        let medoid = find_medoid(&graph.data, MetricType::L2).await.unwrap();
+        let ids = (0..n).collect::<Vec<_>>();
+        let mut changed = vec![false; n];
+        for strategy in params.prune_strategies().unwrap() {
+            index_once(
+                &mut graph,
+                medoid,
+                strategy,
+                params.l,
+                params,
+                &ids,
+                None,
+                &mut changed,
+                None,
+            )
+            .await
+            .unwrap();
+        }
+        graph
+    }
+
+    #[tokio::test]
+    async fn test_merge_shards() {
+        let dim = 8;
+        let mut params = DiskANNParams::default();
+        params.r(10).l(30);
+        let vectors = MatrixView::random(400, dim);
+        let values = vectors.data();
+        let shard_of = |rows: std::ops::Range<usize>| {
+            let values = values.values()[rows.start * dim..rows.end * dim].to_vec();
+            MatrixView::new(Arc::new(Float32Array::from(values)), dim)
+        };
+        let shards = vec![
+            build_shard(0..200, shard_of(0..200), &params).await,
+            build_shard(1000..1200, shard_of(200..400), &params).await,
+        ];
+
+        let (graph, medoid) = merge_shards(shards, &params).await.unwrap();
+        assert_eq!(graph.len(), 400);
+        for i in 0..200 {
+            assert_eq!(graph.vertex(i).row_id, i as u64);
+            assert_eq!(graph.vertex(200 + i).row_id, 1000 + i as u64);
+        }
+        assert!(graph
+            .nodes
+            .iter()
+            .all(|node| node.neighbors.iter().all(|&n| (n as usize) < 400)));
+
+        // Each vector is found from the global medoid, whichever shard it comes from.
+        let mut found = [0, 0];
+        for i in 0..400 {
+            let results = search(
+                &graph,
+                medoid,
+                vectors.row(i).unwrap(),
+                1,
+                &SearchParams::new(30),
+            )
+            .unwrap();
+            if results.results[0].0 == graph.vertex(i).row_id {
+                found[i / 200] += 1;
+            }
+        }
+        assert!(found[0] >= 190 && found[1] >= 190, "{:?}", found);
+
+        let shards = vec![
+            build_shard(0..10, shard_of(0..10), &params).await,
+            build_shard(5..15, shard_of(10..20), &params).await,
+        ];
+        let err = merge_shards(shards, &params).await.err().unwrap();
+        assert!(err.to_string().contains("more than one shard"), "{}", err);
+
+        // Sequential and seeded, the same shards merge into the same graph.
+        params.seed(42).concurrency(1);
+        let mut merged = vec![];
+        for _ in 0..2 {
+            let shards = vec![
+                build_shard(0..50, shard_of(0..50), &params).await,
+                build_shard(50..100, shard_of(50..100), &params).await,
+            ];
+            let (graph, medoid) = merge_shards(shards, &params).await.unwrap();
+            let neighbors = (0..graph.len())
+                .map(|id| graph.neighbors(id).unwrap().to_vec())
+                .collect::<Vec<_>>();
+            merged.push((medoid, neighbors));
+        }
+        assert_eq!(merged[0], merged[1]);
+    }
+
+    #[tokio::test]
+    async fn test_robust_prune_inline() {
+        let n = 200;
+        let mut params = DiskANNParams::default();
+        params.r(8).l(16);
+        let graph = build_shard(0..n as u64, MatrixView::random(n, 16), &params).await;
+
+        let mut inline_duration = Duration::ZERO;
+        let mut blocking_duration = Duration::ZERO;
+        for id in 0..n {
+            let visited = (0..n).step_by(7).collect::<HashSet<_>>();
+
+            let now = Instant::now();
+            let blocking = robust_prune_with_threshold(&graph, id, visited.clone(), 1.2, 8, 0)
+                .await
+                .unwrap();
+            blocking_duration += now.elapsed();
+
+            let now = Instant::now();
+            let inline = robust_prune_with_threshold(&graph, id, visited, 1.2, 8, usize::MAX)
+                .await
+                .unwrap();
+            inline_duration += now.elapsed();
+
+            assert_eq!(inline, blocking, "vertex {}", id);
+        }
+        assert!(
+            inline_duration < blocking_duration,
+            "inline: {:?}, blocking: {:?}",
+            inline_duration,
+            blocking_duration
+        );
+    }
+
+    #[tokio::test]
+    async fn test_robust_prune_canonical_distances() {
+        let n = 100;
+        let dim = 13;
+        let graph = GraphBuilder::new(
+            &(0..n as u64)
+                .map(|i| RowVertex::new(i, None))
+                .collect::<Vec<_>>(),
+            MatrixView::random(n, dim),
+            MetricType::L2,
+        );
+        // Sum in order, as any CPU does without SIMD.
+        let canonical = |i: usize, j: usize| {
+            let (a, b) = (graph.data.row(i).unwrap(), graph.data.row(j).unwrap());
+            let mut sum = 0.0_f32;
+            for (x, y) in a.iter().zip(b) {
+                sum += (x - y) * (x - y);
+            }
+            sum
+        };
+        assert_eq!(
+            distance(&graph.data, &l2_distance_scalar, 3, 42)
+                .unwrap()
+                .to_bits(),
+            canonical(3, 42).to_bits()
+        );
+
+        // Algorithm 2 over the canonical distances.
+        let (alpha, r) = (1.2, 8);
+        for id in 0..n {
+            let mut visited = (0..n).filter(|&v| v != id).collect::<Vec<_>>();
+            let mut expected = vec![];
+            while !visited.is_empty() && expected.len() < r {
+                visited.sort_by(|&a, &b| canonical(id, a).total_cmp(&canonical(id, b)));
+                let p = visited[0];
+                expected.push(p as u32);
+                visited.retain(|&v| alpha * canonical(p, v) > canonical(id, v));
+            }
+
+            let visited = (0..n).collect::<HashSet<_>>();
+            let pruned = robust_prune(&graph, id, visited, alpha, r).await.unwrap();
+            assert_eq!(pruned, expected, "vertex {}", id);
         }
     }
 }
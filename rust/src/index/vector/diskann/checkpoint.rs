@@ -0,0 +1,231 @@
+// Copyright 2023 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checkpoints of an in-progress DiskANN graph build.
+
+use byteorder::{ByteOrder, LE};
+use object_store::path::Path;
+use tokio::io::AsyncWriteExt;
+
+use super::row_vertex::RowVertex;
+use crate::index::vector::graph::builder::GraphBuilder;
+use crate::io::{object_reader::ObjectReader, ObjectStore};
+use crate::{Error, Result};
+
+const CHECKPOINT_MAGIC: &[u8; 4] = b"LDCK";
+
+/// Progress of the indexing passes of a DiskANN graph build.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct BuildProgress {
+    /// Seed of the random generator shuffling the vertices of each pass.
+    pub seed: u64,
+
+    /// The medoid vertex, the entry point of the searches.
+    pub medoid: usize,
+
+    /// Index of the pass in progress. Equal to the number of passes once done.
+    pub pass: usize,
+
+    /// Number of vertices, in the shuffled order, already processed in this pass.
+    pub position: usize,
+
+    /// Vertices to process in this pass.
+    pub dirty: Vec<bool>,
+
+    /// Vertices whose neighbors have changed so far in this pass.
+    pub changed: Vec<bool>,
+}
+
+impl BuildProgress {
+    pub fn new(seed: u64, medoid: usize, num_vertices: usize) -> Self {
+        Self {
+            seed,
+            medoid,
+            pass: 0,
+            position: 0,
+            dirty: vec![true; num_vertices],
+            changed: vec![false; num_vertices],
+        }
+    }
+}
+
+/// A snapshot of the graph being built, with the progress of the build.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Checkpoint {
+    pub progress: BuildProgress,
+
+    /// Row id of each vertex.
+    pub row_ids: Vec<u64>,
+
+    /// Neighbors of each vertex.
+    pub neighbors: Vec<Vec<u32>>,
+}
+
+impl Checkpoint {
+    pub fn new(progress: &BuildProgress, graph: &GraphBuilder<RowVertex>) -> Self {
+        Self {
+            progress: progress.clone(),
+            row_ids: graph.nodes.iter().map(|n| n.vertex.row_id).collect(),
+            neighbors: graph.nodes.iter().map(|n| n.neighbors.clone()).collect(),
+        }
+    }
+
+    /// Restore the neighbors of the graph from this checkpoint.
+    ///
+    /// The graph must have the same vertices as the one the checkpoint was taken from.
+    pub fn restore(&self, graph: &mut GraphBuilder<RowVertex>) -> Result<()> {
+        let num_vertices = graph.len();
+        let row_ids_match = self.row_ids.len() == num_vertices
+            && (0..num_vertices).all(|i| graph.vertex(i).row_id == self.row_ids[i]);
+        if !row_ids_match {
+            return Err(Error::Index(
+                "DiskANN checkpoint does not match the vertices of the dataset".to_string(),
+            ));
+        }
+        if self
+            .neighbors
+            .iter()
+            .flatten()
+            .any(|&n| n as usize >= num_vertices)
+            || self.progress.medoid >= num_vertices
+            || self.progress.position > num_vertices
+            || self.progress.dirty.len() != num_vertices
+            || self.progress.changed.len() != num_vertices
+        {
+            return Err(Error::Index("Corrupted DiskANN checkpoint".to_string()));
+        }
+        for (id, neighbors) in self.neighbors.iter().enumerate() {
+            graph.set_neighbors(id, neighbors.as_slice());
+        }
+        Ok(())
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let n = self.row_ids.len();
+        let num_edges = self.neighbors.iter().map(|n| n.len()).sum::<usize>();
+        let mut buf = Vec::with_capacity(4 + 8 * 5 + n * (8 + 2 + 4) + num_edges * 4);
+        buf.extend_from_slice(CHECKPOINT_MAGIC);
+        for v in [
+            self.progress.seed,
+            self.progress.medoid as u64,
+            self.progress.pass as u64,
+            self.progress.position as u64,
+            n as u64,
+        ] {
+            buf.extend_from_slice(&v.to_le_bytes());
+        }
+        for row_id in self.row_ids.iter() {
+            buf.extend_from_slice(&row_id.to_le_bytes());
+        }
+        buf.extend(self.progress.dirty.iter().map(|&d| d as u8));
+        buf.extend(self.progress.changed.iter().map(|&c| c as u8));
+        for neighbors in self.neighbors.iter() {
+            buf.extend_from_slice(&(neighbors.len() as u32).to_le_bytes());
+            for neighbor in neighbors {
+                buf.extend_from_slice(&neighbor.to_le_bytes());
+            }
+        }
+        buf
+    }
+
+    fn from_bytes(mut buf: &[u8]) -> Result<Self> {
+        fn take<'a>(buf: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+            if buf.len() < len {
+                return Err(Error::Index("Corrupted DiskANN checkpoint".to_string()));
+            }
+            let (head, tail) = buf.split_at(len);
+            *buf = tail;
+            Ok(head)
+        }
+
+        if take(&mut buf, 4)? != CHECKPOINT_MAGIC {
+            return Err(Error::Index("Not a DiskANN checkpoint".to_string()));
+        }
+        let seed = LE::read_u64(take(&mut buf, 8)?);
+        let medoid = LE::read_u64(take(&mut buf, 8)?) as usize;
+        let pass = LE::read_u64(take(&mut buf, 8)?) as usize;
+        let position = LE::read_u64(take(&mut buf, 8)?) as usize;
+        let n = LE::read_u64(take(&mut buf, 8)?) as usize;
+
+        let row_ids = take(&mut buf, n.saturating_mul(8))?
+            .chunks_exact(8)
+            .map(LE::read_u64)
+            .collect::<Vec<_>>();
+        let dirty = take(&mut buf, n)?.iter().map(|&d| d != 0).collect();
+        let changed = take(&mut buf, n)?.iter().map(|&c| c != 0).collect();
+        let mut neighbors = Vec::with_capacity(n);
+        for _ in 0..n {
+            let len = LE::read_u32(take(&mut buf, 4)?) as usize;
+            neighbors.push(
+                take(&mut buf, len * 4)?
+                    .chunks_exact(4)
+                    .map(LE::read_u32)
+                    .collect::<Vec<_>>(),
+            );
+        }
+
+        Ok(Self {
+            progress: BuildProgress {
+                seed,
+                medoid,
+                pass,
+                position,
+                dirty,
+                changed,
+            },
+            row_ids,
+            neighbors,
+        })
+    }
+
+    /// Write the checkpoint, replacing the previous one at the same path.
+    pub async fn write(&self, object_store: &ObjectStore, path: &Path) -> Result<()> {
+        let mut writer = object_store.create(path).await?;
+        writer.write_all(&self.to_bytes()).await?;
+        writer.shutdown().await
+    }
+
+    pub async fn read(object_store: &ObjectStore, path: &Path) -> Result<Self> {
+        let reader = object_store.open(path).await?;
+        let size = reader.size().await?;
+        let bytes = reader.get_range(0..size).await?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_serde() {
+        let checkpoint = Checkpoint {
+            progress: BuildProgress {
+                seed: 42,
+                medoid: 2,
+                pass: 1,
+                position: 3,
+                dirty: vec![true, false, true, true],
+                changed: vec![false, true, false, false],
+            },
+            row_ids: vec![10, 11, 12, 1 << 32],
+            neighbors: vec![vec![1, 2], vec![], vec![0, 1, 3], vec![2]],
+        };
+        let bytes = checkpoint.to_bytes();
+        assert_eq!(Checkpoint::from_bytes(&bytes).unwrap(), checkpoint);
+
+        assert!(Checkpoint::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Checkpoint::from_bytes(b"not a checkpoint").is_err());
+    }
+}
@@ -0,0 +1,386 @@
+// Copyright 2023 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! DiskANN graph reading its vectors from the dataset on demand.
+
+use std::collections::{HashSet, VecDeque};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use arrow_array::{cast::as_primitive_array, types::Float32Type, Float32Array};
+use arrow_schema::DataType;
+use async_trait::async_trait;
+use lru_time_cache::LruCache;
+use object_store::path::Path;
+use tokio::runtime::{Handle, RuntimeFlavor};
+
+use super::row_vertex::{RowVertex, RowVertexSerDe};
+use super::search::{greedy_search_with_early_termination, SearchParams};
+use crate::arrow::*;
+use crate::dataset::Dataset;
+use crate::datatypes::Schema;
+use crate::index::pb::{self, vector_index_stage::Stage};
+use crate::index::vector::graph::builder::Node;
+use crate::index::vector::graph::{read_graph, Graph, Vertex};
+use crate::index::vector::{read_index_metadata, MetricType, SearchIndex};
+use crate::{Error, Result};
+
+/// A DiskANN index whose vectors are taken from the dataset when a distance needs
+/// them, instead of being held in memory. Only the neighbors stay in memory, so it
+/// can search an index over more vectors than fit in memory.
+///
+/// The most recently used vectors are cached. The reads block the calling thread,
+/// which must be a worker of a multi-thread tokio runtime.
+pub struct LazyVectorGraph {
+    nodes: Vec<Node<RowVertex>>,
+
+    /// The entry point of the searches.
+    medoid: usize,
+
+    dataset: Arc<Dataset>,
+
+    /// Projection of the vector column.
+    projection: Schema,
+
+    dimension: usize,
+
+    metric_type: MetricType,
+
+    distance_func: Arc<dyn Fn(&[f32], &[f32]) -> f32 + Send + Sync>,
+
+    /// LRU cache of the vectors, by vertex id.
+    cache: Mutex<LruCache<u32, Arc<Float32Array>>>,
+
+    /// Capacity of the cache.
+    cache_size: usize,
+}
+
+impl LazyVectorGraph {
+    /// Open the DiskANN index `uuid` of the dataset, as listed by
+    /// [`Dataset::load_indices`]. Only its graph is read, the vectors are read on
+    /// demand and up to `cache_size` of them are cached.
+    pub async fn open(dataset: Arc<Dataset>, uuid: &str, cache_size: usize) -> Result<Self> {
+        let metadata = read_index_metadata(&dataset, uuid).await?;
+        let [pb::VectorIndexStage {
+            stage: Some(Stage::Diskann(diskann)),
+        }] = metadata.index.stages.as_slice()
+        else {
+            return Err(Error::Index(format!(
+                "Index {} is not a DiskANN index",
+                uuid
+            )));
+        };
+        let medoid = *diskann
+            .entries
+            .first()
+            .ok_or_else(|| Error::Index("DiskANN index has no entry point".to_string()))?
+            as usize;
+
+        let dimension = metadata.index.dimension as usize;
+        let projection = dataset.schema().project(&[metadata.column.as_str()])?;
+        match projection.fields[0].data_type() {
+            DataType::FixedSizeList(field, dim)
+                if field.data_type() == &DataType::Float32 && *dim as usize == dimension => {}
+            dt => {
+                return Err(Error::Index(format!(
+                    "Vector column {} must be a FixedSizeList of Float32 of dimension {}, got: {}",
+                    metadata.column, dimension, dt
+                )))
+            }
+        }
+
+        let (vertices, neighbors) = read_graph(
+            dataset.object_store(),
+            &Path::from(diskann.filename.as_str()),
+            &RowVertexSerDe {},
+        )
+        .await?;
+        if medoid >= vertices.len() {
+            return Err(Error::Index(format!(
+                "DiskANN entry point {} is out of range: {} vertices",
+                medoid,
+                vertices.len()
+            )));
+        }
+        let nodes = vertices
+            .into_iter()
+            .zip(neighbors)
+            .map(|(vertex, neighbors)| Node { vertex, neighbors })
+            .collect();
+        Ok(Self {
+            nodes,
+            medoid,
+            dataset,
+            projection,
+            dimension,
+            distance_func: metadata.metric_type.func(),
+            metric_type: metadata.metric_type,
+            cache: Mutex::new(LruCache::with_capacity(cache_size)),
+            cache_size,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Load the vectors of the vertices up to `hops` edges away from the medoid, the
+    /// entry point of the searches, into the cache. The first searches then do not
+    /// wait for the dataset reads of the vectors they all go through.
+    ///
+    /// The vertices are loaded by increasing number of hops, up to the cache size,
+    /// in one read of the vector column of their rows. Only the vectors are loaded:
+    /// the neighbors of all the vertices are read by [`Self::open`]. Returns the
+    /// number of vectors loaded.
+    pub fn prewarm(&self, hops: usize) -> Result<usize> {
+        let ids = neighborhood(self, self.medoid, hops, self.cache_size)?;
+        // The rows are read in the order of their row ids.
+        let mut rows = ids
+            .iter()
+            .map(|&id| Ok((self.row_id(id)?, id)))
+            .collect::<Result<Vec<_>>>()?;
+        rows.sort_unstable();
+        let row_ids = rows.iter().map(|(row_id, _)| *row_id).collect::<Vec<_>>();
+        let batch = self.block_on(self.dataset.take_rows(&row_ids, &self.projection))?;
+        if batch.num_rows() != rows.len() {
+            return Err(Error::Index(format!(
+                "Only {} of {} rows found",
+                batch.num_rows(),
+                rows.len()
+            )));
+        }
+        let list = as_fixed_size_list_array(batch.column(0));
+        let mut cache = self.cache.lock().unwrap();
+        for (i, (_, id)) in rows.iter().enumerate() {
+            let values = list.value(i);
+            let vector = as_primitive_array::<Float32Type>(values.as_ref()).clone();
+            cache.insert(*id as u32, Arc::new(vector));
+        }
+        Ok(rows.len())
+    }
+
+    /// The row id of a vertex.
+    fn row_id(&self, id: usize) -> Result<u64> {
+        self.nodes
+            .get(id)
+            .ok_or_else(|| {
+                Error::Index(format!(
+                    "Vector index is out of range: {} >= {}",
+                    id,
+                    self.nodes.len()
+                ))
+            })?
+            .vertex
+            .row_id()
+            .ok_or_else(|| Error::Index(format!("Vertex {} has no row id", id)))
+    }
+
+    /// Run a dataset read to completion, blocking the calling thread.
+    fn block_on<T>(&self, future: impl Future<Output = Result<T>>) -> Result<T> {
+        let handle = Handle::try_current()
+            .map_err(|e| Error::Index(format!("No tokio runtime to read vectors: {}", e)))?;
+        if handle.runtime_flavor() != RuntimeFlavor::MultiThread {
+            return Err(Error::Index(
+                "Reading vectors on demand requires a multi-thread tokio runtime".to_string(),
+            ));
+        }
+        tokio::task::block_in_place(|| handle.block_on(future))
+    }
+
+    /// The vector of a vertex, from the cache or the dataset.
+    fn vector(&self, id: usize) -> Result<Arc<Float32Array>> {
+        if let Some(vector) = self.cache.lock().unwrap().get(&(id as u32)) {
+            return Ok(vector.clone());
+        }
+        let row_id = self.row_id(id)?;
+        let batch = self.block_on(self.dataset.take_rows(&[row_id], &self.projection))?;
+        if batch.num_rows() != 1 {
+            return Err(Error::Index(format!("Row {} not found", row_id)));
+        }
+        let list = as_fixed_size_list_array(batch.column(0));
+        let values = list.value(0);
+        let vector = Arc::new(as_primitive_array::<Float32Type>(values.as_ref()).clone());
+        self.cache.lock().unwrap().insert(id as u32, vector.clone());
+        Ok(vector)
+    }
+}
+
+/// The vertices up to `hops` edges away from `start`, by breadth-first search,
+/// at most `limit` of them.
+pub(super) fn neighborhood(
+    graph: &impl Graph,
+    start: usize,
+    hops: usize,
+    limit: usize,
+) -> Result<Vec<usize>> {
+    let mut ids = vec![];
+    let mut seen = HashSet::from([start]);
+    let mut queue = VecDeque::from([(start, 0)]);
+    while let Some((id, depth)) = queue.pop_front() {
+        if ids.len() >= limit {
+            break;
+        }
+        ids.push(id);
+        if depth == hops {
+            continue;
+        }
+        for &neighbor in graph.neighbors(id)? {
+            if seen.insert(neighbor as usize) {
+                queue.push_back((neighbor as usize, depth + 1));
+            }
+        }
+    }
+    Ok(ids)
+}
+
+impl Graph for LazyVectorGraph {
+    fn distance(&self, a: usize, b: usize) -> Result<f32> {
+        let vector_a = self.vector(a)?;
+        let vector_b = self.vector(b)?;
+        Ok((self.distance_func)(vector_a.values(), vector_b.values()))
+    }
+
+    fn distance_to(&self, query: &[f32], idx: usize) -> Result<f32> {
+        let vector = self.vector(idx)?;
+        Ok((self.distance_func)(query, vector.values()))
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn neighbors(&self, id: usize) -> Result<&[u32]> {
+        self.nodes
+            .get(id)
+            .map(|node| node.neighbors.as_slice())
+            .ok_or_else(|| {
+                Error::Index(format!(
+                    "Vertex index is out of range: {} >= {}",
+                    id,
+                    self.nodes.len()
+                ))
+            })
+    }
+}
+
+#[async_trait]
+impl SearchIndex for LazyVectorGraph {
+    /// Search the `k` nearest rows to the query from the medoid, reading the vectors
+    /// it goes through from the cache or the dataset.
+    ///
+    /// Only the search size and the early termination of `params` are used.
+    async fn search(
+        &self,
+        query: &[f32],
+        k: usize,
+        params: &SearchParams,
+    ) -> Result<Vec<(u64, f32)>> {
+        let state = greedy_search_with_early_termination(
+            self,
+            self.medoid,
+            query,
+            k,
+            params.search_size.max(k),
+            params.early_termination,
+        )?;
+        state
+            .results()
+            .into_iter()
+            .map(|(id, dist)| Ok((self.row_id(id)?, dist)))
+            .collect()
+    }
+
+    fn metric(&self) -> MetricType {
+        self.metric_type.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::arrow::linalg::MatrixView;
+    use crate::index::vector::diskann::builder::tests::create_dataset;
+    use crate::index::vector::diskann::{DiskANNGraph, DiskANNParams};
+    use crate::index::vector::open_index;
+    use crate::index::DatasetIndexExt;
+
+    /// Build a DiskANN index over 100 random vectors of dimension 8, returning the
+    /// indexed dataset and the uuid of the index.
+    async fn create_index(uri: &str) -> (Arc<Dataset>, String) {
+        let dataset = create_dataset(uri, 100, 8).await;
+        let mut params = DiskANNParams::default();
+        params.r(10).l(20);
+        let dataset = dataset
+            .create_diskann_index("vector", &params)
+            .await
+            .unwrap();
+        let uuid = dataset.load_indices().await.unwrap()[0].uuid.to_string();
+        (Arc::new(dataset), uuid)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_lazy_vector_graph() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let (dataset, uuid) = create_index(tmp_dir.path().to_str().unwrap()).await;
+        let index = open_index(&dataset, &uuid).await.unwrap();
+        let graph = index.as_any().downcast_ref::<DiskANNGraph>().unwrap();
+
+        let lazy = LazyVectorGraph::open(dataset.clone(), &uuid, 16)
+            .await
+            .unwrap();
+        assert_eq!(lazy.len(), 100);
+        assert_eq!(lazy.metric(), MetricType::L2);
+
+        // Same results as the graph with its vectors in memory.
+        let queries = MatrixView::random(5, 8);
+        let params = SearchParams::new(20);
+        for i in 0..5 {
+            let query = queries.row(i).unwrap();
+            let expected = graph.search(query, 5, &params).await.unwrap();
+            assert_eq!(lazy.search(query, 5, &params).await.unwrap(), expected);
+        }
+        assert!(lazy.distance(0, 100).is_err());
+
+        // Not a DiskANN index.
+        assert!(LazyVectorGraph::open(dataset, "no-such-index", 16)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_prewarm() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let (dataset, uuid) = create_index(tmp_dir.path().to_str().unwrap()).await;
+        let lazy = LazyVectorGraph::open(dataset, &uuid, 16).await.unwrap();
+        let medoid = lazy.medoid;
+        let neighbors = lazy.neighbors(medoid).unwrap().to_vec();
+
+        // The medoid alone, then its neighbors.
+        assert_eq!(lazy.prewarm(0).unwrap(), 1);
+        let loaded = lazy.prewarm(1).unwrap();
+        assert_eq!(loaded, neighbors.len() + 1);
+        let mut cache = lazy.cache.lock().unwrap();
+        assert!(cache.get(&(medoid as u32)).is_some());
+        assert!(neighbors.iter().all(|n| cache.get(n).is_some()));
+        drop(cache);
+
+        // Bounded by the cache size.
+        assert_eq!(lazy.prewarm(10).unwrap(), 16);
+    }
+}
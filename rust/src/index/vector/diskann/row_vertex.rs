@@ -33,7 +33,11 @@ impl RowVertex {
     }
 }
 
-impl Vertex for RowVertex {}
+impl Vertex for RowVertex {
+    fn row_id(&self) -> Option<u64> {
+        Some(self.row_id)
+    }
+}
 
 pub(crate) struct RowVertexSerDe {}
 
@@ -13,14 +13,33 @@
 // limitations under the License.
 
 use std::{
+    any::Any,
     cmp::Reverse,
-    collections::{BTreeMap, BinaryHeap, HashSet},
+    collections::{BTreeMap, BinaryHeap, HashMap, HashSet},
+    fmt,
+    hash::Hash,
+    sync::Arc,
 };
 
+use arrow_array::{Float32Array, RecordBatch, UInt64Array};
+use arrow_schema::{DataType, Field, Schema as ArrowSchema};
+use async_trait::async_trait;
 use ordered_float::OrderedFloat;
+use roaring::RoaringTreemap;
 
-use crate::index::vector::graph::{Graph, VertexWithDistance};
-use crate::Result;
+use super::builder::{
+    build_graph, build_graph_streaming, load_diskann_graph, load_f64_vectors, merge_shards,
+    rebalance, repair_after_delete,
+};
+use super::lazy::neighborhood;
+use super::row_vertex::RowVertex;
+use super::DiskANNParams;
+use crate::dataset::{Dataset, ROW_ID};
+use crate::index::pb;
+use crate::index::vector::graph::{builder::GraphBuilder, Graph, VertexWithDistance};
+use crate::index::vector::{MetricType, Query, SearchIndex, SCORE_COL};
+use crate::io::object_reader::ObjectReader;
+use crate::{arrow::linalg::MatrixView, Error, Result};
 
 /// DiskANN search state.
 pub(crate) struct SearchState {
@@ -41,23 +60,59 @@ pub(crate) struct SearchState {
     l: usize,
 
     /// Number of results to return.
-    //TODO: used during search.
-    #[allow(dead_code)]
     k: usize,
+
+    /// Number of distances computed so far.
+    pub num_distances: usize,
+
+    /// Number of vertices expanded so far, i.e., popped from the heap.
+    pub num_hops: usize,
 }
 
 impl SearchState {
     /// Creates a new search state.
     pub(crate) fn new(k: usize, l: usize) -> Self {
+        // A search usually visits a few times `l` vertices.
         Self {
-            visited: HashSet::new(),
+            visited: HashSet::with_capacity(l * 2),
             candidates: BTreeMap::new(),
-            heap: BinaryHeap::new(),
+            heap: BinaryHeap::with_capacity(l * 2),
             k,
             l,
+            num_distances: 0,
+            num_hops: 0,
         }
     }
 
+    /// Clear the state for a new search, keeping the allocated memory.
+    fn reset(&mut self, k: usize, l: usize) {
+        self.visited.clear();
+        self.candidates.clear();
+        self.heap.clear();
+        self.k = k;
+        self.l = l;
+        self.num_distances = 0;
+        self.num_hops = 0;
+        self.visited.reserve(l * 2);
+        self.heap.reserve(l * 2);
+    }
+
+    /// The `k` closest vertices found so far, as `(vertex_id, distance)` sorted by distance.
+    pub(crate) fn results(&self) -> Vec<(usize, f32)> {
+        self.sorted_candidates().take(self.k).collect()
+    }
+
+    /// Number of candidates found. Well below the search size, it means that the
+    /// search ran out of reachable vertices, e.g., on a poorly connected graph.
+    pub(crate) fn num_candidates(&self) -> usize {
+        self.candidates.len()
+    }
+
+    /// All the candidates, as `(vertex_id, distance)` sorted by distance.
+    pub(crate) fn sorted_candidates(&self) -> impl Iterator<Item = (usize, f32)> + '_ {
+        self.candidates.iter().map(|(dist, id)| (*id, dist.0))
+    }
+
     /// Return the next unvisited vertex.
     fn pop(&mut self) -> Option<usize> {
         while let Some(vertex) = self.heap.pop() {
@@ -112,14 +167,87 @@ pub(crate) fn greedy_search(
     k: usize,
     search_size: usize, // L in the paper.
 ) -> Result<SearchState> {
+    greedy_search_with_early_termination(graph, start, query, k, search_size, None)
+}
+
+/// Stop a search once the top-k candidates stop improving.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EarlyTermination {
+    /// Minimum relative improvement of the distance of the k-th best candidate,
+    /// for an expansion to count as an improvement.
+    pub epsilon: f32,
+
+    /// Stop after this many expansions in a row without improvement.
+    pub patience: usize,
+}
+
+/// [`greedy_search`], stopping early if `early_termination` is set.
+pub(crate) fn greedy_search_with_early_termination(
+    graph: &dyn Graph,
+    start: usize,
+    query: &[f32],
+    k: usize,
+    search_size: usize,
+    early_termination: Option<EarlyTermination>,
+) -> Result<SearchState> {
+    let mut state = SearchState::new(k, search_size);
+    greedy_search_into(
+        graph,
+        start,
+        &[],
+        query,
+        k,
+        search_size,
+        early_termination,
+        &mut state,
+    )?;
+    Ok(state)
+}
+
+/// [`greedy_search_with_early_termination`], into a reused search state.
+///
+/// `seeds` are vertices expected to be close to the query, from which the search
+/// starts in addition to `start`.
+#[allow(clippy::too_many_arguments)]
+fn greedy_search_into(
+    graph: &dyn Graph,
+    start: usize,
+    seeds: &[usize],
+    query: &[f32],
+    k: usize,
+    search_size: usize,
+    early_termination: Option<EarlyTermination>,
+    state: &mut SearchState,
+) -> Result<()> {
+    if query.len() != graph.dimension() {
+        return Err(Error::Index(format!(
+            "Query dimension {} does not match the index dimension {}",
+            query.len(),
+            graph.dimension()
+        )));
+    }
+
     // L in the paper.
     // A map from distance to vertex id.
-    let mut state = SearchState::new(k, search_size);
+    state.reset(k, search_size);
+
+    let mut kth_distance = f32::INFINITY;
+    let mut num_stale = 0;
 
     let dist = graph.distance_to(query, start)?;
+    state.num_distances += 1;
     state.push(start, dist);
+    for (i, &seed) in seeds.iter().enumerate() {
+        if seed == start || seeds[..i].contains(&seed) {
+            continue;
+        }
+        let dist = graph.distance_to(query, seed)?;
+        state.num_distances += 1;
+        state.push(seed, dist);
+    }
     while let Some(id) = state.pop() {
         state.visit(id);
+        state.num_hops += 1;
         for neighbor_id in graph.neighbors(id)?.iter() {
             let neighbor_id = *neighbor_id as usize;
             if state.is_visited(neighbor_id) {
@@ -127,17 +255,950 @@ pub(crate) fn greedy_search(
                 continue;
             }
             let dist = graph.distance_to(query, neighbor_id)?;
+            state.num_distances += 1;
             state.push(neighbor_id, dist);
         }
+
+        if let Some(EarlyTermination { epsilon, patience }) = early_termination {
+            let Some((_, dist)) = state.sorted_candidates().nth(k.max(1) - 1) else {
+                // Fewer than k candidates yet.
+                continue;
+            };
+            if kth_distance.is_infinite() || kth_distance - dist > epsilon * kth_distance {
+                num_stale = 0;
+            } else {
+                num_stale += 1;
+                if num_stale >= patience {
+                    break;
+                }
+            }
+            kth_distance = dist;
+        }
     }
 
-    Ok(state)
+    Ok(())
+}
+
+/// Parameters to search an in-memory DiskANN graph.
+#[derive(Debug, Clone)]
+pub struct SearchParams {
+    /// Search list size, `L` in the paper.
+    pub search_size: usize,
+
+    /// Return distinct row ids only, keeping the closest vertex of each row id.
+    pub dedup_row_ids: bool,
+
+    /// If set, the search is retried with a doubled search size, up to this cap,
+    /// as long as fewer than `k` results are found.
+    pub max_search_size: Option<usize>,
+
+    /// Stop the search early when the results plateau. Disabled by default.
+    pub early_termination: Option<EarlyTermination>,
+
+    /// Vertices to start the search from, in addition to the entry point of the graph.
+    pub seeds: Vec<usize>,
+
+    /// If set, only these row ids are returned. The other vertices are still
+    /// traversed, to reach the allowed ones.
+    pub filter: Option<Arc<RoaringTreemap>>,
+
+    /// If set, `refine * k` candidates are re-ranked by their exact distances to
+    /// the query, for the graph distances may be approximate.
+    pub refine: Option<usize>,
+}
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        Self {
+            search_size: 100,
+            dedup_row_ids: false,
+            max_search_size: None,
+            early_termination: None,
+            seeds: vec![],
+            filter: None,
+            refine: None,
+        }
+    }
+}
+
+impl SearchParams {
+    pub fn new(search_size: usize) -> Self {
+        Self {
+            search_size,
+            ..Default::default()
+        }
+    }
+
+    pub fn search_size(&mut self, search_size: usize) -> &mut Self {
+        self.search_size = search_size;
+        self
+    }
+
+    pub fn dedup_row_ids(&mut self, dedup: bool) -> &mut Self {
+        self.dedup_row_ids = dedup;
+        self
+    }
+
+    /// Grow the search size up to `max_search_size` until `k` results are found.
+    pub fn max_search_size(&mut self, max_search_size: usize) -> &mut Self {
+        self.max_search_size = Some(max_search_size);
+        self
+    }
+
+    /// Stop the search after `patience` expansions that do not improve the distance
+    /// of the k-th best candidate by more than `epsilon`, relatively.
+    ///
+    /// Saves work on easy queries, at the cost of a possibly lower recall.
+    pub fn early_termination(&mut self, epsilon: f32, patience: usize) -> &mut Self {
+        self.early_termination = Some(EarlyTermination { epsilon, patience });
+        self
+    }
+
+    /// Also start the search from `seeds`, e.g., the results of a similar query,
+    /// to converge faster when they are close to the answer.
+    pub fn seeds(&mut self, seeds: Vec<usize>) -> &mut Self {
+        self.seeds = seeds;
+        self
+    }
+
+    /// Only return the rows of `filter`, e.g., the rows matching a prefilter.
+    ///
+    /// The search size is grown until `k` allowed rows are found, up to
+    /// [`SearchParams::max_search_size`] or, if unset, the size of the graph.
+    pub fn filter(&mut self, filter: Arc<RoaringTreemap>) -> &mut Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Re-rank `factor * k` candidates by exact distance, see [`SearchParams::refine`].
+    ///
+    /// The candidates are bounded by the search size.
+    pub fn refine(&mut self, factor: usize) -> &mut Self {
+        self.refine = Some(factor);
+        self
+    }
+}
+
+/// Cost of a search, independent of the machine it runs on.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchStats {
+    /// Number of distances computed, over all the attempts.
+    pub num_distances: usize,
+
+    /// Number of vertices expanded, over all the attempts.
+    pub num_visited: usize,
+
+    /// Number of candidates of the last attempt, see [`SearchState::num_candidates`].
+    pub num_candidates: usize,
+}
+
+/// Results of a search, see [`DiskANNGraph::search_with_stats`].
+#[derive(Debug, Clone)]
+pub struct SearchResults {
+    /// `(row_id, distance)` pairs, sorted by distance.
+    pub results: Vec<(u64, f32)>,
+
+    /// Search size used by the last search.
+    pub search_size: usize,
+
+    /// Fewer than `k` results were found, even with the largest search size allowed.
+    pub cap_reached: bool,
+
+    pub stats: SearchStats,
+}
+
+/// Search the `k` nearest rows to the query, over an in-memory DiskANN graph.
+///
+/// On a sparse graph, fewer than `k` rows might be found. In that case, the search is
+/// retried with a doubled search size, up to [`SearchParams::max_search_size`] if set.
+pub(crate) fn search(
+    graph: &GraphBuilder<RowVertex>,
+    medoid: usize,
+    query: &[f32],
+    k: usize,
+    params: &SearchParams,
+) -> Result<SearchResults> {
+    search_with_scratch(graph, medoid, query, k, params, &mut SearchScratch::new())
+}
+
+/// Buffers of a search, reused across queries to save the allocations of each search.
+///
+/// The buffers are cleared, not dropped, between the queries, see
+/// [`DiskANNGraph::search_with_scratch`].
+pub struct SearchScratch {
+    state: SearchState,
+}
+
+impl SearchScratch {
+    pub fn new() -> Self {
+        Self {
+            state: SearchState::new(0, 0),
+        }
+    }
+}
+
+impl Default for SearchScratch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// [`search`], reusing the buffers of `scratch`.
+pub(crate) fn search_with_scratch(
+    graph: &GraphBuilder<RowVertex>,
+    medoid: usize,
+    query: &[f32],
+    k: usize,
+    params: &SearchParams,
+    scratch: &mut SearchScratch,
+) -> Result<SearchResults> {
+    let mut search_size = params.search_size.max(1);
+    let mut stats = SearchStats::default();
+    loop {
+        let state = &mut scratch.state;
+        greedy_search_into(
+            graph,
+            medoid,
+            &params.seeds,
+            query,
+            k,
+            search_size,
+            params.early_termination,
+            state,
+        )?;
+        stats.num_distances += state.num_distances;
+        stats.num_visited += state.visited.len();
+        stats.num_candidates = state.num_candidates();
+        let candidates = state
+            .sorted_candidates()
+            .map(|(id, dist)| (graph.vertex(id).row_id, dist))
+            .filter(|(row_id, _)| {
+                params
+                    .filter
+                    .as_ref()
+                    .map_or(true, |filter| filter.contains(*row_id))
+            });
+        let results: Vec<_> = if params.dedup_row_ids {
+            let mut seen = HashSet::new();
+            candidates
+                .filter(|(row_id, _)| seen.insert(*row_id))
+                .take(k)
+                .collect()
+        } else {
+            candidates.take(k).collect()
+        };
+
+        if results.len() >= k {
+            return Ok(SearchResults {
+                results,
+                search_size,
+                cap_reached: false,
+                stats,
+            });
+        }
+        // The allowed rows might be far from the query.
+        let max_search_size = params
+            .max_search_size
+            .or_else(|| params.filter.as_ref().map(|_| graph.len()));
+        match max_search_size {
+            Some(max_search_size) if search_size < max_search_size => {
+                search_size = (search_size * 2).min(max_search_size);
+            }
+            _ => {
+                return Ok(SearchResults {
+                    results,
+                    search_size,
+                    cap_reached: true,
+                    stats,
+                })
+            }
+        }
+    }
+}
+
+/// Search the `k` nearest rows of each row of `queries`, reusing the same buffers.
+pub(crate) fn search_batch(
+    graph: &GraphBuilder<RowVertex>,
+    medoid: usize,
+    queries: &MatrixView,
+    k: usize,
+    params: &SearchParams,
+) -> Result<Vec<SearchResults>> {
+    let mut scratch = SearchScratch::new();
+    (0..queries.num_rows())
+        .map(|i| {
+            search_with_scratch(
+                graph,
+                medoid,
+                queries.row(i).unwrap(),
+                k,
+                params,
+                &mut scratch,
+            )
+        })
+        .collect()
+}
+
+/// How [`search_fused`] combines the distances of a row to several queries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fusion {
+    /// The distance to the closest query.
+    MaxSim,
+
+    /// The sum of the distances to all the queries.
+    Sum,
+}
+
+/// Search the `k` nearest rows to a set of queries, fusing the distances of each
+/// row to all the queries with `fusion`.
+///
+/// The candidates are the `k` nearest rows of each query. Their fused distances
+/// are computed exactly, so a row found by one query only is still scored against
+/// all the queries.
+pub(crate) fn search_fused(
+    graph: &GraphBuilder<RowVertex>,
+    medoid: usize,
+    queries: &[&[f32]],
+    k: usize,
+    params: &SearchParams,
+    fusion: Fusion,
+) -> Result<Vec<(u64, f32)>> {
+    if queries.is_empty() {
+        return Err(Error::Index("Fused search: no query".to_string()));
+    }
+    let mut scratch = SearchScratch::new();
+    let mut candidates = vec![];
+    let mut seen = HashSet::new();
+    for query in queries {
+        let results = search_with_scratch(graph, medoid, query, k, params, &mut scratch)?;
+        for (row_id, _) in results.results {
+            if seen.insert(row_id) {
+                candidates.push(row_id);
+            }
+        }
+    }
+
+    let mut fused: HashMap<u64, f32> = HashMap::with_capacity(candidates.len());
+    for query in queries {
+        for (row_id, dist) in graph.rank_within(query, &candidates, candidates.len(), false)? {
+            fused
+                .entry(row_id)
+                .and_modify(|d| match fusion {
+                    Fusion::MaxSim => *d = d.min(dist),
+                    Fusion::Sum => *d += dist,
+                })
+                .or_insert(dist);
+        }
+    }
+    let mut results = fused.into_iter().collect::<Vec<_>>();
+    results.sort_by(|a, b| a.1.total_cmp(&b.1));
+    results.truncate(k);
+    Ok(results)
+}
+
+/// A DiskANN graph in memory, searchable via [`SearchIndex`].
+pub struct DiskANNGraph {
+    graph: GraphBuilder<RowVertex>,
+
+    /// The entry point of the searches.
+    medoid: usize,
+
+    /// The f64 vectors of the vertices, flattened, to re-rank the search results.
+    /// See [`DiskANNParams::exact_rerank`].
+    exact: Option<Vec<f64>>,
+}
+
+impl DiskANNGraph {
+    pub(crate) fn new(graph: GraphBuilder<RowVertex>, medoid: usize) -> Self {
+        Self {
+            graph,
+            medoid,
+            exact: None,
+        }
+    }
+
+    /// The entry point of the searches, to reuse in a rebuild with
+    /// [`DiskANNParams::precomputed_medoid`].
+    pub fn medoid(&self) -> usize {
+        self.medoid
+    }
+
+    /// Estimate the memory, in bytes, to build the graph over `row_count` vectors of
+    /// `dimension` with `r` neighbors each, e.g., [`DiskANNParams::r`].
+    ///
+    /// It counts the vectors, a neighbor list of capacity `r` per vertex and the
+    /// row id index, not the transient buffers of the build.
+    pub fn estimate_memory(row_count: usize, dimension: usize, r: usize) -> usize {
+        GraphBuilder::<RowVertex>::estimate_memory(row_count, dimension, r)
+    }
+
+    /// Build the graph in memory over the vector `column` of the dataset.
+    pub async fn build(dataset: &Dataset, column: &str, params: &DiskANNParams) -> Result<Self> {
+        let (graph, report) = build_graph(dataset, &[(column, 1.0)], params).await?;
+        let mut index = Self::new(graph, report.medoid);
+        if params.exact_rerank {
+            index.exact = Some(load_f64_vectors(dataset, column, &index.graph).await?);
+        }
+        Ok(index)
+    }
+
+    /// Build the graph in memory over the vector `column` of the dataset, inserting
+    /// the vectors batch by batch while scanning the dataset.
+    ///
+    /// Besides the graph, only one batch of the scan is held in memory, instead of a
+    /// second copy of the whole vector column, at the cost of a slightly lower recall
+    /// than [`Self::build`].
+    pub async fn build_streaming(
+        dataset: &Dataset,
+        column: &str,
+        params: &DiskANNParams,
+    ) -> Result<Self> {
+        let (graph, medoid) = build_graph_streaming(dataset, column, params).await?;
+        let mut index = Self::new(graph, medoid);
+        if params.exact_rerank {
+            index.exact = Some(load_f64_vectors(dataset, column, &index.graph).await?);
+        }
+        Ok(index)
+    }
+
+    /// Search the `k` nearest rows to several queries, e.g., the expansions of one
+    /// query, fusing the distances of each row to the queries with `fusion`.
+    pub fn search_fused(
+        &self,
+        queries: &[&[f32]],
+        k: usize,
+        search_size: usize,
+        fusion: Fusion,
+    ) -> Result<Vec<(u64, f32)>> {
+        search_fused(
+            &self.graph,
+            self.medoid,
+            queries,
+            k,
+            &SearchParams::new(search_size),
+            fusion,
+        )
+    }
+
+    /// Search the `k` nearest rows to the query, keeping at most `per_group_cap` rows
+    /// of each group, e.g., a few chunks per document. `group_key` maps a row id to
+    /// its group.
+    ///
+    /// The whole search list is fetched and capped, so fewer than `k` rows are returned
+    /// when it does not span enough groups.
+    pub fn search_grouped<G: Eq + Hash>(
+        &self,
+        query: &[f32],
+        k: usize,
+        search_size: usize,
+        group_key: impl Fn(u64) -> G,
+        per_group_cap: usize,
+    ) -> Result<Vec<(u64, f32)>> {
+        if per_group_cap == 0 {
+            return Err(Error::Index(
+                "Grouped search: per group cap must be positive".to_string(),
+            ));
+        }
+        let search_size = search_size.max(k);
+        let candidates = search(
+            &self.graph,
+            self.medoid,
+            query,
+            search_size,
+            &SearchParams::new(search_size),
+        )?;
+        let mut counts = HashMap::new();
+        Ok(candidates
+            .results
+            .into_iter()
+            .filter(|(row_id, _)| {
+                let count = counts.entry(group_key(*row_id)).or_insert(0);
+                *count += 1;
+                *count <= per_group_cap
+            })
+            .take(k)
+            .collect())
+    }
+
+    /// Load the graph of the DiskANN index `index` over the vector `column` of the
+    /// dataset, see [`crate::index::DatasetIndexExt::create_diskann_index`].
+    pub(crate) async fn load(
+        dataset: &Dataset,
+        column: &str,
+        index: &pb::DiskAnn,
+        dimension: usize,
+        metric_type: MetricType,
+    ) -> Result<Self> {
+        let medoid = *index
+            .entries
+            .first()
+            .ok_or_else(|| Error::Index("DiskANN index has no entry point".to_string()))?;
+        let graph =
+            load_diskann_graph(dataset, column, &index.filename, dimension, metric_type).await?;
+        Ok(Self::new(graph, medoid as usize))
+    }
+
+    /// Merge the graphs built over disjoint shards of the rows, e.g., by different
+    /// workers, into one graph. The row ids of the shards are kept.
+    ///
+    /// The f64 vectors are kept only if all the shards have them.
+    pub async fn merge_shards(shards: Vec<Self>, params: &DiskANNParams) -> Result<Self> {
+        // The shards are concatenated in order, so are their f64 vectors.
+        let exact = shards
+            .iter()
+            .map(|s| s.exact.as_ref())
+            .collect::<Option<Vec<_>>>()
+            .map(|vectors| vectors.into_iter().flatten().copied().collect());
+        let shards = shards.into_iter().map(|s| s.graph).collect();
+        let (graph, medoid) = merge_shards(shards, params).await?;
+        Ok(Self {
+            exact,
+            ..Self::new(graph, medoid)
+        })
+    }
+
+    /// Prune the vertices with more than [`DiskANNParams::r`] neighbors, to keep the
+    /// search latency bounded after many inserts.
+    ///
+    /// Returns the number of vertices pruned.
+    pub async fn rebalance(&mut self, params: &DiskANNParams) -> Result<usize> {
+        rebalance(&mut self.graph, params).await
+    }
+
+    /// Remove the vertices of deleted rows, repairing only the neighborhoods that
+    /// referenced them, instead of building the graph again.
+    ///
+    /// If the row of the entry point is deleted, the medoid of the remaining vectors
+    /// becomes the entry point, see [`Self::medoid`].
+    pub async fn repair_after_delete(
+        &mut self,
+        deleted_row_ids: &[u64],
+        params: &DiskANNParams,
+    ) -> Result<()> {
+        let (id_map, medoid) =
+            repair_after_delete(&mut self.graph, self.medoid, deleted_row_ids, params).await?;
+        self.medoid = medoid;
+        if let Some(exact) = self.exact.as_mut() {
+            // The remaining vertices keep their order, so each vector moves down.
+            let dim = self.graph.data.num_columns();
+            for (id, new_id) in id_map.iter().enumerate() {
+                if let Some(new_id) = new_id {
+                    let new_id = *new_id as usize;
+                    exact.copy_within(id * dim..(id + 1) * dim, new_id * dim);
+                }
+            }
+            exact.truncate(self.graph.len() * dim);
+        }
+        Ok(())
+    }
+
+    /// Touch the memory of the vertices up to `hops` edges away from the medoid, the
+    /// entry point of the searches, in the order of a breadth-first search.
+    ///
+    /// The graph is already in memory, so nothing is read from the dataset: for each
+    /// of these vertices, one value per memory page of its vector, and of its f64
+    /// vector if kept for re-ranking, is read, so that the pages swapped out or not
+    /// yet mapped are faulted in before the first searches rather than during them.
+    /// See [`super::LazyVectorGraph::prewarm`] to load the vectors of a graph that
+    /// reads them on demand.
+    ///
+    /// Returns the number of vertices touched.
+    pub fn prewarm(&self, hops: usize) -> Result<usize> {
+        let ids = neighborhood(&self.graph, self.medoid, hops, self.graph.len())?;
+        let dim = self.graph.data.num_columns();
+        for &id in ids.iter() {
+            let vector = self.graph.data.row(id).ok_or_else(|| {
+                Error::Index(format!(
+                    "Vector index is out of range: {} >= {}",
+                    id,
+                    self.graph.len()
+                ))
+            })?;
+            touch_pages(vector);
+            if let Some(exact) = self.exact.as_ref() {
+                touch_pages(&exact[id * dim..(id + 1) * dim]);
+            }
+        }
+        Ok(ids.len())
+    }
+
+    /// Search the `k` nearest rows to the query, reusing the buffers of `scratch`.
+    ///
+    /// A serving thread can keep one scratch for all its queries, to save the
+    /// allocations of each search.
+    pub fn search_with_scratch(
+        &self,
+        query: &[f32],
+        k: usize,
+        params: &SearchParams,
+        scratch: &mut SearchScratch,
+    ) -> Result<Vec<(u64, f32)>> {
+        let candidates = search_with_scratch(
+            &self.graph,
+            self.medoid,
+            query,
+            self.num_candidates(k, params),
+            params,
+            scratch,
+        )?;
+        self.rerank(query, candidates.results, k, params)
+    }
+
+    /// Search the `k` nearest rows to the query, with the search size it ended up
+    /// with and the cost of the search.
+    ///
+    /// The results are not re-ranked, see [`Self::search_with_scratch`].
+    pub fn search_with_stats(
+        &self,
+        query: &[f32],
+        k: usize,
+        params: &SearchParams,
+    ) -> Result<SearchResults> {
+        search(&self.graph, self.medoid, query, k, params)
+    }
+
+    /// Average recall@k of the graph over a set of queries, searched with
+    /// `search_size`. See [`Self::tune_search_size`].
+    ///
+    /// `ground_truth[i]` is the list of exact nearest row ids of the i-th query.
+    pub fn evaluate_recall(
+        &self,
+        queries: &MatrixView,
+        ground_truth: &[Vec<u64>],
+        k: usize,
+        search_size: usize,
+    ) -> Result<f32> {
+        evaluate_recall(
+            &self.graph,
+            self.medoid,
+            queries,
+            ground_truth,
+            k,
+            search_size,
+        )
+    }
+
+    /// Average number of vertices a search of `queries` expands from the medoid.
+    /// Lower means faster searches.
+    pub fn avg_hops(&self, queries: &MatrixView, k: usize, search_size: usize) -> Result<f32> {
+        avg_hops(&self.graph, self.medoid, queries, k, search_size)
+    }
+
+    /// The smallest search size that reaches `target_recall` at `k` over `queries`,
+    /// to set [`SearchParams::search_size`] for a recall target.
+    ///
+    /// If the target can not be reached, it is the number of vertices of the graph.
+    pub fn tune_search_size(
+        &self,
+        queries: &MatrixView,
+        ground_truth: &[Vec<u64>],
+        k: usize,
+        target_recall: f32,
+    ) -> Result<usize> {
+        tune_search_size(
+            &self.graph,
+            self.medoid,
+            queries,
+            ground_truth,
+            k,
+            target_recall,
+        )
+    }
+
+    /// Search the `k` nearest rows of each row of `queries`.
+    ///
+    /// The search buffers are reused across the queries.
+    pub fn search_batch(
+        &self,
+        queries: &MatrixView,
+        k: usize,
+        params: &SearchParams,
+    ) -> Result<Vec<Vec<(u64, f32)>>> {
+        search_batch(
+            &self.graph,
+            self.medoid,
+            queries,
+            self.num_candidates(k, params),
+            params,
+        )?
+        .into_iter()
+        .enumerate()
+        .map(|(i, r)| self.rerank(queries.row(i).unwrap(), r.results, k, params))
+        .collect()
+    }
+
+    /// Number of candidates to search for `k` results: `refine * k` if set, otherwise
+    /// the whole search list when they are re-ranked with the f64 vectors.
+    fn num_candidates(&self, k: usize, params: &SearchParams) -> usize {
+        let search_size = k.max(params.search_size);
+        match (params.refine, &self.exact) {
+            (Some(factor), _) => k.saturating_mul(factor).clamp(k, search_size),
+            (None, Some(_)) => search_size,
+            (None, None) => k,
+        }
+    }
+
+    /// Re-rank the candidates with the f64 distances to `query` if the f64 vectors
+    /// are kept, or with the f32 distances of the metric if [`SearchParams::refine`]
+    /// is set, and keep the `k` nearest.
+    fn rerank(
+        &self,
+        query: &[f32],
+        candidates: Vec<(u64, f32)>,
+        k: usize,
+        params: &SearchParams,
+    ) -> Result<Vec<(u64, f32)>> {
+        let Some(exact) = self.exact.as_ref() else {
+            return match params.refine {
+                Some(_) => self.refine(query, candidates, k),
+                None => Ok(candidates),
+            };
+        };
+        let dim = self.graph.data.num_columns();
+        let query = query.iter().map(|&v| v as f64).collect::<Vec<_>>();
+        let metric_type = self.graph.metric_type();
+        let mut results = candidates
+            .into_iter()
+            .map(|(row_id, _)| {
+                let id = self
+                    .graph
+                    .vertex_for_row(row_id)
+                    .ok_or_else(|| Error::Index(format!("Row {} is not in the graph", row_id)))?;
+                let vector = &exact[id * dim..(id + 1) * dim];
+                let dist = exact_distance(&metric_type, &query, vector);
+                Ok((row_id, dist as f32))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        results.sort_by(|a, b| a.1.total_cmp(&b.1));
+        results.truncate(k);
+        Ok(results)
+    }
+
+    /// Re-rank the candidates with the distances of the metric to `query`, and keep
+    /// the `k` nearest.
+    fn refine(
+        &self,
+        query: &[f32],
+        candidates: Vec<(u64, f32)>,
+        k: usize,
+    ) -> Result<Vec<(u64, f32)>> {
+        let distance_func = self.graph.metric_type().func();
+        let mut results = candidates
+            .into_iter()
+            .map(|(row_id, _)| {
+                let id = self
+                    .graph
+                    .vertex_for_row(row_id)
+                    .ok_or_else(|| Error::Index(format!("Row {} is not in the graph", row_id)))?;
+                let vector = self
+                    .graph
+                    .data
+                    .row(id)
+                    .ok_or_else(|| Error::Index(format!("Vertex {} has no vector", id)))?;
+                Ok((row_id, distance_func(query, vector)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        results.sort_by(|a, b| a.1.total_cmp(&b.1));
+        results.truncate(k);
+        Ok(results)
+    }
+}
+
+/// Distance between two f64 vectors, for re-ranking.
+fn exact_distance(metric_type: &MetricType, from: &[f64], to: &[f64]) -> f64 {
+    match metric_type {
+        MetricType::L2 => from.iter().zip(to).map(|(a, b)| (a - b) * (a - b)).sum(),
+        MetricType::WeightedL2(weights) => from
+            .iter()
+            .zip(to)
+            .zip(weights.iter())
+            .map(|((a, b), w)| *w as f64 * (a - b) * (a - b))
+            .sum(),
+        MetricType::Cosine => {
+            let dot = from.iter().zip(to).map(|(a, b)| a * b).sum::<f64>();
+            let norm = |v: &[f64]| v.iter().map(|a| a * a).sum::<f64>().sqrt();
+            1.0 - dot / (norm(from) * norm(to))
+        }
+    }
+}
+
+#[async_trait]
+impl SearchIndex for DiskANNGraph {
+    async fn search(
+        &self,
+        query: &[f32],
+        k: usize,
+        params: &SearchParams,
+    ) -> Result<Vec<(u64, f32)>> {
+        self.search_with_scratch(query, k, params, &mut SearchScratch::new())
+    }
+
+    fn metric(&self) -> MetricType {
+        self.graph.metric_type()
+    }
+}
+
+impl fmt::Debug for DiskANNGraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DiskANNGraph")
+            .field("len", &self.graph.len())
+            .field("medoid", &self.medoid)
+            .finish()
+    }
+}
+
+// Not imported: its `search` would be ambiguous with the one of `SearchIndex`.
+#[async_trait]
+impl crate::index::vector::VectorIndex for DiskANNGraph {
+    /// Search the `k` nearest rows, or `refine_factor * k` rows to refine, with the
+    /// default search list size.
+    async fn search(&self, query: &Query) -> Result<RecordBatch> {
+        let k = query.k * query.refine_factor.unwrap_or(1) as usize;
+        let results = self.search_with_scratch(
+            query.key.values(),
+            k,
+            &SearchParams::default(),
+            &mut SearchScratch::new(),
+        )?;
+        let scores = Float32Array::from_iter_values(results.iter().map(|(_, d)| *d));
+        let row_ids = UInt64Array::from_iter_values(results.iter().map(|(row_id, _)| *row_id));
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new(SCORE_COL, DataType::Float32, false),
+            Field::new(ROW_ID, DataType::UInt64, false),
+        ]));
+        Ok(RecordBatch::try_new(
+            schema,
+            vec![Arc::new(scores), Arc::new(row_ids)],
+        )?)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn is_loadable(&self) -> bool {
+        false
+    }
+
+    async fn load(
+        &self,
+        _reader: &dyn ObjectReader,
+        _offset: usize,
+        _length: usize,
+    ) -> Result<Arc<dyn crate::index::vector::VectorIndex>> {
+        Err(Error::Index(
+            "DiskANN index is not loadable by another index".to_string(),
+        ))
+    }
+}
+
+/// Average recall@k of the graph over a set of queries.
+///
+/// `ground_truth[i]` is the list of exact nearest row ids of the i-th query,
+/// i.e., as returned by [`brute_force_knn`](crate::index::vector::flat::brute_force_knn).
+pub(crate) fn evaluate_recall(
+    graph: &GraphBuilder<RowVertex>,
+    medoid: usize,
+    queries: &MatrixView,
+    ground_truth: &[Vec<u64>],
+    k: usize,
+    search_size: usize,
+) -> Result<f32> {
+    if queries.num_rows() != ground_truth.len() {
+        return Err(Error::Index(format!(
+            "Number of queries ({}) does not match the ground truth ({})",
+            queries.num_rows(),
+            ground_truth.len()
+        )));
+    }
+    if queries.num_rows() == 0 || k == 0 {
+        return Err(Error::Index(
+            "Evaluate recall: queries and k must not be empty".to_string(),
+        ));
+    }
+
+    let mut total_recall = 0.0;
+    for (i, truth) in ground_truth.iter().enumerate() {
+        let query = queries.row(i).unwrap();
+        let truth: HashSet<u64> = truth.iter().take(k).copied().collect();
+        let results = search(graph, medoid, query, k, &SearchParams::new(search_size))?;
+        let hits = results
+            .results
+            .iter()
+            .filter(|(row_id, _)| truth.contains(row_id))
+            .count();
+        total_recall += hits as f32 / truth.len().max(1) as f32;
+    }
+    Ok(total_recall / queries.num_rows() as f32)
+}
+
+/// Average number of hops of [`greedy_search`] from the medoid over a set of
+/// queries, i.e., the number of vertices it expands. Lower means faster searches.
+pub(crate) fn avg_hops(
+    graph: &dyn Graph,
+    medoid: usize,
+    queries: &MatrixView,
+    k: usize,
+    search_size: usize,
+) -> Result<f32> {
+    if queries.num_rows() == 0 {
+        return Err(Error::Index(
+            "Average hops: queries must not be empty".to_string(),
+        ));
+    }
+    let mut total_hops = 0;
+    for i in 0..queries.num_rows() {
+        let query = queries.row(i).unwrap();
+        total_hops += greedy_search(graph, medoid, query, k, search_size)?.num_hops;
+    }
+    Ok(total_hops as f32 / queries.num_rows() as f32)
+}
+
+/// Find the smallest search size (`L`) that reaches the target recall@k.
+///
+/// It binary-searches `L` in `[k, graph.len()]`, assuming that the recall
+/// grows monotonically with the search size. If the target recall can not be
+/// reached, returns the largest search size, `graph.len()`.
+pub(crate) fn tune_search_size(
+    graph: &GraphBuilder<RowVertex>,
+    medoid: usize,
+    queries: &MatrixView,
+    ground_truth: &[Vec<u64>],
+    k: usize,
+    target_recall: f32,
+) -> Result<usize> {
+    let mut low = k;
+    let mut high = graph.len().max(k);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        let recall = evaluate_recall(graph, medoid, queries, ground_truth, k, mid)?;
+        if recall >= target_recall {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    Ok(low)
+}
+
+/// Read one value per memory page of `values`, with volatile reads that are not
+/// optimized away.
+fn touch_pages<T: Copy>(values: &[T]) {
+    const PAGE_SIZE: usize = 4096;
+    let step = (PAGE_SIZE / std::mem::size_of::<T>()).max(1);
+    for value in values.iter().step_by(step).chain(values.last()) {
+        // Safety: the reference is valid and aligned.
+        unsafe { std::ptr::read_volatile(value) };
+    }
 }
 
 #[cfg(test)]
 mod test {
 
+    use std::sync::Arc;
+
     use super::*;
+    use crate::linalg::l2::l2_distance;
 
     #[test]
     fn test_search_state() {
@@ -164,4 +1225,489 @@ mod test {
         assert!(state.heap.is_empty());
         assert_eq!(state.candidates.len(), 20);
     }
+
+    #[test]
+    fn test_dedup_row_ids() {
+        // Each row is indexed by two vertices with near-identical vectors.
+        let dim = 4;
+        let num_vertices = 20;
+        let nodes = (0..num_vertices)
+            .map(|i| RowVertex::new((i / 2) as u64, None))
+            .collect::<Vec<_>>();
+        let values = (0..num_vertices)
+            .flat_map(|i| {
+                let v = (i / 2) as f32 + (i % 2) as f32 * 0.001;
+                vec![v; dim]
+            })
+            .collect::<Vec<_>>();
+        let matrix = MatrixView::new(Arc::new(Float32Array::from(values)), dim);
+        let mut graph = GraphBuilder::new(&nodes, matrix, MetricType::L2);
+        for i in 0..num_vertices {
+            for j in 0..num_vertices {
+                if i != j {
+                    graph.add_neighbor(i, j);
+                }
+            }
+        }
+
+        let query = vec![0.0; dim];
+        let results = search(&graph, 0, &query, 4, &SearchParams::new(10)).unwrap();
+        let row_ids = results.results.iter().map(|(r, _)| *r).collect::<Vec<_>>();
+        assert_eq!(row_ids, vec![0, 0, 1, 1]);
+
+        let mut params = SearchParams::new(10);
+        params.dedup_row_ids(true);
+        let results = search(&graph, 0, &query, 4, &params).unwrap();
+        let row_ids = results.results.iter().map(|(r, _)| *r).collect::<Vec<_>>();
+        assert_eq!(row_ids, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_grow_search_size() {
+        // A chain graph: each vertex only links to the next one, which is farther
+        // away from the query.
+        let dim = 4;
+        let num_vertices = 20;
+        let nodes = (0..num_vertices)
+            .map(|i| RowVertex::new(i as u64, None))
+            .collect::<Vec<_>>();
+        let values = (0..num_vertices)
+            .flat_map(|i| vec![i as f32; dim])
+            .collect::<Vec<_>>();
+        let matrix = MatrixView::new(Arc::new(Float32Array::from(values)), dim);
+        let mut graph = GraphBuilder::new(&nodes, matrix, MetricType::L2);
+        for i in 0..num_vertices - 1 {
+            graph.add_neighbor(i, i + 1);
+        }
+        let query = vec![0.0; dim];
+
+        let results = search(&graph, 0, &query, 5, &SearchParams::new(2)).unwrap();
+        assert_eq!(results.results.len(), 2);
+        assert!(results.cap_reached);
+
+        let mut params = SearchParams::new(2);
+        params.max_search_size(16);
+        let results = search(&graph, 0, &query, 5, &params).unwrap();
+        let row_ids = results.results.iter().map(|(r, _)| *r).collect::<Vec<_>>();
+        assert_eq!(row_ids, vec![0, 1, 2, 3, 4]);
+        assert_eq!(results.search_size, 8);
+        assert!(!results.cap_reached);
+
+        params.max_search_size(3);
+        let results = search(&graph, 0, &query, 5, &params).unwrap();
+        assert_eq!(results.results.len(), 3);
+        assert_eq!(results.search_size, 3);
+        assert!(results.cap_reached);
+    }
+
+    #[tokio::test]
+    async fn test_search_index() {
+        let dim = 4;
+        let num_vertices = 20;
+        let nodes = (0..num_vertices)
+            .map(|i| RowVertex::new(i as u64 + 100, None))
+            .collect::<Vec<_>>();
+        let values = (0..num_vertices)
+            .flat_map(|i| vec![i as f32; dim])
+            .collect::<Vec<_>>();
+        let matrix = MatrixView::new(Arc::new(Float32Array::from(values)), dim);
+        let mut graph = GraphBuilder::new(&nodes, matrix, MetricType::L2);
+        for i in 0..num_vertices - 1 {
+            graph.add_neighbor(i, i + 1);
+            graph.add_neighbor(i + 1, i);
+        }
+
+        let index: Box<dyn SearchIndex> = Box::new(DiskANNGraph::new(graph, 10));
+        assert_eq!(index.metric(), MetricType::L2);
+        let results = index
+            .search(&[3.1; 4], 3, &SearchParams::new(10))
+            .await
+            .unwrap();
+        let row_ids = results.iter().map(|(r, _)| *r).collect::<Vec<_>>();
+        assert_eq!(row_ids, vec![103, 104, 102]);
+
+        assert!(index
+            .search(&[3.0; 8], 3, &SearchParams::new(10))
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_wrong_query_dimension() {
+        let nodes = (0..10)
+            .map(|i| RowVertex::new(i as u64, None))
+            .collect::<Vec<_>>();
+        let mut graph = GraphBuilder::new(&nodes, MatrixView::random(10, 8), MetricType::L2);
+        for i in 0..9 {
+            graph.add_neighbor(i, i + 1);
+        }
+
+        assert!(greedy_search(&graph, 0, &[0.0; 8], 1, 10).is_ok());
+        let err = greedy_search(&graph, 0, &[0.0; 6], 1, 10).err().unwrap();
+        assert!(err.to_string().contains("dimension"), "{}", err);
+        assert!(search(&graph, 0, &[0.0; 12], 1, &SearchParams::default()).is_err());
+    }
+
+    /// A `size x size` grid, each vertex links to its 4 neighbors.
+    fn grid_graph(size: usize) -> GraphBuilder<RowVertex> {
+        let nodes = (0..size * size)
+            .map(|i| RowVertex::new(i as u64, None))
+            .collect::<Vec<_>>();
+        let values = (0..size * size)
+            .flat_map(|i| [(i / size) as f32, (i % size) as f32])
+            .collect::<Vec<_>>();
+        let matrix = MatrixView::new(Arc::new(Float32Array::from(values)), 2);
+        let mut graph = GraphBuilder::new(&nodes, matrix, MetricType::L2);
+        for i in 0..size * size {
+            let (x, y) = (i / size, i % size);
+            if x > 0 {
+                graph.add_neighbor(i, i - size);
+            }
+            if x + 1 < size {
+                graph.add_neighbor(i, i + size);
+            }
+            if y > 0 {
+                graph.add_neighbor(i, i - 1);
+            }
+            if y + 1 < size {
+                graph.add_neighbor(i, i + 1);
+            }
+        }
+        graph
+    }
+
+    #[tokio::test]
+    async fn test_repair_after_delete() {
+        // The center of the grid is the entry point.
+        let mut index = DiskANNGraph::new(grid_graph(5), 12);
+        index.exact = Some((0..25 * 2).map(|v| v as f64).collect());
+        let params = DiskANNParams::default();
+        index
+            .repair_after_delete(&[0, 12, 24], &params)
+            .await
+            .unwrap();
+        assert_eq!(index.graph.len(), 22);
+
+        // The centroid is still (2, 2). The first of its closest vertices, (1, 2), is
+        // the new entry point.
+        assert_eq!(index.medoid(), 6);
+        assert_eq!(index.graph.vertex(6).row_id, 7);
+        assert_eq!(index.graph.reachable_count(6), 22);
+        let exact = index.exact.as_ref().unwrap();
+        assert_eq!(exact.len(), 22 * 2);
+        assert_eq!(&exact[2 * 3..2 * 4], &[8.0, 9.0]);
+        assert_eq!(&exact[2 * 11..2 * 12], &[26.0, 27.0]);
+
+        let results = index
+            .search_with_stats(&[2.0, 2.0], 1, &SearchParams::new(10))
+            .unwrap();
+        assert_ne!(results.results[0].0, 12);
+    }
+
+    #[test]
+    fn test_prewarm() {
+        // The center of the grid is the entry point.
+        let mut index = DiskANNGraph::new(grid_graph(5), 12);
+        assert_eq!(index.prewarm(0).unwrap(), 1);
+        assert_eq!(index.prewarm(1).unwrap(), 5);
+        assert_eq!(index.prewarm(2).unwrap(), 13);
+        assert_eq!(index.prewarm(10).unwrap(), 25);
+
+        index.exact = Some((0..25 * 2).map(|v| v as f64).collect());
+        assert_eq!(index.prewarm(10).unwrap(), 25);
+    }
+
+    #[test]
+    fn test_num_distances() {
+        let graph = grid_graph(10);
+
+        let query = [6.2, 7.1];
+        let mut last = 0;
+        for search_size in [1, 2, 4, 8, 16, 32, 64] {
+            let results = search(&graph, 0, &query, 1, &SearchParams::new(search_size)).unwrap();
+            assert_eq!(results.results[0].0, 67);
+            let stats = results.stats;
+            assert!(stats.num_distances >= stats.num_visited);
+            assert!(
+                stats.num_distances >= last,
+                "search size {}: {} < {}",
+                search_size,
+                stats.num_distances,
+                last
+            );
+            last = stats.num_distances;
+        }
+        assert!(last > 0);
+    }
+
+    #[test]
+    fn test_num_candidates() {
+        // Only the first 3 vertices are reachable from the entry point.
+        let nodes = (0..20)
+            .map(|i| RowVertex::new(i as u64, None))
+            .collect::<Vec<_>>();
+        let mut graph = GraphBuilder::new(&nodes, MatrixView::random(20, 4), MetricType::L2);
+        graph.add_neighbor(0, 1);
+        graph.add_neighbor(1, 2);
+        for i in 3..19 {
+            graph.add_neighbor(i, i + 1);
+        }
+        let query = vec![0.5; 4];
+
+        let state = greedy_search(&graph, 0, &query, 2, 10).unwrap();
+        assert_eq!(state.num_candidates(), 3);
+        let results = search(&graph, 0, &query, 2, &SearchParams::new(10)).unwrap();
+        assert_eq!(results.stats.num_candidates, 3);
+
+        // Once connected, the candidate list is filled.
+        graph.add_neighbor(2, 3);
+        let state = greedy_search(&graph, 0, &query, 2, 10).unwrap();
+        assert_eq!(state.num_candidates(), 10);
+    }
+
+    #[test]
+    fn test_search_batch() {
+        let nodes = (0..100)
+            .map(|i| RowVertex::new(i as u64, None))
+            .collect::<Vec<_>>();
+        let mut graph = GraphBuilder::new(&nodes, MatrixView::random(100, 8), MetricType::L2);
+        for i in 0..100 {
+            for j in 1..=5 {
+                graph.add_neighbor(i, (i + j * 7) % 100);
+            }
+        }
+        let queries = MatrixView::random(10, 8);
+        let params = SearchParams::new(20);
+        let batch = search_batch(&graph, 0, &queries, 5, &params).unwrap();
+        assert_eq!(batch.len(), 10);
+        for (i, results) in batch.iter().enumerate() {
+            let expected = search(&graph, 0, queries.row(i).unwrap(), 5, &params).unwrap();
+            assert_eq!(results.results, expected.results);
+            assert_eq!(results.stats, expected.stats);
+        }
+    }
+
+    #[test]
+    fn test_search_with_scratch() {
+        let nodes = (0..100)
+            .map(|i| RowVertex::new(i as u64, None))
+            .collect::<Vec<_>>();
+        let mut graph = GraphBuilder::new(&nodes, MatrixView::random(100, 8), MetricType::L2);
+        for i in 0..100 {
+            for j in 1..=5 {
+                graph.add_neighbor(i, (i + j * 7) % 100);
+            }
+        }
+        let index = DiskANNGraph::new(graph, 0);
+        let queries = MatrixView::random(10, 8);
+        let mut scratch = SearchScratch::new();
+        for i in 0..10 {
+            // Alternate the sizes, so that the buffers of a larger search are reused.
+            let params = SearchParams::new(if i % 2 == 0 { 40 } else { 10 });
+            let query = queries.row(i).unwrap();
+            let reused = index
+                .search_with_scratch(query, 5, &params, &mut scratch)
+                .unwrap();
+            let fresh = index
+                .search_with_scratch(query, 5, &params, &mut SearchScratch::new())
+                .unwrap();
+            assert_eq!(reused, fresh);
+        }
+    }
+
+    #[test]
+    fn test_search_with_refine() {
+        let num_vertices = 200;
+        let dim = 16;
+        let nodes = (0..num_vertices)
+            .map(|i| RowVertex::new(i as u64, None))
+            .collect::<Vec<_>>();
+        let data = MatrixView::random(num_vertices, dim);
+        let mut graph = GraphBuilder::new(&nodes, data.clone(), MetricType::L2);
+        for i in 0..num_vertices {
+            for j in 0..num_vertices {
+                if i != j {
+                    graph.add_neighbor(i, j);
+                }
+            }
+        }
+        // Approximate the distances with the first 4 dimensions only.
+        graph.set_distance_func(Arc::new(|a: &[f32], b: &[f32]| {
+            l2_distance(&a[..4], &b[..4])
+        }));
+        let index = DiskANNGraph::new(graph, 0);
+
+        let k = 10;
+        let queries = MatrixView::random(10, dim);
+        let recall = |params: &SearchParams| {
+            let results = index.search_batch(&queries, k, params).unwrap();
+            results
+                .iter()
+                .enumerate()
+                .map(|(i, result)| {
+                    let query = queries.row(i).unwrap();
+                    let mut expected = (0..num_vertices)
+                        .map(|id| (id as u64, l2_distance(query, data.row(id).unwrap())))
+                        .collect::<Vec<_>>();
+                    expected.sort_by(|a, b| a.1.total_cmp(&b.1));
+                    let expected = expected[..k]
+                        .iter()
+                        .map(|(r, _)| *r)
+                        .collect::<HashSet<_>>();
+                    assert_eq!(result.len(), k);
+                    result.iter().filter(|(r, _)| expected.contains(r)).count()
+                })
+                .sum::<usize>()
+        };
+
+        let mut params = SearchParams::new(100);
+        let approximate = recall(&params);
+        params.refine(4);
+        let refined = recall(&params);
+        assert!(
+            refined > approximate,
+            "refined: {}, approximate: {}",
+            refined,
+            approximate
+        );
+    }
+
+    #[test]
+    fn test_search_with_seeds() {
+        let graph = grid_graph(10);
+        let query = [8.9, 9.2];
+
+        let params = SearchParams::new(4);
+        let unseeded = search(&graph, 0, &query, 1, &params).unwrap();
+        assert_eq!(unseeded.results[0].0, 99);
+
+        // Empty seeds do not change the search.
+        let mut params = SearchParams::new(4);
+        params.seeds(vec![]);
+        let results = search(&graph, 0, &query, 1, &params).unwrap();
+        assert_eq!(results.results, unseeded.results);
+        assert_eq!(results.stats, unseeded.stats);
+
+        // Seeding next to the answer, e.g., from the results of a similar query.
+        params.seeds(vec![89, 98, 89]);
+        let seeded = search(&graph, 0, &query, 1, &params).unwrap();
+        assert_eq!(seeded.results[0].0, 99);
+        assert!(
+            seeded.stats.num_distances < unseeded.stats.num_distances,
+            "{} >= {}",
+            seeded.stats.num_distances,
+            unseeded.stats.num_distances
+        );
+
+        params.seeds(vec![100]);
+        assert!(search(&graph, 0, &query, 1, &params).is_err());
+    }
+
+    #[test]
+    fn test_search_with_filter() {
+        let graph = grid_graph(10);
+        let query = [8.9, 9.2];
+
+        // Only the column y = 0, far from the query.
+        let allowed = Arc::new((0..10).map(|x| x * 10).collect::<RoaringTreemap>());
+        let mut params = SearchParams::new(5);
+        params.filter(allowed.clone());
+        let results = search(&graph, 0, &query, 5, &params).unwrap();
+        assert!(!results.cap_reached);
+        assert!(results.search_size > 5);
+        assert!(results
+            .results
+            .iter()
+            .all(|(row_id, _)| allowed.contains(*row_id)));
+        assert_eq!(results.results.len(), 5);
+        assert_eq!(results.results[0].0, 90);
+
+        // Fewer allowed rows than k.
+        let mut params = SearchParams::new(5);
+        params.filter(Arc::new(RoaringTreemap::from_iter([3, 42])));
+        let results = search(&graph, 0, &query, 5, &params).unwrap();
+        assert!(results.cap_reached);
+        assert_eq!(results.results.len(), 2);
+    }
+
+    #[test]
+    fn test_search_fused() {
+        // Two clusters, around (0, 0) and (10, 10), in a fully connected graph.
+        let nodes = (0..40)
+            .map(|i| RowVertex::new(i as u64, None))
+            .collect::<Vec<_>>();
+        let values = (0..40)
+            .flat_map(|i| {
+                let center = if i < 20 { 0.0 } else { 10.0 };
+                [center + (i % 20) as f32 * 0.01, center]
+            })
+            .collect::<Vec<_>>();
+        let matrix = MatrixView::new(Arc::new(Float32Array::from(values)), 2);
+        let mut graph = GraphBuilder::new(&nodes, matrix, MetricType::L2);
+        for i in 0..40 {
+            for j in 0..40 {
+                if i != j {
+                    graph.add_neighbor(i, j);
+                }
+            }
+        }
+        let index = DiskANNGraph::new(graph, 0);
+        let queries: [&[f32]; 2] = [&[0.0, 0.0], &[10.0, 10.0]];
+
+        let results = index.search_fused(&queries, 4, 20, Fusion::MaxSim).unwrap();
+        let row_ids = results.iter().map(|(r, _)| *r).collect::<HashSet<_>>();
+        assert_eq!(row_ids, HashSet::from([0, 1, 20, 21]));
+        assert_eq!(results[0].1, 0.0);
+
+        let results = index.search_fused(&queries, 4, 20, Fusion::Sum).unwrap();
+        assert_eq!(results.len(), 4);
+        for (row_id, dist) in results {
+            let vertex = index.graph.vertex_for_row(row_id).unwrap();
+            let expected = queries
+                .iter()
+                .map(|q| index.graph.distance_to(q, vertex).unwrap())
+                .sum::<f32>();
+            assert_eq!(dist, expected);
+        }
+
+        assert!(index.search_fused(&[], 4, 20, Fusion::Sum).is_err());
+    }
+
+    #[test]
+    fn test_search_grouped() {
+        // Rows on a line, grouped by tens, in a fully connected graph.
+        let nodes = (0..40)
+            .map(|i| RowVertex::new(i as u64, None))
+            .collect::<Vec<_>>();
+        let values = (0..40).flat_map(|i| [i as f32, 0.0]).collect::<Vec<_>>();
+        let matrix = MatrixView::new(Arc::new(Float32Array::from(values)), 2);
+        let mut graph = GraphBuilder::new(&nodes, matrix, MetricType::L2);
+        for i in 0..40 {
+            for j in 0..40 {
+                if i != j {
+                    graph.add_neighbor(i, j);
+                }
+            }
+        }
+        let index = DiskANNGraph::new(graph, 0);
+        let query = [0.0, 0.0];
+
+        let top_k = search(&index.graph, 0, &query, 6, &SearchParams::new(40)).unwrap();
+        let top_k = top_k.results.iter().map(|(r, _)| *r).collect::<Vec<_>>();
+        assert_eq!(top_k, vec![0, 1, 2, 3, 4, 5]);
+
+        let results = index
+            .search_grouped(&query, 6, 40, |row_id| row_id / 10, 2)
+            .unwrap();
+        let row_ids = results.iter().map(|(r, _)| *r).collect::<Vec<_>>();
+        assert_eq!(row_ids, vec![0, 1, 10, 11, 20, 21]);
+
+        // Only four groups of two rows.
+        let results = index
+            .search_grouped(&query, 10, 40, |row_id| row_id / 10, 2)
+            .unwrap();
+        assert_eq!(results.len(), 8);
+
+        assert!(index.search_grouped(&query, 6, 40, |r| r, 0).is_err());
+    }
 }
@@ -16,10 +16,11 @@
 //!
 
 use std::any::Any;
+use std::collections::BinaryHeap;
 use std::sync::Arc;
 
 use arrow::array::as_primitive_array;
-use arrow::datatypes::Float32Type;
+use arrow::datatypes::{Float32Type, UInt64Type};
 use arrow_array::{cast::as_struct_array, ArrayRef, RecordBatch, StructArray};
 use arrow_ord::sort::sort_to_indices;
 use arrow_schema::{DataType, Field as ArrowField};
@@ -27,10 +28,11 @@ use arrow_select::{concat::concat_batches, take::take};
 use async_trait::async_trait;
 use futures::future;
 use futures::stream::{repeat_with, Stream, StreamExt, TryStreamExt};
+use ordered_float::OrderedFloat;
 
-use super::{Query, VectorIndex, SCORE_COL};
-use crate::arrow::*;
-use crate::dataset::Dataset;
+use super::{MetricType, Query, VectorIndex, SCORE_COL};
+use crate::arrow::{linalg::MatrixView, *};
+use crate::dataset::{Dataset, ROW_ID};
 use crate::io::object_reader::ObjectReader;
 use crate::{Error, Result};
 
@@ -65,12 +67,13 @@ pub async fn flat_search(
     stream: impl Stream<Item = Result<RecordBatch>>,
     query: &Query,
 ) -> Result<RecordBatch> {
+    query.metric_type.validate(query.key.len())?;
     let batches = stream
         .filter(|batch| {
             let pred = batch.as_ref().map(|b| b.num_rows() > 0).unwrap_or(false);
             future::ready(pred)
         })
-        .zip(repeat_with(|| query.metric_type))
+        .zip(repeat_with(|| query.metric_type.clone()))
         .map(|(batch, mt)| async move {
             let k = query.key.clone();
             let mut batch = batch?;
@@ -114,6 +117,88 @@ pub async fn flat_search(
     Ok(as_struct_array(&selected_arr).into())
 }
 
+/// Exact k-nearest neighbors of each query over a vector column, without any index.
+///
+/// The dataset is scanned once for the whole batch of queries. Each query keeps a
+/// bounded max-heap of its `k` best candidates, so the memory footprint is
+/// `O(num_queries * k)` plus one record batch, regardless of the dataset size.
+///
+/// It is mostly useful to compute the ground truth to measure the recall of
+/// approximate indices.
+///
+/// Returns `(row_id, distance)` pairs for each query, sorted by ascending distance.
+pub async fn brute_force_knn(
+    dataset: &Dataset,
+    column: &str,
+    queries: &MatrixView,
+    k: usize,
+    metric_type: MetricType,
+) -> Result<Vec<Vec<(u64, f32)>>> {
+    metric_type.validate(queries.num_columns())?;
+    let mut heaps: Vec<BinaryHeap<(OrderedFloat<f32>, u64)>> = (0..queries.num_rows())
+        .map(|_| BinaryHeap::with_capacity(k + 1))
+        .collect();
+
+    let mut stream = dataset
+        .scan()
+        .project(&[column])?
+        .with_row_id()
+        .try_into_stream()
+        .await?;
+
+    let dist_func = metric_type.batch_func();
+    while let Some(batch) = stream.next().await {
+        let batch = batch?;
+        if batch.num_rows() == 0 {
+            continue;
+        }
+        let row_ids = as_primitive_array::<UInt64Type>(
+            batch
+                .column_by_qualified_name(ROW_ID)
+                .ok_or_else(|| Error::Index("row_id not found".to_string()))?,
+        );
+        let vectors: MatrixView = as_fixed_size_list_array(
+            batch
+                .column_by_qualified_name(column)
+                .ok_or_else(|| Error::Index(format!("column {} not found", column)))?,
+        )
+        .try_into()?;
+        if vectors.num_columns() != queries.num_columns() {
+            return Err(Error::Index(format!(
+                "Query dimension {} does not match the dimension of column {}: {}",
+                queries.num_columns(),
+                column,
+                vectors.num_columns()
+            )));
+        }
+
+        let data = vectors.data();
+        for (i, heap) in heaps.iter_mut().enumerate() {
+            let query = queries.row(i).unwrap();
+            let dists = dist_func(query, data.values(), vectors.num_columns());
+            for (dist, row_id) in dists.values().iter().zip(row_ids.values().iter()) {
+                let dist = OrderedFloat(*dist);
+                if heap.len() < k {
+                    heap.push((dist, *row_id));
+                } else if heap.peek().map(|(d, _)| dist < *d).unwrap_or(false) {
+                    heap.pop();
+                    heap.push((dist, *row_id));
+                }
+            }
+        }
+    }
+
+    Ok(heaps
+        .into_iter()
+        .map(|heap| {
+            heap.into_sorted_vec()
+                .into_iter()
+                .map(|(dist, row_id)| (row_id, dist.0))
+                .collect()
+        })
+        .collect())
+}
+
 #[async_trait]
 impl VectorIndex for FlatIndex<'_> {
     /// Search the flat index.
@@ -146,3 +231,100 @@ impl VectorIndex for FlatIndex<'_> {
         Err(Error::Index("Flat index does not support load".to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{FixedSizeListArray, Float32Array, RecordBatchReader};
+    use arrow_schema::Schema as ArrowSchema;
+
+    use crate::linalg::l2::l2_distance;
+    use crate::utils::testing::generate_random_array;
+
+    #[tokio::test]
+    async fn test_brute_force_knn() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        const DIM: usize = 8;
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "vector",
+            DataType::FixedSizeList(
+                Arc::new(ArrowField::new("item", DataType::Float32, true)),
+                DIM as i32,
+            ),
+            true,
+        )]));
+        let data = generate_random_array(100 * DIM);
+        let batches = RecordBatchBuffer::new(vec![RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(
+                FixedSizeListArray::try_new(&data, DIM as i32).unwrap(),
+            )],
+        )
+        .unwrap()]);
+        let mut reader: Box<dyn RecordBatchReader> = Box::new(batches);
+        let dataset = Dataset::write(&mut reader, test_uri, None).await.unwrap();
+
+        let queries = MatrixView::random(3, DIM);
+        let results = brute_force_knn(&dataset, "vector", &queries, 5, MetricType::L2)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 3);
+
+        for (i, result) in results.iter().enumerate() {
+            let query = queries.row(i).unwrap();
+            let mut expected = data
+                .values()
+                .chunks(DIM)
+                .enumerate()
+                .map(|(row_id, v)| (row_id as u64, l2_distance(query, v)))
+                .collect::<Vec<_>>();
+            expected.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+            expected.truncate(5);
+            assert_eq!(result, &expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_weighted_l2() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "vector",
+            DataType::FixedSizeList(
+                Arc::new(ArrowField::new("item", DataType::Float32, true)),
+                2,
+            ),
+            true,
+        )]));
+        // The second dimension is noise.
+        let data = Float32Array::from(vec![0.5, 0.0, 0.0, 2.0]);
+        let batches = RecordBatchBuffer::new(vec![RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(FixedSizeListArray::try_new(&data, 2).unwrap())],
+        )
+        .unwrap()]);
+        let mut reader: Box<dyn RecordBatchReader> = Box::new(batches);
+        let dataset = Dataset::write(&mut reader, test_uri, None).await.unwrap();
+
+        let queries = MatrixView::new(Arc::new(Float32Array::from(vec![0.0, 0.0])), 2);
+        let results = brute_force_knn(&dataset, "vector", &queries, 1, MetricType::L2)
+            .await
+            .unwrap();
+        assert_eq!(results[0], vec![(0, 0.25)]);
+
+        let weighted = MetricType::WeightedL2(Arc::new(vec![1.0, 0.0]));
+        let results = brute_force_knn(&dataset, "vector", &queries, 1, weighted)
+            .await
+            .unwrap();
+        assert_eq!(results[0], vec![(1, 0.0)]);
+
+        let mismatched = MetricType::WeightedL2(Arc::new(vec![1.0, 0.0, 1.0]));
+        assert!(brute_force_knn(&dataset, "vector", &queries, 1, mismatched)
+            .await
+            .is_err());
+    }
+}
@@ -30,11 +30,19 @@ pub trait Graph {
 
     fn distance_to(&self, query: &[f32], idx: usize) -> Result<f32>;
 
+    /// Dimension of the vectors.
+    fn dimension(&self) -> usize;
+
     fn neighbors(&self, id: usize) -> Result<&[u32]>;
 }
 
 /// Vertex (metadata). It does not include the actual data.
-pub trait Vertex {}
+pub trait Vertex {
+    /// The row id in the dataset this vertex refers to, if any.
+    fn row_id(&self) -> Option<u64> {
+        None
+    }
+}
 
 /// Vertex SerDe. Used for serializing and deserializing the vertex.
 pub(crate) trait VertexSerDe<V: Vertex> {
@@ -49,6 +57,10 @@ pub(crate) trait VertexSerDe<V: Vertex> {
 }
 
 /// Vertex With Distance. Used for traversing the graph.
+///
+/// Ordered by distance, ascending. A `BinaryHeap<VertexWithDistance>` is thus a
+/// max-heap, popping the farthest vertex first; wrap it in [`std::cmp::Reverse`]
+/// for a min-heap popping the nearest vertex first.
 pub(crate) struct VertexWithDistance {
     /// Vertex ID.
     pub id: usize,
@@ -85,3 +97,36 @@ impl Ord for VertexWithDistance {
         self.distance.cmp(&other.distance)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cmp::Reverse;
+    use std::collections::BinaryHeap;
+
+    #[test]
+    fn test_vertex_with_distance_heap_order() {
+        let vertices = [(0, 2.0), (1, 0.5), (2, 3.0), (3, 1.0)];
+
+        let mut max_heap = vertices
+            .iter()
+            .map(|&(id, dist)| VertexWithDistance::new(id, dist))
+            .collect::<BinaryHeap<_>>();
+        let mut farthest_first = vec![];
+        while let Some(v) = max_heap.pop() {
+            farthest_first.push(v.id);
+        }
+        assert_eq!(farthest_first, vec![2, 0, 3, 1]);
+
+        let mut min_heap = vertices
+            .iter()
+            .map(|&(id, dist)| Reverse(VertexWithDistance::new(id, dist)))
+            .collect::<BinaryHeap<_>>();
+        let mut nearest_first = vec![];
+        while let Some(Reverse(v)) = min_heap.pop() {
+            nearest_first.push(v.id);
+        }
+        assert_eq!(nearest_first, vec![1, 3, 0, 2]);
+    }
+}
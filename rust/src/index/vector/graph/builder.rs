@@ -14,13 +14,23 @@
 
 //! Graph in memory.
 
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
+use arrow_array::{FixedSizeListArray, Float32Array, RecordBatch, UInt64Array};
+use arrow_schema::{DataType, Field, Schema};
+
 use super::{Graph, Vertex};
-use crate::arrow::linalg::MatrixView;
+use crate::arrow::{linalg::MatrixView, FixedSizeListArrayExt};
+use crate::dataset::ROW_ID;
 use crate::index::vector::MetricType;
+use crate::linalg::norm_l2::norm_l2;
 use crate::{Error, Result};
 
+/// Maximum number of vertices of [`GraphBuilder::distance_matrix`].
+const MAX_DISTANCE_MATRIX_VERTICES: usize = 4096;
+
 /// A graph node to hold the vertex data and its neighbors.
 #[derive(Debug)]
 pub(crate) struct Node<V: Vertex> {
@@ -41,11 +51,18 @@ pub(crate) struct GraphBuilder<V: Vertex + Clone> {
     /// Hold all vectors in memory for fast access at the moment.
     pub(crate) data: MatrixView,
 
+    /// Norms of the original vectors when `data` holds their normalized copy,
+    /// see [`GraphBuilder::normalize`].
+    norms: Option<Vec<f32>>,
+
     /// Metric type.
     metric_type: MetricType,
 
     /// Distance function.
     distance_func: Arc<dyn Fn(&[f32], &[f32]) -> f32 + Send + Sync>,
+
+    /// Reverse mapping from the row id of a vertex to its id, see [`Vertex::row_id()`].
+    row_index: HashMap<u64, u32>,
 }
 
 impl<'a, V: Vertex + Clone> GraphBuilder<V> {
@@ -59,15 +76,79 @@ impl<'a, V: Vertex + Clone> GraphBuilder<V> {
                 })
                 .collect(),
             data,
-            metric_type,
+            norms: None,
             distance_func: metric_type.func(),
+            metric_type,
+            row_index: build_row_index(vertices.iter()),
+        }
+    }
+
+    /// Replace the vectors by their normalized copy, e.g., to index the cosine
+    /// similarity with the L2 distance. The norms are kept to restore the original
+    /// vectors, see [`GraphBuilder::vector`]. Vectors with a zero norm are kept as is.
+    pub fn normalize(&mut self) {
+        if self.norms.is_none() {
+            let (data, norms) = normalize_rows(&self.data);
+            self.data = data;
+            self.norms = Some(norms);
+        }
+    }
+
+    /// The vectors are normalized, see [`GraphBuilder::normalize`].
+    pub fn is_normalized(&self) -> bool {
+        self.norms.is_some()
+    }
+
+    /// The vector of a vertex: the original one if `original` is set, otherwise the
+    /// one the distances are computed on, which is normalized if the graph is.
+    pub fn vector(&self, id: usize, original: bool) -> Result<Cow<'_, [f32]>> {
+        let vector = self.data.row(id).ok_or_else(|| {
+            Error::Index(format!(
+                "Vertex index is out of range: {} >= {}",
+                id,
+                self.nodes.len()
+            ))
+        })?;
+        match (&self.norms, original) {
+            (Some(norms), true) => Ok(Cow::Owned(vector.iter().map(|v| v * norms[id]).collect())),
+            _ => Ok(Cow::Borrowed(vector)),
         }
     }
 
+    /// The original vectors of all the vertices, see [`GraphBuilder::vector`].
+    pub fn original_data(&self) -> MatrixView {
+        let Some(norms) = self.norms.as_ref() else {
+            return self.data.clone();
+        };
+        let dim = self.data.num_columns();
+        let values = self
+            .data
+            .data()
+            .values()
+            .chunks(dim)
+            .zip(norms)
+            .flat_map(|(row, norm)| row.iter().map(move |v| v * norm))
+            .collect::<Vec<_>>();
+        MatrixView::new(Arc::new(Float32Array::from(values)), dim)
+    }
+
     pub fn len(&self) -> usize {
         self.nodes.len()
     }
 
+    pub fn metric_type(&self) -> MetricType {
+        self.metric_type.clone()
+    }
+
+    /// Compute the distances with `func` instead of the metric, e.g., an approximate
+    /// distance over compressed vectors.
+    pub(crate) fn set_distance_func(
+        &mut self,
+        func: Arc<dyn Fn(&[f32], &[f32]) -> f32 + Send + Sync>,
+    ) {
+        self.distance_func = func;
+    }
+
     pub fn is_empty(&self) -> bool {
         self.nodes.is_empty()
     }
@@ -76,23 +157,321 @@ impl<'a, V: Vertex + Clone> GraphBuilder<V> {
         &self.nodes[id].vertex
     }
 
+    /// Mutable access to a vertex. Its row id must not be changed.
     pub fn vertex_mut(&mut self, id: usize) -> &mut V {
         &mut self.nodes[id].vertex
     }
 
+    /// Estimate the memory, in bytes, of a graph of `row_count` vertices of `dimension`,
+    /// with `r` neighbors each.
+    ///
+    /// It counts the contiguous f32 vectors, the nodes, a neighbor list of capacity `r`
+    /// per vertex and the row id index, at the 7/8 load factor of [`HashMap`]. The
+    /// neighbor lists can temporarily exceed `r` during a build, before being pruned.
+    pub fn estimate_memory(row_count: usize, dimension: usize, r: usize) -> usize {
+        let vectors = row_count * dimension * std::mem::size_of::<f32>();
+        let nodes = row_count * std::mem::size_of::<Node<V>>();
+        let neighbors = row_count * r * std::mem::size_of::<u32>();
+        // One control byte per bucket.
+        let row_index = row_count * 8 / 7 * (std::mem::size_of::<(u64, u32)>() + 1);
+        vectors + nodes + neighbors + row_index
+    }
+
+    /// Find the vertex of a row id, without scanning the graph.
+    pub fn vertex_for_row(&self, row_id: u64) -> Option<usize> {
+        self.row_index.get(&row_id).map(|&id| id as usize)
+    }
+
+    /// Append vertices, and their vectors, to the graph. They have no neighbors yet.
+    /// The vectors are normalized if the graph is.
+    ///
+    /// The vectors are appended in place, see [`MatrixView::append`], so that
+    /// inserting the vectors batch by batch does not copy the graph each time.
+    ///
+    /// Returns the id of the first inserted vertex.
+    pub fn insert(&mut self, vertices: &[V], vectors: &MatrixView) -> Result<usize> {
+        if vertices.len() != vectors.num_rows() {
+            return Err(Error::Index(format!(
+                "Number of vertices ({}) does not match the number of vectors ({})",
+                vertices.len(),
+                vectors.num_rows()
+            )));
+        }
+        if vectors.num_columns() != self.data.num_columns() {
+            return Err(Error::Index(format!(
+                "Vector dimension {} does not match the dimension of the graph: {}",
+                vectors.num_columns(),
+                self.data.num_columns()
+            )));
+        }
+
+        let start = self.nodes.len();
+        let vectors = match self.norms.as_mut() {
+            Some(norms) => {
+                let (normalized, new_norms) = normalize_rows(vectors);
+                norms.extend(new_norms);
+                normalized
+            }
+            None => vectors.clone(),
+        };
+        self.data.append(&vectors)?;
+        for (i, vertex) in vertices.iter().enumerate() {
+            if let Some(row_id) = vertex.row_id() {
+                self.row_index.insert(row_id, (start + i) as u32);
+            }
+            self.nodes.push(Node {
+                vertex: vertex.clone(),
+                neighbors: Vec::new(),
+            });
+        }
+        Ok(start)
+    }
+
+    /// Remove the vertices in `removed`, and the edges pointing to them.
+    ///
+    /// The remaining vertices are renumbered in order. Returns the mapping from
+    /// the old vertex ids to the new ones, `None` for the removed vertices.
+    pub fn compact(&mut self, removed: &HashSet<usize>) -> Vec<Option<u32>> {
+        let mut next_id = 0;
+        let id_map = (0..self.nodes.len())
+            .map(|id| {
+                if removed.contains(&id) {
+                    None
+                } else {
+                    next_id += 1;
+                    Some(next_id - 1)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(norms) = self.norms.as_mut() {
+            let mut id = 0;
+            norms.retain(|_| {
+                id += 1;
+                id_map[id - 1].is_some()
+            });
+        }
+        let dim = self.data.num_columns();
+        let mut values = Vec::with_capacity(next_id as usize * dim);
+        let nodes = std::mem::take(&mut self.nodes);
+        for (id, mut node) in nodes.into_iter().enumerate() {
+            if id_map[id].is_none() {
+                continue;
+            }
+            node.neighbors = node
+                .neighbors
+                .iter()
+                .filter_map(|&n| id_map[n as usize])
+                .collect();
+            values.extend_from_slice(self.data.row(id).unwrap());
+            self.nodes.push(node);
+        }
+        self.data = MatrixView::new(Arc::new(Float32Array::from(values)), dim);
+        self.row_index = build_row_index(self.nodes.iter().map(|n| &n.vertex));
+        id_map
+    }
+
     pub fn neighbors_mut(&mut self, id: usize) -> &mut Vec<u32> {
         &mut self.nodes[id].neighbors
     }
 
-    /// Set neighbors of a node.
-    pub fn set_neighbors(&mut self, id: usize, neighbors: impl Into<Vec<u32>>) {
-        self.nodes[id].neighbors = neighbors.into();
+    /// Set neighbors of a node, reusing the memory of its neighbor list.
+    pub fn set_neighbors(&mut self, id: usize, neighbors: impl AsRef<[u32]>) {
+        let current = &mut self.nodes[id].neighbors;
+        current.clear();
+        current.extend_from_slice(neighbors.as_ref());
+    }
+
+    /// Set the capacity of each neighbor list to `capacity`, or to its length if
+    /// longer, to bound the memory of the vertices.
+    pub fn reserve_neighbors(&mut self, capacity: usize) {
+        for node in self.nodes.iter_mut() {
+            node.neighbors.shrink_to(capacity);
+            node.neighbors
+                .reserve_exact(capacity.saturating_sub(node.neighbors.len()));
+        }
     }
 
     /// Add a neighbor to a specific vertex.
     pub fn add_neighbor(&mut self, vertex: usize, neighbor: usize) {
         self.nodes[vertex].neighbors.push(neighbor as u32);
     }
+
+    /// Exact `k` nearest rows to the query, among the `candidates` row ids.
+    ///
+    /// The distances are computed to the original vectors if `original` is set,
+    /// otherwise to the normalized ones if the graph is, see [`GraphBuilder::vector`].
+    ///
+    /// Returns `(row_id, distance)` pairs sorted by distance. Candidates that are not
+    /// in the graph are ignored.
+    pub fn rank_within(
+        &self,
+        query: &[f32],
+        candidates: &[u64],
+        k: usize,
+        original: bool,
+    ) -> Result<Vec<(u64, f32)>> {
+        if query.len() != self.data.num_columns() {
+            return Err(Error::Index(format!(
+                "Query dimension {} does not match the dimension of the graph: {}",
+                query.len(),
+                self.data.num_columns()
+            )));
+        }
+        let mut ids = candidates
+            .iter()
+            .filter_map(|&row_id| self.vertex_for_row(row_id))
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids.dedup();
+
+        let mut results = ids
+            .into_iter()
+            .map(|id| {
+                let row_id = self.vertex(id).row_id().unwrap();
+                let vector = self.vector(id, original)?;
+                Ok((row_id, (self.distance_func)(query, &vector)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        results.sort_by(|a, b| a.1.total_cmp(&b.1));
+        results.truncate(k);
+        Ok(results)
+    }
+
+    /// Number of vertices reachable from `start`, following the edges, `start` included.
+    ///
+    /// Searches start from the medoid, so the vertices not reachable from it can not
+    /// be found.
+    pub fn reachable_count(&self, start: usize) -> usize {
+        if start >= self.nodes.len() {
+            return 0;
+        }
+        let mut visited = vec![false; self.nodes.len()];
+        visited[start] = true;
+        let mut stack = vec![start];
+        let mut count = 0;
+        while let Some(id) = stack.pop() {
+            count += 1;
+            for &neighbor in self.nodes[id].neighbors.iter() {
+                if !visited[neighbor as usize] {
+                    visited[neighbor as usize] = true;
+                    stack.push(neighbor as usize);
+                }
+            }
+        }
+        count
+    }
+
+    /// Number of connected components of the graph, ignoring the direction of the edges.
+    pub fn component_count(&self) -> usize {
+        // Union-find over the vertex ids.
+        fn find(parents: &mut [usize], mut id: usize) -> usize {
+            while parents[id] != id {
+                parents[id] = parents[parents[id]];
+                id = parents[id];
+            }
+            id
+        }
+
+        let mut parents = (0..self.nodes.len()).collect::<Vec<_>>();
+        let mut count = self.nodes.len();
+        for (id, node) in self.nodes.iter().enumerate() {
+            for &neighbor in node.neighbors.iter() {
+                let a = find(&mut parents, id);
+                let b = find(&mut parents, neighbor as usize);
+                if a != b {
+                    parents[a] = b;
+                    count -= 1;
+                }
+            }
+        }
+        count
+    }
+
+    /// Pairwise distances between the vertices, as a row-major `n x n` matrix,
+    /// where `n = vertex_ids.len()`.
+    ///
+    /// Meant for diagnostics over a small sample of vertices: at most
+    /// 4096 vertices are accepted.
+    pub fn distance_matrix(&self, vertex_ids: &[usize]) -> Result<Vec<f32>> {
+        let n = vertex_ids.len();
+        if n > MAX_DISTANCE_MATRIX_VERTICES {
+            return Err(Error::Index(format!(
+                "Distance matrix of {} vertices is too large, the maximum is {}",
+                n, MAX_DISTANCE_MATRIX_VERTICES
+            )));
+        }
+        let mut matrix = vec![0.0; n * n];
+        for i in 0..n {
+            for j in i + 1..n {
+                let dist = self.distance(vertex_ids[i], vertex_ids[j])?;
+                matrix[i * n + j] = dist;
+                matrix[j * n + i] = dist;
+            }
+        }
+        Ok(matrix)
+    }
+
+    /// Dump the vectors of the graph, in vertex order, as a [`RecordBatch`] of a
+    /// `vector` FixedSizeList column and a [`ROW_ID`] column.
+    ///
+    /// Row ids are null for the vertices without a row id.
+    pub fn to_record_batch(&self) -> Result<RecordBatch> {
+        let dim = self.data.num_columns() as i32;
+        let vectors = FixedSizeListArray::try_new(self.data.data().as_ref(), dim)?;
+        let row_ids = UInt64Array::from_iter(self.nodes.iter().map(|n| n.vertex.row_id()));
+        let schema = Schema::new(vec![
+            Field::new("vector", vectors.data_type().clone(), false),
+            Field::new(ROW_ID, DataType::UInt64, true),
+        ]);
+        Ok(RecordBatch::try_new(
+            Arc::new(schema),
+            vec![Arc::new(vectors), Arc::new(row_ids)],
+        )?)
+    }
+
+    /// Iterate over all the edges of the graph, as `(src, dst, distance)`.
+    ///
+    /// The distances are computed lazily, while iterating.
+    pub fn edges(&self) -> impl Iterator<Item = (u32, u32, f32)> + '_ {
+        self.nodes.iter().enumerate().flat_map(move |(src, node)| {
+            let vector = self.data.row(src).unwrap();
+            node.neighbors.iter().map(move |&dst| {
+                let neighbor = self.data.row(dst as usize).unwrap();
+                (src as u32, dst, (self.distance_func)(vector, neighbor))
+            })
+        })
+    }
+}
+
+/// Normalize the rows of a matrix, and return them with their norms.
+/// Rows with a zero norm are kept as is.
+fn normalize_rows(matrix: &MatrixView) -> (MatrixView, Vec<f32>) {
+    let dim = matrix.num_columns();
+    let mut values = Vec::with_capacity(matrix.num_rows() * dim);
+    let mut norms = Vec::with_capacity(matrix.num_rows());
+    for i in 0..matrix.num_rows() {
+        let row = matrix.row(i).unwrap();
+        let norm = norm_l2(row);
+        if norm > 0.0 {
+            values.extend(row.iter().map(|v| v / norm));
+            norms.push(norm);
+        } else {
+            values.extend_from_slice(row);
+            norms.push(1.0);
+        }
+    }
+    (
+        MatrixView::new(Arc::new(Float32Array::from(values)), dim),
+        norms,
+    )
+}
+
+fn build_row_index<'a, V: Vertex + 'a>(vertices: impl Iterator<Item = &'a V>) -> HashMap<u64, u32> {
+    vertices
+        .enumerate()
+        .filter_map(|(id, v)| v.row_id().map(|row_id| (row_id, id as u32)))
+        .collect()
 }
 
 impl<V: Vertex + Clone> Graph for GraphBuilder<V> {
@@ -126,6 +505,10 @@ impl<V: Vertex + Clone> Graph for GraphBuilder<V> {
         Ok((self.distance_func)(query, vector))
     }
 
+    fn dimension(&self) -> usize {
+        self.data.num_columns()
+    }
+
     fn neighbors(&self, id: usize) -> Result<&[u32]> {
         Ok(self.nodes[id].neighbors.as_slice())
     }
@@ -135,7 +518,13 @@ impl<V: Vertex + Clone> Graph for GraphBuilder<V> {
 mod tests {
     use approx::assert_relative_eq;
 
+    use arrow_array::{
+        cast::{as_fixed_size_list_array, as_primitive_array},
+        types::{Float32Type, UInt64Type},
+    };
+
     use super::*;
+    use crate::linalg::l2::l2_distance;
 
     #[derive(Debug, Clone)]
     struct FooVertex {
@@ -143,7 +532,11 @@ mod tests {
         val: f32,
     }
 
-    impl Vertex for FooVertex {}
+    impl Vertex for FooVertex {
+        fn row_id(&self) -> Option<u64> {
+            Some(self.id as u64 + 1000)
+        }
+    }
 
     #[test]
     fn test_construct_builder() {
@@ -163,4 +556,276 @@ mod tests {
         builder.vertex_mut(88).val = 22.0;
         assert_relative_eq!(builder.vertex(88).val, 22.0);
     }
+
+    #[test]
+    fn test_estimate_memory() {
+        let (n, dim, r) = (1000, 16, 10);
+        let nodes = (0..n)
+            .map(|v| FooVertex {
+                id: v as u32,
+                val: v as f32,
+            })
+            .collect::<Vec<_>>();
+        let mut builder = GraphBuilder::new(&nodes, MatrixView::random(n, dim), MetricType::L2);
+        for i in 0..n {
+            builder.set_neighbors(i, (1..=r).map(|j| ((i + j) % n) as u32).collect::<Vec<_>>());
+        }
+
+        let actual = builder.data.data().values().len() * std::mem::size_of::<f32>()
+            + builder.nodes.capacity() * std::mem::size_of::<Node<FooVertex>>()
+            + builder
+                .nodes
+                .iter()
+                .map(|n| n.neighbors.capacity() * std::mem::size_of::<u32>())
+                .sum::<usize>()
+            + builder.row_index.capacity() * (std::mem::size_of::<(u64, u32)>() + 1);
+        let estimate = GraphBuilder::<FooVertex>::estimate_memory(n, dim, r);
+        assert_relative_eq!(estimate as f32, actual as f32, max_relative = 0.1);
+    }
+
+    #[test]
+    fn test_edges() {
+        let nodes = (0..20)
+            .map(|v| FooVertex {
+                id: v as u32,
+                val: v as f32,
+            })
+            .collect::<Vec<_>>();
+        let mut builder = GraphBuilder::new(&nodes, MatrixView::random(20, 8), MetricType::L2);
+        for i in 0..20 {
+            for j in 1..=(i % 4) {
+                builder.add_neighbor(i, (i + j) % 20);
+            }
+        }
+
+        let total_degree = (0..20)
+            .map(|i| builder.neighbors(i).unwrap().len())
+            .sum::<usize>();
+        let edges = builder.edges().collect::<Vec<_>>();
+        assert_eq!(edges.len(), total_degree);
+        for (src, dst, dist) in edges {
+            assert!(builder.neighbors(src as usize).unwrap().contains(&dst));
+            assert_relative_eq!(dist, builder.distance(src as usize, dst as usize).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_row_index() {
+        let nodes = (0..10)
+            .map(|v| FooVertex {
+                id: v as u32,
+                val: v as f32,
+            })
+            .collect::<Vec<_>>();
+        let mut builder = GraphBuilder::new(&nodes, MatrixView::random(10, 8), MetricType::L2);
+        assert_eq!(builder.vertex_for_row(1005), Some(5));
+        assert_eq!(builder.vertex_for_row(5), None);
+
+        let new_nodes = (10..15)
+            .map(|v| FooVertex {
+                id: v as u32,
+                val: v as f32,
+            })
+            .collect::<Vec<_>>();
+        let new_vectors = MatrixView::random(5, 8);
+        let start = builder.insert(&new_nodes, &new_vectors).unwrap();
+        assert_eq!(start, 10);
+        assert_eq!(builder.len(), 15);
+        assert_eq!(builder.data.num_rows(), 15);
+        assert_eq!(builder.data.row(12).unwrap(), new_vectors.row(2).unwrap());
+        for id in 0..15 {
+            let row_id = builder.vertex(id).row_id().unwrap();
+            assert_eq!(builder.vertex_for_row(row_id), Some(id));
+        }
+
+        // Wrong dimension.
+        assert!(builder
+            .insert(&new_nodes, &MatrixView::random(5, 4))
+            .is_err());
+
+        builder.add_neighbor(0, 3);
+        builder.add_neighbor(0, 4);
+        builder.add_neighbor(4, 14);
+        let vector_4 = builder.data.row(4).unwrap().to_vec();
+        let id_map = builder.compact(&HashSet::from([1, 3]));
+        assert_eq!(id_map[1], None);
+        assert_eq!(id_map[4], Some(2));
+        assert_eq!(builder.len(), 13);
+        assert_eq!(builder.data.num_rows(), 13);
+        assert_eq!(builder.neighbors(0).unwrap(), &[2]);
+        assert_eq!(builder.neighbors(2).unwrap(), &[12]);
+        assert_eq!(builder.data.row(2).unwrap(), vector_4.as_slice());
+        assert_eq!(builder.vertex_for_row(1001), None);
+        assert_eq!(builder.vertex_for_row(1003), None);
+        for id in 0..13 {
+            let row_id = builder.vertex(id).row_id().unwrap();
+            assert_eq!(builder.vertex_for_row(row_id), Some(id));
+        }
+    }
+
+    #[test]
+    fn test_rank_within() {
+        let nodes = (0..50)
+            .map(|v| FooVertex {
+                id: v as u32,
+                val: v as f32,
+            })
+            .collect::<Vec<_>>();
+        let builder = GraphBuilder::new(&nodes, MatrixView::random(50, 8), MetricType::L2);
+        let query = MatrixView::random(1, 8);
+        let query = query.row(0).unwrap();
+
+        // Unknown row ids and duplicates are ignored.
+        let candidates = [1003, 1010, 1042, 1017, 1003, 5, 1025, 1031, 1008];
+        let results = builder.rank_within(query, &candidates, 4, false).unwrap();
+
+        let mut expected = [1003_u64, 1010, 1042, 1017, 1025, 1031, 1008]
+            .iter()
+            .map(|&row_id| {
+                let vector = builder.data.row(row_id as usize - 1000).unwrap();
+                (row_id, l2_distance(query, vector))
+            })
+            .collect::<Vec<_>>();
+        expected.sort_by(|a, b| a.1.total_cmp(&b.1));
+        expected.truncate(4);
+        assert_eq!(results.len(), 4);
+        for ((row_id, dist), (expected_row_id, expected_dist)) in results.iter().zip(expected) {
+            assert_eq!(*row_id, expected_row_id);
+            assert_relative_eq!(*dist, expected_dist);
+        }
+
+        assert!(builder
+            .rank_within(&[1.0; 4], &candidates, 4, false)
+            .is_err());
+    }
+
+    #[test]
+    fn test_normalize() {
+        let nodes = (0..20)
+            .map(|v| FooVertex {
+                id: v as u32,
+                val: v as f32,
+            })
+            .collect::<Vec<_>>();
+        let original = MatrixView::random(20, 8);
+        let mut builder = GraphBuilder::new(&nodes[..10], original.clone(), MetricType::L2);
+        builder.normalize();
+        assert!(builder.is_normalized());
+        for id in 0..10 {
+            assert_relative_eq!(norm_l2(builder.data.row(id).unwrap()), 1.0, epsilon = 1e-5);
+            let restored = builder.vector(id, true).unwrap();
+            for (a, b) in restored.iter().zip(original.row(id).unwrap()) {
+                assert_relative_eq!(a, b, epsilon = 1e-5);
+            }
+            assert_eq!(
+                builder.vector(id, false).unwrap().as_ref(),
+                builder.data.row(id).unwrap()
+            );
+        }
+
+        // Inserted vectors are normalized too.
+        let inserted = MatrixView::random(10, 8);
+        builder.insert(&nodes[10..], &inserted).unwrap();
+        assert_relative_eq!(norm_l2(builder.data.row(15).unwrap()), 1.0, epsilon = 1e-5);
+        let restored = builder.vector(15, true).unwrap();
+        assert_relative_eq!(restored[3], inserted.row(5).unwrap()[3], epsilon = 1e-5);
+
+        // The norms follow the compaction.
+        builder.compact(&HashSet::from([0, 1]));
+        let restored = builder.vector(13, true).unwrap();
+        assert_relative_eq!(restored[3], inserted.row(5).unwrap()[3], epsilon = 1e-5);
+        assert_eq!(builder.original_data().num_rows(), 18);
+
+        // Rank by the distances to the original vectors.
+        let query = MatrixView::random(1, 8);
+        let query = query.row(0).unwrap();
+        let results = builder.rank_within(query, &[1013, 1002], 2, true).unwrap();
+        for (row_id, dist) in results {
+            let id = builder.vertex_for_row(row_id).unwrap();
+            let expected = l2_distance(query, &builder.vector(id, true).unwrap());
+            assert_relative_eq!(dist, expected);
+        }
+        assert!(builder.vector(18, true).is_err());
+    }
+
+    #[test]
+    fn test_reachability() {
+        let nodes = (0..10)
+            .map(|v| FooVertex {
+                id: v as u32,
+                val: v as f32,
+            })
+            .collect::<Vec<_>>();
+        let mut builder = GraphBuilder::new(&nodes, MatrixView::random(10, 8), MetricType::L2);
+        assert_eq!(builder.reachable_count(0), 1);
+        assert_eq!(builder.component_count(), 10);
+
+        // Two clusters, {0..6} and {6..10}, with one-way edges in the first one.
+        for i in 0..5 {
+            builder.add_neighbor(i, i + 1);
+        }
+        for i in 6..9 {
+            builder.add_neighbor(i, i + 1);
+            builder.add_neighbor(i + 1, i);
+        }
+        assert_eq!(builder.reachable_count(0), 6);
+        assert_eq!(builder.reachable_count(3), 3);
+        assert_eq!(builder.reachable_count(7), 4);
+        assert_eq!(builder.component_count(), 2);
+
+        // Bridge the clusters.
+        builder.add_neighbor(5, 6);
+        assert_eq!(builder.reachable_count(0), 10);
+        assert_eq!(builder.component_count(), 1);
+    }
+
+    #[test]
+    fn test_distance_matrix() {
+        let nodes = (0..5)
+            .map(|v| FooVertex {
+                id: v as u32,
+                val: v as f32,
+            })
+            .collect::<Vec<_>>();
+        let values = Float32Array::from_iter_values((0..5).flat_map(|i| [i as f32, 0.0]));
+        let builder =
+            GraphBuilder::new(&nodes, MatrixView::new(Arc::new(values), 2), MetricType::L2);
+
+        let matrix = builder.distance_matrix(&[4, 1, 2]).unwrap();
+        assert_eq!(matrix, vec![0.0, 9.0, 4.0, 9.0, 0.0, 1.0, 4.0, 1.0, 0.0]);
+
+        assert!(builder.distance_matrix(&[]).unwrap().is_empty());
+        assert!(builder.distance_matrix(&[0, 5]).is_err());
+        assert!(builder.distance_matrix(&vec![0; 5000]).is_err());
+    }
+
+    #[test]
+    fn test_to_record_batch() {
+        let nodes = (0..10)
+            .map(|v| FooVertex {
+                id: v as u32,
+                val: v as f32,
+            })
+            .collect::<Vec<_>>();
+        let vectors = MatrixView::random(10, 4);
+        let values = vectors.data();
+        let head = MatrixView::new(Arc::new(values.values()[..24].to_vec().into()), 4);
+        let tail = MatrixView::new(Arc::new(values.values()[24..].to_vec().into()), 4);
+        let mut builder = GraphBuilder::new(&nodes[..6], head, MetricType::L2);
+        builder.insert(&nodes[6..], &tail).unwrap();
+
+        let batch = builder.to_record_batch().unwrap();
+        assert_eq!(batch.num_rows(), 10);
+        let dumped = as_fixed_size_list_array(batch.column_by_name("vector").unwrap());
+        assert_eq!(dumped.value_length(), 4);
+        assert_eq!(
+            as_primitive_array::<Float32Type>(dumped.values().as_ref()).values()[..],
+            vectors.data().values()[..]
+        );
+        let row_ids = as_primitive_array::<UInt64Type>(batch.column_by_name(ROW_ID).unwrap());
+        assert_eq!(
+            row_ids.values().to_vec(),
+            (1000..1010).collect::<Vec<u64>>()
+        );
+    }
 }
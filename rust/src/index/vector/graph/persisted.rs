@@ -250,6 +250,31 @@ pub(crate) async fn write_graph<V: Vertex + Clone>(
     Ok(())
 }
 
+/// Read all the vertices of a graph written by [`write_graph`], and their neighbors.
+pub(crate) async fn read_graph<V: Vertex>(
+    object_store: &ObjectStore,
+    path: &Path,
+    serde: &impl VertexSerDe<V>,
+) -> Result<(Vec<V>, Vec<Vec<u32>>)> {
+    let reader = FileReader::try_new(object_store, path).await?;
+    let batch = reader.read_range(0..reader.len(), reader.schema()).await?;
+    let vertices = as_fixed_size_binary_array(batch.column(0))
+        .iter()
+        .map(|bytes| {
+            serde.deserialize(bytes.ok_or_else(|| Error::Index("Invalid graph".to_string()))?)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let lists = as_list_array(batch.column(1));
+    let neighbors = (0..lists.len())
+        .map(|i| {
+            let value = lists.value(i);
+            let nb_array: &UInt32Array = as_primitive_array(value.as_ref());
+            nb_array.values().to_vec()
+        })
+        .collect();
+    Ok((vertices, neighbors))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -327,5 +352,11 @@ mod tests {
             neighbors.values(),
             &[88, 89, 90, 91, 92, 93, 94, 95, 96, 97]
         );
+
+        let (vertices, neighbors) = read_graph(&store, &path, &FooVertexSerDe {}).await.unwrap();
+        assert_eq!(vertices.len(), 100);
+        assert_eq!(vertices[42].row_id, 42);
+        assert_eq!(neighbors.len(), 100);
+        assert_eq!(neighbors[88], builder.nodes[88].neighbors);
     }
 }
@@ -119,7 +119,7 @@ impl VectorIndex for IVFIndex {
     async fn search(&self, query: &Query) -> Result<RecordBatch> {
         let partition_ids =
             self.ivf
-                .find_partitions(&query.key, query.nprobes, self.metric_type)?;
+                .find_partitions(&query.key, query.nprobes, self.metric_type.clone())?;
         assert!(partition_ids.len() <= query.nprobes as usize);
         let part_ids = partition_ids.values().to_vec();
         let batches = stream::iter(part_ids)
@@ -227,10 +227,8 @@ impl TryFrom<&IvfPQIndexMetadata> for pb::Index {
                 spec_version: 1,
                 dimension: idx.dimension,
                 stages,
-                metric_type: match idx.metric_type {
-                    MetricType::L2 => pb::VectorMetricType::L2.into(),
-                    MetricType::Cosine => pb::VectorMetricType::Cosine.into(),
-                },
+                metric_type: pb::VectorMetricType::from(&idx.metric_type).into(),
+                metric_weights: idx.metric_type.weights(),
             })),
         })
     }
@@ -523,11 +521,12 @@ pub async fn build_ivf_pq_index(
     }
 
     // Train IVF partitions.
-    let ivf_model = train_ivf_model(&training_data, metric_type, ivf_params).await?;
+    let ivf_model = train_ivf_model(&training_data, metric_type.clone(), ivf_params).await?;
 
     // Compute the residual vector for training PQ
     let ivf_centroids = ivf_model.centroids.as_ref().try_into()?;
-    let residual_data = compute_residual_matrix(&training_data, &ivf_centroids, metric_type)?;
+    let residual_data =
+        compute_residual_matrix(&training_data, &ivf_centroids, metric_type.clone())?;
     let pq_training_data = MatrixView::new(residual_data, training_data.num_columns());
 
     // The final train of PQ sub-vectors
@@ -540,7 +539,7 @@ pub async fn build_ivf_pq_index(
 
     let ivf = &ivf_model;
     let pq_ref = &pq;
-    let metric_type = pq_params.metric_type;
+    let metric_type = &pq_params.metric_type;
     let transform_ref = &transforms;
 
     // Scan the dataset and compute residual, pq with with partition ID.
@@ -561,17 +560,17 @@ pub async fn build_ivf_pq_index(
             }
 
             let i = ivf.clone();
-            let part_id_and_residual = tokio::task::spawn_blocking(move || {
-                i.compute_partition_and_residual(&vectors, metric_type)
-            })
-            .await??;
+            let mt = metric_type.clone();
+            let part_id_and_residual =
+                tokio::task::spawn_blocking(move || i.compute_partition_and_residual(&vectors, mt))
+                    .await??;
 
             let residual_col = part_id_and_residual
                 .column_by_name(RESIDUAL_COLUMN)
                 .unwrap();
             let residual_data = as_fixed_size_list_array(&residual_col);
             let pq_code = pq_ref
-                .transform(&residual_data.try_into()?, metric_type)
+                .transform(&residual_data.try_into()?, metric_type.clone())
                 .await?;
 
             let row_ids = batch
@@ -612,7 +611,7 @@ pub async fn build_ivf_pq_index(
         &transforms,
         ivf_model,
         pq,
-        metric_type,
+        metric_type.clone(),
         &batches,
     )
     .await
@@ -114,7 +114,7 @@ impl OptimizedProductQuantizer {
         let dim = train.num_columns();
 
         // Train few times to get a better rotation matrix. See Faiss.
-        pq.train(&train, metric_type, 1).await?;
+        pq.train(&train, metric_type.clone(), 1).await?;
         let pq_code = pq.transform(&train, metric_type).await?;
 
         // Reconstruct Y
@@ -149,7 +149,7 @@ pub(crate) async fn train_opq(
     let mut opq = OptimizedProductQuantizer::new(
         params.num_sub_vectors as usize,
         params.num_bits as u32,
-        params.metric_type,
+        params.metric_type.clone(),
         params.max_opq_iters,
     );
 
@@ -188,9 +188,9 @@ impl Transformer for OptimizedProductQuantizer {
 
         // Run a few iterations to get the initialized centroids.
         let mut pq = ProductQuantizer::new(self.num_sub_vectors, self.num_bits, dim);
-        pq.train(&train, self.metric_type, OPQ_PQ_INIT_ITERATIONS)
+        pq.train(&train, self.metric_type.clone(), OPQ_PQ_INIT_ITERATIONS)
             .await?;
-        let mut pq_code = pq.transform(&train, self.metric_type).await?;
+        let mut pq_code = pq.transform(&train, self.metric_type.clone()).await?;
 
         // Initialize R (rotation matrix)
         let mut rotation = init_rotation(dim)?;
@@ -200,14 +200,15 @@ impl Transformer for OptimizedProductQuantizer {
             // Reset the pq centroids after rotation.
             pq.reset_centroids(&rotated_data, &pq_code)?;
             (rotation, pq_code) = self
-                .train_once(&mut pq, &rotated_data, self.metric_type)
+                .train_once(&mut pq, &rotated_data, self.metric_type.clone())
                 .await?;
             if (i + 1) % 5 == 0 {
                 println!(
                     "Training OPQ iteration {}/{}, PQ distortion={}",
                     i + 1,
                     self.num_iters,
-                    pq.distortion(&rotated_data, self.metric_type).await?
+                    pq.distortion(&rotated_data, self.metric_type.clone())
+                        .await?
                 );
             }
         }
@@ -257,7 +257,7 @@ impl VectorIndex for PQIndex {
             code: Some(Arc::new(as_primitive_array(&pq_code).clone())),
             row_ids: Some(Arc::new(as_primitive_array(&row_ids).clone())),
             pq: self.pq.clone(),
-            metric_type: self.metric_type,
+            metric_type: self.metric_type.clone(),
         }))
     }
 }
@@ -367,7 +367,8 @@ impl ProductQuantizer {
             .collect::<Vec<_>>();
         let distortion = stream::iter(vectors)
             .zip(stream::iter(all_centroids))
-            .map(|(vec, centroid)| async move {
+            .zip(stream::repeat(metric_type))
+            .map(|((vec, centroid), metric_type)| async move {
                 tokio::task::spawn_blocking(move || {
                     let dist_func = metric_type.batch_func();
                     (0..vec.len())
@@ -463,7 +464,7 @@ impl ProductQuantizer {
                 max_iters as u32,
                 REDOS,
                 rng.clone(),
-                metric_type,
+                metric_type.clone(),
             )
             .await?;
             // TODO: COPIED COPIED COPIED
@@ -627,7 +628,8 @@ pub(crate) async fn train_pq(
         params.num_bits as u32,
         data.num_columns(),
     );
-    pq.train(data, params.metric_type, params.max_iters).await?;
+    pq.train(data, params.metric_type.clone(), params.max_iters)
+        .await?;
     Ok(pq)
 }
 
@@ -20,7 +20,7 @@ use std::{any::Any, sync::Arc};
 use arrow_array::RecordBatch;
 use async_trait::async_trait;
 
-use super::Query;
+use super::{diskann::SearchParams, MetricType, Query};
 use crate::{
     arrow::linalg::MatrixView,
     index::pb::Transform,
@@ -64,6 +64,23 @@ pub trait VectorIndex: Send + Sync + std::fmt::Debug {
     ) -> Result<Arc<dyn VectorIndex>>;
 }
 
+/// An index that can be searched with a query vector, regardless of its concrete type.
+#[async_trait]
+pub trait SearchIndex: Send + Sync {
+    /// Search the `k` nearest rows to the query.
+    ///
+    /// Returns `(row_id, distance)` pairs, sorted by distance.
+    async fn search(
+        &self,
+        query: &[f32],
+        k: usize,
+        params: &SearchParams,
+    ) -> Result<Vec<(u64, f32)>>;
+
+    /// The metric of the returned distances.
+    fn metric(&self) -> MetricType;
+}
+
 /// Transformer on vectors.
 #[async_trait]
 pub trait Transformer: std::fmt::Debug + Sync + Send {
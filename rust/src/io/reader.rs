@@ -38,7 +38,7 @@ use prost::Message;
 
 use super::ReadBatchParams;
 use crate::arrow::*;
-use crate::encodings::{dictionary::DictionaryDecoder, AsyncIndex};
+use crate::encodings::{compression::DecompressedPage, dictionary::DictionaryDecoder, AsyncIndex};
 use crate::error::{Error, Result};
 use crate::format::Manifest;
 use crate::format::{pb, Metadata, PageTable};
@@ -86,7 +86,7 @@ fn compute_row_id(fragment_id: u64, offset: i32) -> u64 {
 ///
 /// It reads arrow data from one data file.
 pub struct FileReader {
-    object_reader: Box<dyn ObjectReader>,
+    object_reader: Arc<dyn ObjectReader>,
     metadata: Metadata,
     page_table: PageTable,
     projection: Option<Schema>,
@@ -108,7 +108,7 @@ impl FileReader {
         fragment_id: u64,
         manifest: Option<&Manifest>,
     ) -> Result<FileReader> {
-        let object_reader = object_store.open(path).await?;
+        let object_reader: Arc<dyn ObjectReader> = object_store.open(path).await?.into();
 
         let file_size = object_reader.size().await?;
         let begin = if file_size < object_store.prefetch_size() {
@@ -132,7 +132,7 @@ impl FileReader {
         } else {
             let mut m: Manifest =
                 read_struct(object_reader.as_ref(), metadata.manifest_position.unwrap()).await?;
-            m.schema.load_dictionary(object_reader.as_ref()).await?;
+            m.schema.set_dictionary_reader(&object_reader);
             (m.schema.clone(), m.schema.max_field_id().unwrap() + 1)
         };
         let page_table = PageTable::load(
@@ -332,6 +332,38 @@ fn get_page_info<'a>(
     })
 }
 
+/// Read and decompress the page of a compressed field, with the position to decode
+/// it from. Returns `None` if the field is not compressed.
+async fn decompress_page(
+    reader: &FileReader,
+    field: &Field,
+    page_info: &PageInfo,
+) -> Result<Option<(DecompressedPage, usize)>> {
+    match field.compression() {
+        Some(compression) => Ok(Some(
+            DecompressedPage::read(
+                reader.object_reader.as_ref(),
+                &compression,
+                page_info.position,
+            )
+            .await?,
+        )),
+        None => Ok(None),
+    }
+}
+
+/// The reader and the position to decode a page from, see [`decompress_page`].
+fn page_source<'a>(
+    reader: &'a FileReader,
+    page_info: &PageInfo,
+    decompressed: &'a Option<(DecompressedPage, usize)>,
+) -> (&'a dyn ObjectReader, usize) {
+    match decompressed {
+        Some((page, position)) => (page as &dyn ObjectReader, *position),
+        None => (reader.object_reader.as_ref(), page_info.position),
+    }
+}
+
 /// Read primitive array for batch `batch_idx`.
 async fn _read_fixed_stride_array(
     reader: &FileReader,
@@ -340,11 +372,13 @@ async fn _read_fixed_stride_array(
     params: &ReadBatchParams,
 ) -> Result<ArrayRef> {
     let page_info = get_page_info(&reader.page_table, field, batch_id)?;
+    let decompressed = decompress_page(reader, field, page_info).await?;
+    let (object_reader, position) = page_source(reader, page_info, &decompressed);
 
     read_fixed_stride_array(
-        reader.object_reader.as_ref(),
+        object_reader,
         &field.data_type(),
-        page_info.position,
+        position,
         page_info.length,
         params.clone(),
     )
@@ -402,13 +436,15 @@ async fn read_binary_array(
     params: &ReadBatchParams,
 ) -> Result<ArrayRef> {
     let page_info = get_page_info(&reader.page_table, field, batch_id)?;
+    let decompressed = decompress_page(reader, field, page_info).await?;
+    let (object_reader, position) = page_source(reader, page_info, &decompressed);
 
     use crate::io::object_reader::read_binary_array;
     read_binary_array(
-        reader.object_reader.as_ref(),
+        object_reader,
         &field.data_type(),
         field.nullable,
-        page_info.position,
+        position,
         page_info.length,
         params,
     )
@@ -428,14 +464,7 @@ async fn read_dictionary_array(
         page_info.position,
         page_info.length,
         &data_type,
-        field
-            .dictionary
-            .as_ref()
-            .unwrap()
-            .values
-            .as_ref()
-            .unwrap()
-            .clone(),
+        field.dictionary_values().await?.clone(),
     );
     decoder.get(params.clone()).await
 }
@@ -525,10 +554,12 @@ where
     };
 
     let page_info = get_page_info(&reader.page_table, field, batch_id)?;
+    let decompressed = decompress_page(reader, field, page_info).await?;
+    let (object_reader, position) = page_source(reader, page_info, &decompressed);
     let position_arr = read_fixed_stride_array(
-        reader.object_reader.as_ref(),
+        object_reader,
         &T::DATA_TYPE,
-        page_info.position,
+        position,
         page_info.length,
         positions_params,
     )
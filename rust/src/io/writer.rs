@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use arrow_array::builder::{ArrayBuilder, PrimitiveBuilder};
@@ -24,7 +25,8 @@ use async_recursion::async_recursion;
 use object_store::path::Path;
 
 use crate::arrow::*;
-use crate::datatypes::{Field, Schema};
+use crate::datatypes::{Field, Schema, StatisticsCollector};
+use crate::encodings::compression::PageBuffer;
 use crate::encodings::dictionary::DictionaryEncoder;
 use crate::encodings::{binary::BinaryEncoder, plain::PlainEncoder, Encoder, Encoding};
 use crate::format::{pb, Index, Manifest, Metadata, PageInfo, PageTable};
@@ -44,26 +46,25 @@ pub async fn write_manifest(
     for field_id in 0..max_field_id + 1 {
         if let Some(field) = manifest.schema.mut_field_by_id(field_id) {
             if field.data_type().is_dictionary() {
+                let value_arr = field.dictionary_values().await?.clone();
                 let dict_info = field.dictionary.as_mut().ok_or_else(|| {
                     Error::IO(format!("Lance field {} misses dictionary info", field.name))
                 })?;
 
-                let value_arr = dict_info.values.as_ref().ok_or_else(|| {
-                    Error::IO(format!(
-                        "Lance field {} is dictionary type, but misses the dictionary value array",
-                        field.name
-                    ))
-                })?;
-
                 let data_type = value_arr.data_type();
                 let pos = match data_type {
-                    dt if dt.is_numeric() => {
+                    dt if dt.is_numeric()
+                        || matches!(
+                            dt,
+                            DataType::Decimal128(_, _) | DataType::Decimal256(_, _)
+                        ) =>
+                    {
                         let mut encoder = PlainEncoder::new(writer, dt);
-                        encoder.encode(&[value_arr]).await?
+                        encoder.encode(&[&value_arr]).await?
                     }
                     dt if dt.is_binary_like() => {
                         let mut encoder = BinaryEncoder::new(writer);
-                        encoder.encode(&[value_arr]).await?
+                        encoder.encode(&[&value_arr]).await?
                     }
                     _ => {
                         return Err(Error::IO(format!(
@@ -107,6 +108,11 @@ pub struct FileWriter<'a> {
     batch_id: i32,
     page_table: PageTable,
     metadata: Metadata,
+
+    /// Statistics of the values written so far, by field id.
+    statistics: HashMap<i32, StatisticsCollector>,
+
+    collect_statistics: bool,
 }
 
 impl<'a> FileWriter<'a> {
@@ -122,9 +128,19 @@ impl<'a> FileWriter<'a> {
             batch_id: 0,
             page_table: PageTable::default(),
             metadata: Metadata::default(),
+            statistics: HashMap::new(),
+            collect_statistics: true,
         })
     }
 
+    /// Collect the statistics of the values of each field, written in the schema of
+    /// the file, see [`Field::statistics`]. Enabled by default.
+    ///
+    /// It takes one pass over each written array.
+    pub fn set_collect_statistics(&mut self, collect: bool) {
+        self.collect_statistics = collect;
+    }
+
     /// Write a [RecordBatch] to the open file.
     /// All RecordBatch will be treated as one RecordBatch on disk
     ///
@@ -163,6 +179,13 @@ impl<'a> FileWriter<'a> {
     #[async_recursion]
     async fn write_array(&mut self, field: &Field, arrs: &[&ArrayRef]) -> Result<()> {
         assert!(!arrs.is_empty());
+        if self.collect_statistics {
+            let stats = self.statistics.entry(field.id).or_default();
+            for arr in arrs {
+                stats.update(arr)?;
+            }
+        }
+
         let data_type = arrs[0].data_type();
         let arrs_ref = arrs.iter().map(|a| a.as_ref()).collect::<Vec<_>>();
 
@@ -212,8 +235,20 @@ impl<'a> FileWriter<'a> {
         assert!(!arrs.is_empty());
         let data_type = arrs[0].data_type();
 
-        let mut encoder = PlainEncoder::new(&mut self.object_writer, data_type);
-        let pos = encoder.encode(arrs).await?;
+        let pos = match field.compression() {
+            Some(compression) => {
+                let mut page = PageBuffer::try_new().await?;
+                let pos = PlainEncoder::new(&mut page.writer, data_type)
+                    .encode(arrs)
+                    .await?;
+                page.write_compressed(&mut self.object_writer, &compression, pos)
+                    .await?
+            }
+            None => {
+                let mut encoder = PlainEncoder::new(&mut self.object_writer, data_type);
+                encoder.encode(arrs).await?
+            }
+        };
         let arrs_length: i32 = arrs.iter().map(|a| a.len() as i32).sum();
         let page_info = PageInfo::new(pos, arrs_length as usize);
         self.page_table.set(field.id, self.batch_id, page_info);
@@ -223,8 +258,18 @@ impl<'a> FileWriter<'a> {
     /// Write var-length binary arrays.
     async fn write_binary_array(&mut self, field: &Field, arrs: &[&dyn Array]) -> Result<()> {
         assert_eq!(field.encoding, Some(Encoding::VarBinary));
-        let mut encoder = BinaryEncoder::new(&mut self.object_writer);
-        let pos = encoder.encode(arrs).await?;
+        let pos = match field.compression() {
+            Some(compression) => {
+                let mut page = PageBuffer::try_new().await?;
+                let pos = BinaryEncoder::new(&mut page.writer).encode(arrs).await?;
+                page.write_compressed(&mut self.object_writer, &compression, pos)
+                    .await?
+            }
+            None => {
+                let mut encoder = BinaryEncoder::new(&mut self.object_writer);
+                encoder.encode(arrs).await?
+            }
+        };
         let arrs_length: i32 = arrs.iter().map(|a| a.len() as i32).sum();
         let page_info = PageInfo::new(pos, arrs_length as usize);
         self.page_table.set(field.id, self.batch_id, page_info);
@@ -345,8 +390,14 @@ impl<'a> FileWriter<'a> {
         let pos = self.page_table.write(&mut self.object_writer).await?;
         self.metadata.page_table_position = pos;
 
-        // Step 2. Write manifest and dictionary values.
-        let mut manifest = Manifest::new(self.schema, Arc::new(vec![]));
+        // Step 2. Write manifest, with the statistics of the fields, and dictionary values.
+        let mut schema = self.schema.clone();
+        for (field_id, stats) in self.statistics.drain() {
+            if let Some(field) = schema.mut_field_by_id(field_id) {
+                field.set_statistics(Some(stats.finish()));
+            }
+        }
+        let mut manifest = Manifest::new(&schema, Arc::new(vec![]));
         let pos = write_manifest(&mut self.object_writer, &mut manifest, None).await?;
 
         // Step 3. Write metadata.
@@ -367,8 +418,8 @@ mod tests {
     use arrow_array::{
         types::UInt32Type, BooleanArray, Decimal128Array, Decimal256Array, DictionaryArray,
         DurationMicrosecondArray, DurationMillisecondArray, DurationNanosecondArray,
-        DurationSecondArray, FixedSizeBinaryArray, FixedSizeListArray, Float32Array, Int64Array,
-        LargeListArray, ListArray, NullArray, StringArray, TimestampMicrosecondArray,
+        DurationSecondArray, FixedSizeBinaryArray, FixedSizeListArray, Float32Array, Int32Array,
+        Int64Array, LargeListArray, ListArray, NullArray, StringArray, TimestampMicrosecondArray,
         TimestampSecondArray, UInt8Array,
     };
     use arrow_buffer::i256;
@@ -377,6 +428,12 @@ mod tests {
     };
     use object_store::path::Path;
 
+    use arrow_array::types::Float32Type;
+    use arrow_select::concat::concat_batches;
+
+    use datafusion::scalar::ScalarValue;
+
+    use crate::encodings::compression::Compression;
     use crate::io::{FileReader, ObjectStore};
 
     #[tokio::test]
@@ -601,6 +658,39 @@ mod tests {
         assert_eq!(actual, batch);
     }
 
+    #[tokio::test]
+    async fn test_decimal_dictionary() {
+        let arrow_schema = ArrowSchema::new(vec![ArrowField::new(
+            "d",
+            DataType::Dictionary(
+                Box::new(DataType::Int32),
+                Box::new(DataType::Decimal128(7, 3)),
+            ),
+            true,
+        )]);
+        let mut schema = Schema::try_from(&arrow_schema).unwrap();
+
+        let values = Decimal128Array::from_iter_values([1000, 2500, 12345])
+            .with_precision_and_scale(7, 3)
+            .unwrap();
+        let keys = Int32Array::from_iter_values((0..100).map(|n| n % 3));
+        let dict_arr = DictionaryArray::<Int32Type>::try_new(&keys, &values).unwrap();
+
+        let columns: Vec<ArrayRef> = vec![Arc::new(dict_arr)];
+        let batch = RecordBatch::try_new(Arc::new(arrow_schema), columns).unwrap();
+        schema.set_dictionary(&batch).unwrap();
+
+        let store = ObjectStore::memory();
+        let path = Path::from("/foo");
+        let mut file_writer = FileWriter::try_new(&store, &path, &schema).await.unwrap();
+        file_writer.write(&[&batch]).await.unwrap();
+        file_writer.finish().await.unwrap();
+
+        let reader = FileReader::try_new(&store, &path).await.unwrap();
+        let actual = reader.read_batch(0, .., reader.schema()).await.unwrap();
+        assert_eq!(actual, batch);
+    }
+
     #[tokio::test]
     async fn test_write_temporal_types() {
         let arrow_schema = Arc::new(ArrowSchema::new(vec![
@@ -635,4 +725,273 @@ mod tests {
         let actual = reader.read_batch(0, .., reader.schema()).await.unwrap();
         assert_eq!(actual, batch);
     }
+
+    #[tokio::test]
+    async fn test_write_null_in_struct() {
+        let struct_type = DataType::Struct(ArrowFields::from(vec![
+            ArrowField::new("n", DataType::Null, true),
+            ArrowField::new("i", DataType::Int32, true),
+        ]));
+        let arrow_schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "s",
+            struct_type,
+            true,
+        )]));
+        let columns: Vec<ArrayRef> = vec![Arc::new(StructArray::from(vec![
+            (
+                ArrowField::new("n", DataType::Null, true),
+                Arc::new(NullArray::new(10)) as ArrayRef,
+            ),
+            (
+                ArrowField::new("i", DataType::Int32, true),
+                Arc::new(Int32Array::from_iter_values(0..10)) as ArrayRef,
+            ),
+        ]))];
+        let batch = RecordBatch::try_new(arrow_schema.clone(), columns).unwrap();
+
+        let schema = Schema::try_from(arrow_schema.as_ref()).unwrap();
+        let store = ObjectStore::memory();
+        let path = Path::from("/foo");
+        let mut file_writer = FileWriter::try_new(&store, &path, &schema).await.unwrap();
+        file_writer.write(&[&batch]).await.unwrap();
+        file_writer.finish().await.unwrap();
+
+        let reader = FileReader::try_new(&store, &path).await.unwrap();
+        assert_eq!(
+            reader.schema().fields[0].children[0].data_type(),
+            DataType::Null
+        );
+        let actual = reader.read_batch(0, .., reader.schema()).await.unwrap();
+        assert_eq!(actual, batch);
+        let actual = reader.read_batch(0, 2..5, reader.schema()).await.unwrap();
+        assert_eq!(actual, batch.slice(2, 3));
+    }
+
+    #[tokio::test]
+    async fn test_write_statistics() {
+        let arrow_schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int32, true),
+            ArrowField::new("s", DataType::Utf8, true),
+        ]));
+        let batches = [
+            (
+                vec![Some(5), None, Some(-3), Some(5)],
+                vec!["b", "a", "b", "c"],
+            ),
+            (
+                vec![Some(42), Some(0), None, None],
+                vec!["x", "a", "y", "z"],
+            ),
+        ]
+        .into_iter()
+        .map(|(ints, strs)| {
+            RecordBatch::try_new(
+                arrow_schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from(ints)),
+                    Arc::new(StringArray::from(strs)),
+                ],
+            )
+            .unwrap()
+        })
+        .collect::<Vec<_>>();
+
+        let schema = Schema::try_from(arrow_schema.as_ref()).unwrap();
+        let store = ObjectStore::memory();
+        let path = Path::from("/foo");
+        let mut file_writer = FileWriter::try_new(&store, &path, &schema).await.unwrap();
+        for batch in batches.iter() {
+            file_writer.write(&[batch]).await.unwrap();
+        }
+        file_writer.finish().await.unwrap();
+
+        let reader = FileReader::try_new(&store, &path).await.unwrap();
+        let stats = reader.schema().fields[0].statistics().unwrap();
+        assert_eq!(stats.null_count, 3);
+        assert_eq!(stats.distinct_count, 4);
+        assert_eq!(stats.min, Some(ScalarValue::Int32(Some(-3))));
+        assert_eq!(stats.max, Some(ScalarValue::Int32(Some(42))));
+
+        let stats = reader.schema().fields[1].statistics().unwrap();
+        assert_eq!(stats.null_count, 0);
+        assert_eq!(stats.distinct_count, 6);
+        assert_eq!(stats.min, Some(ScalarValue::Utf8(Some("a".to_string()))));
+        assert_eq!(stats.max, Some(ScalarValue::Utf8(Some("z".to_string()))));
+
+        // The schema to write with has no statistics.
+        assert!(schema.fields[0].statistics().is_none());
+
+        let mut file_writer = FileWriter::try_new(&store, &path, &schema).await.unwrap();
+        file_writer.set_collect_statistics(false);
+        file_writer.write(&[&batches[0]]).await.unwrap();
+        file_writer.finish().await.unwrap();
+        let reader = FileReader::try_new(&store, &path).await.unwrap();
+        assert!(reader.schema().fields[0].statistics().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_write_compressed_pages() {
+        let arrow_schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("s", DataType::Utf8, true),
+            ArrowField::new("i", DataType::Int64, true),
+            ArrowField::new(
+                "l",
+                DataType::List(Arc::new(ArrowField::new("item", DataType::Float32, true))),
+                true,
+            ),
+            ArrowField::new("u", DataType::Utf8, true),
+        ]));
+        let batches = (0..2)
+            .map(|b| {
+                let strings = (0..100)
+                    .map(|n| Some(format!("cold storage value {}", (b * 100 + n) % 7)))
+                    .collect::<StringArray>();
+                let columns: Vec<ArrayRef> = vec![
+                    Arc::new(strings.clone()),
+                    Arc::new(Int64Array::from_iter_values(
+                        (0..100).map(|n| (b * 100 + n) as i64),
+                    )),
+                    Arc::new(ListArray::from_iter_primitive::<Float32Type, _, _>(
+                        (0..100).map(|n| Some((0..n % 4).map(|v| Some(v as f32)))),
+                    )),
+                    Arc::new(strings),
+                ];
+                RecordBatch::try_new(arrow_schema.clone(), columns).unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let plain_schema = Schema::try_from(arrow_schema.as_ref()).unwrap();
+        let mut schema = plain_schema.clone();
+        schema.fields[0]
+            .set_compression(Some(Compression::Zstd { level: 3 }))
+            .unwrap();
+        schema.fields[1]
+            .set_compression(Some(Compression::Lz4))
+            .unwrap();
+        schema.fields[2]
+            .set_compression(Some(Compression::Zstd { level: 1 }))
+            .unwrap();
+        schema.fields[2].children[0]
+            .set_compression(Some(Compression::Lz4))
+            .unwrap();
+
+        let store = ObjectStore::memory();
+        let write = |path: &'static str, schema: Schema| {
+            let store = store.clone();
+            let batches = batches.clone();
+            async move {
+                let path = Path::from(path);
+                let mut file_writer = FileWriter::try_new(&store, &path, &schema).await.unwrap();
+                for batch in batches.iter() {
+                    file_writer.write(&[batch]).await.unwrap();
+                }
+                file_writer.finish().await.unwrap();
+                store.inner.head(&path).await.unwrap().size
+            }
+        };
+        let compressed_size = write("/compressed", schema).await;
+        let plain_size = write("/plain", plain_schema).await;
+        assert!(compressed_size < plain_size);
+
+        let reader = FileReader::try_new(&store, &Path::from("/compressed"))
+            .await
+            .unwrap();
+        let read_schema = reader.schema();
+        assert_eq!(
+            read_schema.fields[0].compression(),
+            Some(Compression::Zstd { level: 3 })
+        );
+        assert_eq!(read_schema.fields[3].compression(), None);
+        for (batch_id, batch) in batches.iter().enumerate() {
+            let actual = reader
+                .read_batch(batch_id as i32, .., read_schema)
+                .await
+                .unwrap();
+            assert_eq!(&actual, batch);
+            let actual = reader
+                .read_batch(batch_id as i32, 10..20, read_schema)
+                .await
+                .unwrap();
+            assert_eq!(actual, batch.slice(10, 10));
+        }
+        let actual = reader.take(&[1, 42, 150], read_schema).await.unwrap();
+        let expected = concat_batches(
+            &arrow_schema,
+            &[
+                batches[0].slice(1, 1),
+                batches[0].slice(42, 1),
+                batches[1].slice(50, 1),
+            ],
+        )
+        .unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[tokio::test]
+    async fn test_illegal_encoding_does_not_reach_writer() {
+        let arrow_schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int32, true),
+            ArrowField::new("s", DataType::Utf8, true),
+        ]));
+        let mut schema = Schema::try_from(arrow_schema.as_ref()).unwrap();
+        for encoding in [Encoding::RLE, Encoding::VarBinary, Encoding::Dictionary] {
+            assert!(schema.fields[0].set_encoding(encoding).is_err());
+        }
+        for encoding in [Encoding::RLE, Encoding::Plain, Encoding::Dictionary] {
+            assert!(schema.fields[1].set_encoding(encoding).is_err());
+        }
+
+        // The rejected encodings leave the writable defaults in place.
+        let batch = RecordBatch::try_new(
+            arrow_schema,
+            vec![
+                Arc::new(Int32Array::from_iter_values(vec![7; 100])),
+                Arc::new(StringArray::from(vec![None as Option<&str>; 100])),
+            ],
+        )
+        .unwrap();
+        let store = ObjectStore::memory();
+        let path = Path::from("/illegal_encoding");
+        let mut file_writer = FileWriter::try_new(&store, &path, &schema).await.unwrap();
+        file_writer.write(&[&batch]).await.unwrap();
+        file_writer.finish().await.unwrap();
+
+        let reader = FileReader::try_new(&store, &path).await.unwrap();
+        assert_eq!(
+            reader.read_batch(0, .., reader.schema()).await.unwrap(),
+            batch
+        );
+    }
+
+    #[test]
+    fn test_compression_requires_plain_or_var_binary() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new(
+                "d",
+                DataType::Dictionary(Box::new(DataType::UInt32), Box::new(DataType::Utf8)),
+                true,
+            ),
+            ArrowField::new(
+                "s",
+                DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+                    "si",
+                    DataType::Int64,
+                    true,
+                )])),
+                true,
+            ),
+        ]);
+        let mut schema = Schema::try_from(&arrow_schema).unwrap();
+        assert!(schema.fields[0]
+            .set_compression(Some(Compression::Lz4))
+            .is_err());
+        assert!(schema.fields[1]
+            .set_compression(Some(Compression::Lz4))
+            .is_err());
+        schema.fields[1].children[0]
+            .set_compression(Some(Compression::Lz4))
+            .unwrap();
+        schema.fields[1].children[0].set_compression(None).unwrap();
+        assert_eq!(schema.fields[1].children[0].compression(), None);
+    }
 }
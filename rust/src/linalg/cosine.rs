@@ -61,7 +61,6 @@ impl Cosine for [f32] {
 }
 
 /// Fallback non-SIMD implementation
-#[allow(dead_code)] // Does not fallback on aarch64.
 #[inline]
 fn cosine_scalar<T: Real + Sum>(x: &[T], x_norm: T, y: &[T]) -> T {
     let y_sq = dot(y, y);
@@ -75,6 +74,14 @@ pub fn cosine_distance(from: &[f32], to: &[f32]) -> f32 {
     from.cosine(to)
 }
 
+/// Cosine distance between two vectors with scalar operations, summed in order.
+///
+/// Unlike [`cosine_distance`], the result does not depend on the SIMD width of the CPU,
+/// so the decisions taken on it are the same on every machine.
+pub fn cosine_distance_scalar(from: &[f32], to: &[f32]) -> f32 {
+    cosine_scalar(from, dot(from, from).sqrt(), to)
+}
+
 /// Cosine Distance
 ///
 /// <https://en.wikipedia.org/wiki/Cosine_similarity>
@@ -178,6 +185,10 @@ mod tests {
         let d = cosine_distance_batch(x.values(), y.values(), 8);
         // from sklearn.metrics.pairwise import cosine_similarity
         assert_relative_eq!(d.value(0), 1.0 - 0.8735806510613104);
+        assert_relative_eq!(
+            cosine_distance_scalar(x.values(), y.values()),
+            1.0 - 0.8735806510613104
+        );
     }
 
     #[test]
@@ -235,7 +235,7 @@ impl KMeanMembership {
             centroids: Arc::new(as_primitive_array(centroids.as_ref()).clone()),
             dimension,
             k: self.k,
-            metric_type: self.metric_type,
+            metric_type: self.metric_type.clone(),
         })
     }
 
@@ -332,7 +332,7 @@ impl KMeans {
     ) -> Self {
         // TODO: refactor kmeans to work with reference instead of Arc?
         let data = Arc::new(data.clone());
-        let mut best_kmeans = Self::empty(k, dimension, params.metric_type);
+        let mut best_kmeans = Self::empty(k, dimension, params.metric_type.clone());
         let mut best_stddev = f32::MAX;
 
         let rng = rand::rngs::SmallRng::from_entropy();
@@ -340,15 +340,21 @@ impl KMeans {
         for _ in 1..=params.redos {
             let mut kmeans = if let Some(centroids) = params.centroids.as_ref() {
                 // Use existing centroids.
-                KMeans::with_centroids(centroids.clone(), k, dimension, params.metric_type)
+                KMeans::with_centroids(centroids.clone(), k, dimension, params.metric_type.clone())
             } else {
                 match params.init {
                     KMeanInit::Random => {
-                        Self::init_random(&mat, k, params.metric_type, rng.clone()).await
+                        Self::init_random(&mat, k, params.metric_type.clone(), rng.clone()).await
                     }
                     KMeanInit::KMeanPlusPlus => {
-                        kmean_plusplus(data.clone(), dimension, k, rng.clone(), params.metric_type)
-                            .await
+                        kmean_plusplus(
+                            data.clone(),
+                            dimension,
+                            k,
+                            rng.clone(),
+                            params.metric_type.clone(),
+                        )
+                        .await
                     }
                 }
             };
@@ -402,12 +408,17 @@ impl KMeans {
     async fn compute_membership(&self, data: Arc<Float32Array>) -> KMeanMembership {
         let dimension = self.dimension;
         let n = data.len() / self.dimension;
-        let metric_type = self.metric_type;
         let cluster_with_distances = stream::iter(0..n)
             // make tiles of input data to split between threads.
             .chunks(1024)
-            .zip(repeat_with(|| (data.clone(), self.centroids.clone())))
-            .map(|(indices, (data, centroids))| async move {
+            .zip(repeat_with(|| {
+                (
+                    data.clone(),
+                    self.centroids.clone(),
+                    self.metric_type.clone(),
+                )
+            }))
+            .map(|(indices, (data, centroids, metric_type))| async move {
                 let data = tokio::task::spawn_blocking(move || {
                     let dist = metric_type.batch_func();
                     let mut results = vec![];
@@ -443,7 +454,7 @@ impl KMeans {
                 .map(|(_, d)| *d)
                 .collect(),
             k: self.k,
-            metric_type: self.metric_type,
+            metric_type: self.metric_type.clone(),
         }
     }
 }